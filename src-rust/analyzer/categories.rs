@@ -0,0 +1,166 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::anyhow;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use tracing::debug;
+
+use crate::{
+    archive, imaging,
+    models::{metadata::CategoryMetadata, prelude::*},
+    AppState,
+};
+
+/// Resolve the cover of every category whose cover is missing or outdated.
+///
+/// The `cover` of the category TOML wins, relative to the category directory. Otherwise the
+/// covers of the first four titles make a mosaic, or the cover of the first title is used
+/// when there are fewer of them or they can't be decoded.
+///
+/// Returns how many category covers were updated.
+pub async fn analyze_category_covers(app_state: &AppState) -> anyhow::Result<usize> {
+    let categories = Categories::find()
+        .all(&app_state.db)
+        .await
+        .map_err(|e| anyhow!("Can't find categories: {}", e))?;
+
+    let mut analyzed = 0;
+    for category in categories {
+        let titles = Titles::find()
+            .filter(titles::Column::CategoryId.eq(&category.id))
+            .filter(titles::Column::DuplicateOf.is_null())
+            .order_by_asc(titles::Column::Title)
+            .all(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't find titles: {}", e))?;
+        let Some(category_path) = titles
+            .first()
+            .and_then(|title| Path::new(&title.path).parent())
+            .map(|path| path.to_path_buf())
+        else {
+            continue;
+        };
+
+        let mut covers = Covers::find()
+            .filter(covers::Column::Id.is_in(titles.iter().map(|title| &title.id)))
+            .all(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't find covers: {}", e))?
+            .into_iter()
+            .map(|cover| (cover.id.clone(), cover))
+            .collect::<HashMap<_, _>>();
+        let covered = titles
+            .into_iter()
+            .filter_map(|title| covers.remove(&title.id).map(|cover| (title, cover)))
+            .collect::<Vec<_>>();
+
+        let file = tokio::task::spawn_blocking(move || {
+            CategoryMetadata::from(&category_path)
+                .ok()
+                .and_then(|metadata| metadata.cover)
+                .map(|cover| category_path.join(cover))
+                .filter(|path| path.is_file())
+        })
+        .await?;
+        let wanted = match (&file, covered.len()) {
+            (Some(path), _) => (
+                CategoryCoverSource::File,
+                path.to_string_lossy().to_string(),
+            ),
+            (None, 0) => continue,
+            (None, count) if count >= imaging::MOSAIC_COVERS => (
+                CategoryCoverSource::Mosaic,
+                join_ids(&covered[..imaging::MOSAIC_COVERS]),
+            ),
+            (None, _) => (CategoryCoverSource::Title, join_ids(&covered[..1])),
+        };
+
+        let existing = CategoryCovers::find_by_id(&category.id)
+            .one(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't find category cover: {}", e))?;
+        let is_current = existing.as_ref().is_some_and(|existing| {
+            (existing.source, existing.path.clone()) == wanted
+                && (existing.source != CategoryCoverSource::Title
+                    || covered.first().is_some_and(|(_, cover)| {
+                        (&cover.blurhash, cover.ratio) == (&existing.blurhash, existing.ratio)
+                    }))
+        });
+        if is_current {
+            continue;
+        }
+
+        let first = covered.first().cloned();
+        let generated = match wanted.0 {
+            CategoryCoverSource::Title => None,
+            source => {
+                let path = wanted.1.clone();
+                let generated = tokio::task::spawn_blocking(move || {
+                    let image = match source {
+                        CategoryCoverSource::File => imaging::decode(&std::fs::read(&path)?)?.0,
+                        _ => imaging::mosaic(
+                            &covered
+                                .iter()
+                                .take(imaging::MOSAIC_COVERS)
+                                .map(|(title, cover)| {
+                                    Ok(imaging::decode(&archive::read_cover(&title.path, cover)?)?
+                                        .0)
+                                })
+                                .collect::<anyhow::Result<Vec<_>>>()?,
+                        ),
+                    };
+                    anyhow::Ok((imaging::blurhash(&image)?, image.width(), image.height()))
+                })
+                .await?;
+
+                match generated {
+                    Ok((blurhash, width, height)) => Some((
+                        source,
+                        wanted.1,
+                        blurhash,
+                        width * app_state.config.ratio_percision / height.max(1),
+                    )),
+                    Err(e) => {
+                        debug!("can't generate the cover of {}: {}", category.name, e);
+                        None
+                    }
+                }
+            }
+        };
+
+        // a title cover already has a blurhash and ratio
+        let (source, path, blurhash, ratio) = match (generated, first) {
+            (Some(generated), _) => generated,
+            (None, Some((title, cover))) => (
+                CategoryCoverSource::Title,
+                title.id.to_string(),
+                cover.blurhash,
+                cover.ratio,
+            ),
+            (None, None) => continue,
+        };
+
+        let category_cover = category_covers::ActiveModel {
+            id: Set(category.id.clone()),
+            source: Set(source),
+            path: Set(path),
+            blurhash: Set(blurhash),
+            ratio: Set(ratio),
+        };
+        match existing {
+            Some(_) => category_cover.update(&app_state.db).await.map(|_| ()),
+            None => category_cover.insert(&app_state.db).await.map(|_| ()),
+        }
+        .map_err(|e| anyhow!("Can't save category cover: {}", e))?;
+        analyzed += 1;
+    }
+
+    Ok(analyzed)
+}
+
+fn join_ids(covered: &[(titles::Model, covers::Model)]) -> String {
+    covered
+        .iter()
+        .map(|(title, _)| title.id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::anyhow;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use tracing::{debug, warn};
+
+use crate::{
+    models::{
+        cover_rules::{pick_cover, rules_hash, CoverRule},
+        metadata::{CategoryMetadata, TitleMetadata},
+        prelude::*,
+    },
+    AppState,
+};
+
+/// Pick the cover of every title that the current cover rules haven't been applied to yet,
+/// once all of its pages are analyzed.
+///
+/// The `cover` of the title TOML wins, then the `cover_rules` of the category TOML or
+/// `COVER_RULES`. Pages that can't be decoded are never picked. The rule that picked each
+/// cover is saved along with it, and a hash of the rules so they're applied again when they
+/// change.
+///
+/// Returns how many covers were picked.
+pub async fn analyze_title_covers(app_state: &AppState) -> anyhow::Result<usize> {
+    let covers = Covers::find()
+        .find_also_related(Titles)
+        .filter(covers::Column::IsCustom.eq(false))
+        .all(&app_state.db)
+        .await
+        .map_err(|e| anyhow!("Can't find covers: {}", e))?;
+
+    let mut category_rules = HashMap::new();
+    let mut analyzed = 0;
+    for (cover, title) in covers {
+        let Some(title) = title else {
+            continue;
+        };
+
+        let category_path = Path::new(&title.path)
+            .parent()
+            .map(|path| path.to_path_buf())
+            .unwrap_or_default();
+        if !category_rules.contains_key(&category_path) {
+            let path = category_path.clone();
+            let rules = tokio::task::spawn_blocking(move || {
+                CategoryMetadata::from(&path)
+                    .ok()
+                    .and_then(|metadata| metadata.cover_rules)
+            })
+            .await?;
+            // unlike `COVER_RULES`, nothing else checks the patterns of a category TOML
+            for rule in rules.iter().flatten() {
+                if let CoverRule::Regex { pattern } = rule {
+                    if let Some(e) = pattern.error() {
+                        warn!("invalid cover regex in {}: {}", category_path.display(), e);
+                    }
+                }
+            }
+            let rules = rules.unwrap_or_else(|| app_state.config.cover_rules.clone());
+            let hash = rules_hash(&rules);
+            category_rules.insert(category_path.clone(), (rules, hash));
+        }
+        let (rules, hash) = &category_rules[&category_path];
+        if cover.rule.is_some() && cover.rules_hash.as_ref() == Some(hash) {
+            continue;
+        }
+
+        let pages = Pages::find()
+            .filter(pages::Column::TitleId.eq(&title.id))
+            .order_by_asc(pages::Column::Path)
+            .all(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't find pages: {}", e))?;
+        if pages.iter().any(|page| page.blurhash.is_none()) {
+            continue;
+        }
+        let pages = pages
+            .into_iter()
+            .filter(|page| page.blurhash.as_ref().is_some_and(|b| !b.is_empty()))
+            .collect::<Vec<_>>();
+
+        let title_path = title.path.clone();
+        let toml_cover = tokio::task::spawn_blocking(move || {
+            TitleMetadata::from(Path::new(&title_path))
+                .ok()
+                .and_then(|metadata| metadata.cover)
+        })
+        .await?;
+        let picked = match toml_cover.and_then(|path| pages.iter().find(|page| page.path == path)) {
+            Some(page) => Some((page, "toml")),
+            None => pick_cover(rules, &pages).map(|(page, rule)| (page, rule.name())),
+        };
+        // without a match, the cover the scanner picked is kept
+        let rule = picked.map(|(_, rule)| rule).unwrap_or("none");
+
+        let mut active_cover = covers::ActiveModel {
+            id: Set(cover.id.clone()),
+            rule: Set(Some(rule.to_string())),
+            rules_hash: Set(Some(hash.clone())),
+            ..Default::default()
+        };
+        if let Some((page, _)) = picked.filter(|(page, _)| page.path != cover.path) {
+            if let (Some(blurhash), Some(width), Some(height)) =
+                (&page.blurhash, page.width, page.height)
+            {
+                active_cover.path = Set(page.path.clone());
+                active_cover.blurhash = Set(blurhash.clone());
+                active_cover.ratio = Set(width * app_state.config.ratio_percision / height.max(1));
+            }
+        }
+        active_cover
+            .update(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't update cover: {}", e))?;
+
+        debug!("cover of {} picked by {}", title.title, rule);
+        analyzed += 1;
+    }
+
+    Ok(analyzed)
+}
@@ -0,0 +1,73 @@
+mod categories;
+mod covers;
+mod pages;
+mod people;
+mod settings;
+
+use std::{sync::Arc, time::Duration};
+
+use tracing::{debug, error};
+
+use crate::AppState;
+
+/// How often the analyzer looks for rows the scanner left incomplete.
+const ANALYZE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fills in the details of library rows that are too slow to compute while scanning,
+/// e.g. decoding every page of every archive.
+///
+/// Each pass only picks up rows it hasn't processed yet, so it's cheap to run after every scan.
+pub struct Analyzer {
+    app_state: Arc<AppState>,
+}
+
+impl Analyzer {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        loop {
+            // rows may still be inserted or removed while the scanner runs
+            if *self.app_state.scanning_complete.lock().await {
+                if let Err(e) = self.analyze().await {
+                    error!("analyzer error: {}", e);
+                }
+            }
+            tokio::time::sleep(ANALYZE_INTERVAL).await;
+        }
+    }
+
+    async fn analyze(&self) -> anyhow::Result<()> {
+        let analyzed = settings::analyze_title_settings(&self.app_state.db).await?;
+        if analyzed > 0 {
+            debug!("read the settings of {} titles", analyzed);
+        }
+
+        let analyzed = pages::analyze_pages(&self.app_state).await?;
+        if analyzed > 0 {
+            debug!("analyzed {} pages", analyzed);
+        }
+
+        // once pages have their dimensions, which some cover rules need
+        let analyzed = covers::analyze_title_covers(&self.app_state).await?;
+        if analyzed > 0 {
+            debug!("picked {} covers", analyzed);
+        }
+
+        // after the pages and covers, as animated covers get their blurhash from them
+        let analyzed = categories::analyze_category_covers(&self.app_state).await?;
+        if analyzed > 0 {
+            debug!("updated {} category covers", analyzed);
+        }
+
+        let analyzed = people::analyze_title_people(&self.app_state.db).await?;
+        if analyzed > 0 {
+            debug!("credited the people of {} titles", analyzed);
+        }
+
+        // the scanner may have added, removed or renamed titles and tags since the last pass
+        self.app_state.search_cache.clear().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, io::Read, path::Path};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use tracing::warn;
+
+use crate::{archive::ArchiveIndex, config::Config, imaging, models::prelude::*, AppState};
+
+#[derive(Debug, Clone, Default)]
+struct PageDetails {
+    width: Option<u32>,
+    height: Option<u32>,
+    size: Option<i64>,
+    blurhash: String,
+    slices: Option<String>,
+    is_animated: bool,
+    phash: Option<i64>,
+}
+
+/// Record the dimensions, size, blurhash, spread flag, slices, animation flag and perceptual
+/// hash of every page that doesn't have them yet.
+///
+/// Animated pages and videos are analyzed from their first frame, which also replaces the
+/// blurhash of the cover when it is one of them.
+///
+/// Returns how many pages were analyzed.
+pub async fn analyze_pages(app_state: &AppState) -> anyhow::Result<usize> {
+    let pending = Pages::find()
+        .filter(
+            Condition::any()
+                .add(pages::Column::Blurhash.is_null())
+                .add(pages::Column::PhashPending.eq(true)),
+        )
+        .all(&app_state.db)
+        .await
+        .map_err(|e| anyhow!("Can't find pages: {}", e))?;
+
+    let mut by_title: HashMap<TitleID, Vec<pages::Model>> = HashMap::new();
+    for page in pending {
+        by_title
+            .entry(page.title_id.clone())
+            .or_default()
+            .push(page);
+    }
+
+    let mut analyzed = 0;
+    for (title_id, pages) in by_title {
+        let title = match Titles::find_by_id(title_id)
+            .one(&app_state.db)
+            .await
+            .map_err(|e| anyhow!("Can't find title: {}", e))?
+        {
+            Some(title) => title,
+            None => continue,
+        };
+
+        let config = app_state.config.clone();
+        let archive_path = title.path.clone();
+        let paths = pages
+            .iter()
+            .map(|page| page.path.clone())
+            .collect::<Vec<_>>();
+        let details = match tokio::task::spawn_blocking(move || {
+            analyze_archive(&config, &archive_path, &paths)
+        })
+        .await?
+        {
+            Ok(details) => details,
+            Err(e) => {
+                // the archive may be gone already, the scanner will clean it up, or broken,
+                // its pages are marked as undecodable so it isn't reopened on every pass
+                warn!("can't analyze {}: {}", title.path, e);
+                Pages::update_many()
+                    .col_expr(pages::Column::Blurhash, Expr::value(""))
+                    .filter(pages::Column::Id.is_in(pages.iter().map(|page| &page.id)))
+                    .exec(&app_state.db)
+                    .await
+                    .map_err(|e| anyhow!("Can't update pages: {}", e))?;
+                continue;
+            }
+        };
+
+        let txn = app_state.db.begin().await?;
+        let cover = Covers::find_by_id(&title.id)
+            .one(&txn)
+            .await
+            .map_err(|e| anyhow!("Can't find cover: {}", e))?;
+        for (page, details) in pages.iter().zip(details) {
+            if let Some(cover) = cover
+                .as_ref()
+                .filter(|cover| !cover.is_custom && cover.path == page.path)
+            {
+                if let (true, Some(width), Some(height)) =
+                    (details.is_animated, details.width, details.height)
+                {
+                    if !details.blurhash.is_empty() && height > 0 {
+                        covers::ActiveModel {
+                            id: Set(cover.id.clone()),
+                            blurhash: Set(details.blurhash.clone()),
+                            ratio: Set(width * app_state.config.ratio_percision / height),
+                            ..Default::default()
+                        }
+                        .update(&txn)
+                        .await
+                        .map_err(|e| anyhow!("Can't update cover: {}", e))?;
+                    }
+                }
+            }
+
+            let is_spread = match (details.width, details.height) {
+                (Some(width), Some(height)) => !details.is_animated && is_spread(width, height),
+                _ => false,
+            };
+            pages::ActiveModel {
+                id: Set(page.id.clone()),
+                width: Set(details.width),
+                height: Set(details.height),
+                size: Set(details.size),
+                blurhash: Set(Some(details.blurhash)),
+                is_spread: Set(is_spread),
+                slices: Set(details.slices),
+                is_animated: Set(details.is_animated),
+                phash: Set(details.phash),
+                phash_pending: Set(false),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|e| anyhow!("Can't update page: {}", e))?;
+        }
+        txn.commit().await?;
+
+        analyzed += pages.len();
+    }
+
+    Ok(analyzed)
+}
+
+/// Spreads are two portrait pages side by side, so anything wider than 6:5 is one.
+///
+/// m_20240618_000012_add_spreads uses the same threshold for pages analyzed before it.
+fn is_spread(width: u32, height: u32) -> bool {
+    width as u64 * 5 >= height as u64 * 6
+}
+
+/// Decode the given pages of an archive in parallel. This is blocking.
+fn analyze_archive(
+    config: &Config,
+    archive_path: &str,
+    pages: &[String],
+) -> anyhow::Result<Vec<PageDetails>> {
+    let index = ArchiveIndex::open(archive_path)?;
+
+    Ok(pages
+        .par_iter()
+        .map(|path| {
+            let size = index.get(path).map(|entry| entry.size as i64);
+            let details = analyze_page(config, &index, path).unwrap_or_else(|e| {
+                warn!("can't analyze {} in {}: {}", path, archive_path, e);
+                PageDetails {
+                    // videos are still videos without ffmpeg
+                    is_animated: config.video_formats.contains(&extension(path).as_str()),
+                    ..Default::default()
+                }
+            });
+            PageDetails { size, ..details }
+        })
+        .collect())
+}
+
+fn analyze_page(config: &Config, index: &ArchiveIndex, path: &str) -> anyhow::Result<PageDetails> {
+    let extension = extension(path);
+    let is_video = config.video_formats.contains(&extension.as_str());
+    if !is_video && !config.native_img_formats.contains(&extension.as_str()) {
+        return Err(anyhow!("unsupported format: {}", extension));
+    }
+
+    let mut buffer = Vec::new();
+    index.reader(path)?.read_to_end(&mut buffer)?;
+
+    // only the first frame of animated images is decoded
+    let (image, is_animated) = match is_video {
+        true => {
+            let ffmpeg_path = config
+                .ffmpeg_path
+                .as_deref()
+                .ok_or_else(|| anyhow!("FFMPEG_PATH is required for videos"))?;
+            let still = imaging::video_still(ffmpeg_path, &config.temp_dir, &buffer, &extension)?;
+            (still, true)
+        }
+        false => (
+            image::load_from_memory(&buffer)?,
+            imaging::is_animated(&buffer),
+        ),
+    };
+
+    let blurhash = imaging::blurhash(&image)?;
+
+    // cropping animations would only keep their first frame
+    let slices = match is_animated {
+        true => vec![],
+        false => imaging::slice_boundaries(&image),
+    }
+    .iter()
+    .map(|offset| offset.to_string())
+    .collect::<Vec<_>>();
+
+    Ok(PageDetails {
+        width: Some(image.width()),
+        height: Some(image.height()),
+        size: None,
+        blurhash,
+        slices: match slices.is_empty() {
+            true => None,
+            false => Some(slices.join(",")),
+        },
+        is_animated,
+        // stored as the bits of a signed integer, SQLite has no unsigned one
+        phash: Some(imaging::phash(&image) as i64),
+    })
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use sea_orm::{
+    sea_query::Query, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QuerySelect, Set, TransactionTrait,
+};
+use tracing::warn;
+
+use crate::{
+    archive::{comic_info_people, read_comic_info, split_names, ArchiveIndex},
+    models::{
+        metadata::{toml_modified, TitleMetadata},
+        prelude::*,
+    },
+    routes::fold,
+};
+
+/// A person credited on a title, with other spellings of their name.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Credit {
+    name: String,
+    role: PersonRole,
+    aliases: Vec<String>,
+}
+
+/// Credit the people of every title that hasn't been yet, or whose TOML changed since, from
+/// the `people` of its TOML and its ComicInfo.xml, or its `author` split into names when
+/// neither has any.
+///
+/// Names are the same person when they're spelled the same once folded, like "Ōno Yūki" and
+/// "Oono Yuuki", or when one is an alias of the other. People left without titles are removed.
+///
+/// Returns how many titles were credited.
+pub async fn analyze_title_people(db: &DatabaseConnection) -> anyhow::Result<usize> {
+    let titles = Titles::find()
+        .select_only()
+        .column(titles::Column::Id)
+        .column(titles::Column::Path)
+        .column(titles::Column::Author)
+        .column(titles::Column::PeopleAnalyzed)
+        .column(titles::Column::PeopleModified)
+        .into_tuple::<(TitleID, String, Option<String>, bool, Option<i64>)>()
+        .all(db)
+        .await
+        .map_err(|e| anyhow!("Can't find titles: {}", e))?;
+    // `[[people]]` and their aliases can be edited without the archive or author changing
+    let pending = tokio::task::spawn_blocking(move || {
+        titles
+            .into_iter()
+            .filter(|(_, path, _, analyzed, credited_modified)| {
+                !analyzed || toml_modified(Path::new(path)) != *credited_modified
+            })
+            .map(|(title_id, path, author, _, _)| (title_id, path, author))
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let mut known = known_people(db).await?;
+    let mut analyzed = 0;
+    for (title_id, title_path, author) in pending {
+        let (credits, people_modified) = tokio::task::spawn_blocking(move || {
+            let path = Path::new(&title_path);
+            let credits = read_credits(path, author);
+            // after reading, as missing tomls are created then
+            (credits, toml_modified(path))
+        })
+        .await?;
+
+        let txn = db.begin().await?;
+        TitlePeople::delete_many()
+            .filter(title_people::Column::TitleId.eq(&title_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| anyhow!("Can't delete title people: {}", e))?;
+
+        let mut credited = HashSet::new();
+        for credit in credits {
+            let folded = fold(&credit.name);
+            if folded.is_empty() {
+                continue;
+            }
+            let person_id = match known.get(&folded) {
+                Some(person_id) => person_id.clone(),
+                None => {
+                    let person = people::ActiveModel {
+                        id: Set(PersonID::new()),
+                        name: Set(credit.name.clone()),
+                    }
+                    .insert(&txn)
+                    .await
+                    .map_err(|e| anyhow!("Can't insert person: {}", e))?;
+                    known.insert(folded, person.id.clone());
+                    person.id
+                }
+            };
+
+            for alias in credit.aliases {
+                let folded = fold(&alias);
+                if folded.is_empty() || known.contains_key(&folded) {
+                    continue;
+                }
+                person_aliases::ActiveModel {
+                    person_id: Set(person_id.clone()),
+                    name: Set(alias),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await
+                .map_err(|e| anyhow!("Can't insert person alias: {}", e))?;
+                known.insert(folded, person_id.clone());
+            }
+
+            if credited.insert((person_id.clone(), credit.role)) {
+                title_people::ActiveModel {
+                    title_id: Set(title_id.clone()),
+                    person_id: Set(person_id),
+                    role: Set(credit.role),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await
+                .map_err(|e| anyhow!("Can't insert title person: {}", e))?;
+            }
+        }
+
+        titles::ActiveModel {
+            id: Set(title_id),
+            people_analyzed: Set(true),
+            people_modified: Set(people_modified),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|e| anyhow!("Can't update title: {}", e))?;
+        txn.commit().await?;
+
+        analyzed += 1;
+    }
+
+    // people of removed titles, or whose name was fixed
+    People::delete_many()
+        .filter(
+            people::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(title_people::Column::PersonId)
+                    .from(TitlePeople)
+                    .to_owned(),
+            ),
+        )
+        .exec(db)
+        .await
+        .map_err(|e| anyhow!("Can't delete people: {}", e))?;
+
+    Ok(analyzed)
+}
+
+/// Every person by their folded name and aliases.
+async fn known_people(db: &DatabaseConnection) -> anyhow::Result<HashMap<String, PersonID>> {
+    let mut known = People::find()
+        .all(db)
+        .await
+        .map_err(|e| anyhow!("Can't find people: {}", e))?
+        .into_iter()
+        .map(|person| (fold(&person.name), person.id))
+        .collect::<HashMap<_, _>>();
+    let aliases = PersonAliases::find()
+        .all(db)
+        .await
+        .map_err(|e| anyhow!("Can't find person aliases: {}", e))?;
+    for alias in aliases {
+        known.entry(fold(&alias.name)).or_insert(alias.person_id);
+    }
+    Ok(known)
+}
+
+/// Read the credits of a title from its TOML and ComicInfo.xml, falling back to `author`.
+/// This is blocking.
+fn read_credits(archive_path: &Path, author: Option<String>) -> Vec<Credit> {
+    let mut credits = TitleMetadata::from(archive_path)
+        .ok()
+        .and_then(|metadata| metadata.people)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|person| {
+            let roles = person
+                .roles
+                .filter(|roles| !roles.is_empty())
+                .unwrap_or_else(|| vec![PersonRole::Author]);
+            roles.into_iter().map(move |role| Credit {
+                name: person.name.clone(),
+                role,
+                aliases: person.aliases.clone().unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    match ArchiveIndex::open(archive_path).and_then(|index| read_comic_info(&index)) {
+        Ok(Some(xml)) => {
+            credits.extend(
+                comic_info_people(&xml)
+                    .into_iter()
+                    .map(|(name, role)| Credit {
+                        name,
+                        role,
+                        ..Default::default()
+                    }),
+            )
+        }
+        Ok(None) => {}
+        // the archive may be gone already, the scanner will clean it up
+        Err(e) => warn!(
+            "can't read ComicInfo.xml of {}: {}",
+            archive_path.display(),
+            e
+        ),
+    }
+
+    if credits.is_empty() {
+        credits = split_names(author.as_deref().unwrap_or_default())
+            .into_iter()
+            .map(|name| Credit {
+                name,
+                ..Default::default()
+            })
+            .collect();
+    }
+    credits
+}
+
+#[cfg(test)]
+mod tests_people {
+    use super::*;
+    use crate::models::fixtures::{insert_category, memory_db};
+    use sea_orm::{IntoActiveModel, QueryOrder};
+    use std::{fs::remove_file, io::Write};
+
+    async fn credited(db: &DatabaseConnection, title_id: &TitleID) -> Vec<(String, PersonRole)> {
+        TitlePeople::find()
+            .find_also_related(People)
+            .filter(title_people::Column::TitleId.eq(title_id))
+            .order_by_asc(title_people::Column::Id)
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(credit, person)| (person.unwrap().name, credit.role))
+            .collect()
+    }
+
+    #[test]
+    fn credits() {
+        let path = Path::new("test-people-credits.cbz");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        zip.start_file("Volume 1/ComicInfo.xml", Default::default())
+            .unwrap();
+        zip.write_all(b"<ComicInfo><Writer>Oda</Writer><Inker>Ink</Inker></ComicInfo>")
+            .unwrap();
+        zip.finish().unwrap();
+        std::fs::write(
+            "test-people-credits.toml",
+            "[[people]]\nname = \"Oda\"\naliases = [\"Oda E.\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_credits(path, Some("Someone".to_string())),
+            vec![
+                Credit {
+                    name: "Oda".to_string(),
+                    role: PersonRole::Author,
+                    aliases: vec!["Oda E.".to_string()],
+                },
+                Credit {
+                    name: "Oda".to_string(),
+                    role: PersonRole::Writer,
+                    ..Default::default()
+                },
+                Credit {
+                    name: "Ink".to_string(),
+                    role: PersonRole::Inker,
+                    ..Default::default()
+                },
+            ]
+        );
+        remove_file(path).unwrap();
+        remove_file("test-people-credits.toml").unwrap();
+
+        // neither the TOML nor the archive have any
+        let path = Path::new("test-people-author.cbz");
+        assert_eq!(
+            read_credits(path, Some("Writer, Artist".to_string())),
+            vec![
+                Credit {
+                    name: "Writer".to_string(),
+                    ..Default::default()
+                },
+                Credit {
+                    name: "Artist".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+        remove_file("test-people-author.toml").unwrap();
+    }
+
+    #[tokio::test]
+    async fn analyzed() {
+        let db = &memory_db().await;
+        let category_id = insert_category(db, "Manga").await;
+        let mut title_ids = vec![];
+        for (name, author) in [("First", "Ōno Yūki, Artist"), ("Second", "Oono Yuuki")] {
+            let title = titles::Model {
+                id: CustomID::new(),
+                title: name.to_string(),
+                author: Some(author.to_string()),
+                category_id: category_id.clone(),
+                path: format!("test-people-{}.cbz", name),
+                ..Default::default()
+            };
+            title.clone().into_active_model().insert(db).await.unwrap();
+            title_ids.push(title.id);
+        }
+
+        assert_eq!(analyze_title_people(db).await.unwrap(), 2);
+        assert_eq!(analyze_title_people(db).await.unwrap(), 0);
+        assert_eq!(
+            credited(db, &title_ids[0]).await,
+            vec![
+                ("Ōno Yūki".to_string(), PersonRole::Author),
+                ("Artist".to_string(), PersonRole::Author),
+            ]
+        );
+        // the same person, spelled another way
+        assert_eq!(
+            credited(db, &title_ids[1]).await,
+            vec![("Ōno Yūki".to_string(), PersonRole::Author)]
+        );
+
+        // a new author credits the title again, and the artist is gone with it
+        titles::ActiveModel {
+            id: Set(title_ids[0].clone()),
+            author: Set(Some("Ono Yuki".to_string())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        assert_eq!(analyze_title_people(db).await.unwrap(), 1);
+        assert_eq!(
+            credited(db, &title_ids[0]).await,
+            vec![("Ōno Yūki".to_string(), PersonRole::Author)]
+        );
+        assert_eq!(People::find().all(db).await.unwrap().len(), 1);
+
+        for name in ["First", "Second"] {
+            remove_file(format!("test-people-{}.toml", name)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn toml_edited() {
+        let db = &memory_db().await;
+        let category_id = insert_category(db, "Manga").await;
+        let title = titles::Model {
+            id: CustomID::new(),
+            title: "Edited".to_string(),
+            author: Some("Oda".to_string()),
+            category_id,
+            path: "test-people-edited.cbz".to_string(),
+            ..Default::default()
+        };
+        title.clone().into_active_model().insert(db).await.unwrap();
+        assert_eq!(analyze_title_people(db).await.unwrap(), 1);
+        assert_eq!(
+            credited(db, &title.id).await,
+            vec![("Oda".to_string(), PersonRole::Author)]
+        );
+
+        // dated later, edits within the same millisecond would look unchanged
+        std::fs::write(
+            "test-people-edited.toml",
+            "[[people]]\nname = \"Eiichiro Oda\"\nroles = [\"writer\"]\naliases = [\"Oda\"]\n",
+        )
+        .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open("test-people-edited.toml")
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(analyze_title_people(db).await.unwrap(), 1);
+        assert_eq!(analyze_title_people(db).await.unwrap(), 0);
+        assert_eq!(
+            credited(db, &title.id).await,
+            vec![("Eiichiro Oda".to_string(), PersonRole::Writer)]
+        );
+        // the name from `author` is gone with its only title
+        assert_eq!(People::find().all(db).await.unwrap().len(), 1);
+
+        remove_file("test-people-edited.toml").unwrap();
+    }
+}
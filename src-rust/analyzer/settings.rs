@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QuerySelect, Set};
+
+use crate::models::{
+    metadata::{toml_modified, CategoryMetadata, TitleMetadata},
+    prelude::*,
+};
+
+/// Read the reading direction and long strip mode of every title whose toml, or the toml of
+/// its category, changed since they were last read.
+///
+/// Returns how many titles were read.
+pub async fn analyze_title_settings(db: &DatabaseConnection) -> anyhow::Result<usize> {
+    let titles = Titles::find()
+        .select_only()
+        .column(titles::Column::Id)
+        .column(titles::Column::Path)
+        .column(titles::Column::SettingsModified)
+        .into_tuple::<(TitleID, String, Option<i64>)>()
+        .all(db)
+        .await
+        .map_err(|e| anyhow!("Can't find titles: {}", e))?;
+
+    let changed = tokio::task::spawn_blocking(move || {
+        titles
+            .into_iter()
+            .filter_map(|(title_id, path, read_modified)| {
+                let path = Path::new(&path);
+                if settings_modified(path) == read_modified {
+                    return None;
+                }
+                let (reading_direction, long_strip) = title_settings(path);
+                // after reading, as missing tomls are created then
+                Some((
+                    title_id,
+                    reading_direction,
+                    long_strip,
+                    settings_modified(path),
+                ))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    for (title_id, reading_direction, long_strip, settings_modified) in &changed {
+        titles::ActiveModel {
+            id: Set(title_id.clone()),
+            reading_direction: Set(*reading_direction),
+            long_strip: Set(*long_strip),
+            settings_modified: Set(*settings_modified),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(|e| anyhow!("Can't update title: {}", e))?;
+    }
+
+    Ok(changed.len())
+}
+
+/// Modification times of the toml of a title and of the toml of its category added up, which
+/// changes when either file does.
+fn settings_modified(archive_path: &Path) -> Option<i64> {
+    let category = archive_path.parent().and_then(toml_modified);
+    match (toml_modified(archive_path), category) {
+        (None, None) => None,
+        (title, category) => Some(title.unwrap_or_default() + category.unwrap_or_default()),
+    }
+}
+
+/// Read the reading direction and long strip mode of a title from its toml, falling back
+/// to the toml of its category for the long strip mode. This is blocking.
+fn title_settings(archive_path: &Path) -> (ReadingDirection, bool) {
+    let metadata = TitleMetadata::from(archive_path).unwrap_or_default();
+    let long_strip = metadata.long_strip.or_else(|| {
+        archive_path
+            .parent()
+            .and_then(|category_path| CategoryMetadata::from(category_path).ok())
+            .and_then(|category| category.long_strip)
+    });
+
+    (
+        metadata.reading_direction.unwrap_or_default(),
+        long_strip.unwrap_or(false),
+    )
+}
+
+#[cfg(test)]
+mod tests_settings {
+    use super::*;
+    use crate::{
+        models::fixtures::{insert_category, memory_db},
+        routes::find_page_counts,
+    };
+    use sea_orm::IntoActiveModel;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Write a toml dated `secs` after the epoch, edits within the same millisecond would
+    /// otherwise look unchanged.
+    fn write_toml(path: &str, contents: &str, secs: u64) {
+        std::fs::write(path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    async fn title(db: &DatabaseConnection, path: &str) -> TitleID {
+        let category_id = insert_category(db, "Manga").await;
+        let title_id = CustomID::new();
+        titles::Model {
+            id: title_id.clone(),
+            title: "Title".to_string(),
+            category_id,
+            path: path.to_string(),
+            ..Default::default()
+        }
+        .into_active_model()
+        .insert(db)
+        .await
+        .unwrap();
+        title_id
+    }
+
+    async fn settings(db: &DatabaseConnection, title_id: &TitleID) -> (ReadingDirection, bool) {
+        let title = Titles::find_by_id(title_id).one(db).await.unwrap().unwrap();
+        (title.reading_direction, title.long_strip)
+    }
+
+    #[tokio::test]
+    async fn edited() {
+        let db = &memory_db().await;
+        std::fs::create_dir_all("test-settings-edited/Manga").unwrap();
+        let title_id = title(db, "test-settings-edited/Manga/Title.cbz").await;
+
+        write_toml(
+            "test-settings-edited/Manga/Title.toml",
+            "reading_direction = \"rtl\"",
+            1,
+        );
+        assert_eq!(analyze_title_settings(db).await.unwrap(), 1);
+        assert_eq!(analyze_title_settings(db).await.unwrap(), 0);
+        assert_eq!(
+            settings(db, &title_id).await,
+            (ReadingDirection::Rtl, false)
+        );
+
+        write_toml(
+            "test-settings-edited/Manga/Title.toml",
+            "long_strip = true",
+            2,
+        );
+        assert_eq!(analyze_title_settings(db).await.unwrap(), 1);
+        assert_eq!(settings(db, &title_id).await, (ReadingDirection::Ltr, true));
+
+        std::fs::remove_dir_all("test-settings-edited").unwrap();
+    }
+
+    #[tokio::test]
+    async fn category_long_strip() {
+        let db = &memory_db().await;
+        std::fs::create_dir_all("test-settings-category/Webtoons").unwrap();
+        let title_id = title(db, "test-settings-category/Webtoons/Title.cbz").await;
+        for slices in [None, Some("0,1000,2000,2500")] {
+            pages::ActiveModel {
+                id: Set(PageID::new()),
+                title_id: Set(title_id.clone()),
+                path: Set(PageID::new().to_string()),
+                blurhash: Set(Some(String::new())),
+                slices: Set(slices.map(str::to_string)),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        let page_count = || async {
+            let title = Titles::find_by_id(&title_id)
+                .one(db)
+                .await
+                .unwrap()
+                .unwrap();
+            find_page_counts(db, &[title]).await.unwrap()[&title_id]
+        };
+
+        write_toml(
+            "test-settings-category/Webtoons.toml",
+            "long_strip = true",
+            1,
+        );
+        assert_eq!(analyze_title_settings(db).await.unwrap(), 1);
+        assert_eq!(page_count().await, 4);
+
+        // after the pages were analyzed
+        write_toml(
+            "test-settings-category/Webtoons.toml",
+            "long_strip = false",
+            2,
+        );
+        assert_eq!(analyze_title_settings(db).await.unwrap(), 1);
+        assert_eq!(page_count().await, 2);
+
+        std::fs::remove_dir_all("test-settings-category").unwrap();
+    }
+}
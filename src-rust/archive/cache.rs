@@ -0,0 +1,140 @@
+use std::{
+    num::NonZeroUsize,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use tokio::sync::Mutex;
+
+use super::ArchiveIndex;
+use crate::models::prelude::TitleID;
+
+/// A title's archive changes whenever its hash does, so both are part of the key.
+type CacheKey = (TitleID, String);
+
+#[derive(Debug)]
+struct CachedIndex {
+    index: Arc<ArchiveIndex>,
+    loaded_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveCacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// An LRU cache of archive central directories, shared by every route reading from archives.
+#[derive(Debug)]
+pub struct ArchiveCache {
+    /// `None` when the cache is disabled with a capacity of 0.
+    entries: Option<Mutex<LruCache<CacheKey, CachedIndex>>>,
+    /// Entries older than this are read again from disk.
+    ttl: Option<Duration>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ArchiveCache {
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the index of a title's archive, reading it from disk on a miss.
+    pub async fn get(
+        &self,
+        title_id: &TitleID,
+        hash: &str,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<Arc<ArchiveIndex>> {
+        let Some(entries) = &self.entries else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(Arc::new(ArchiveIndex::load(path).await?));
+        };
+
+        let key = (title_id.clone(), hash.to_string());
+
+        {
+            let mut entries = entries.lock().await;
+            if let Some(cached) = entries.get(&key) {
+                if self.is_fresh(cached) {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(cached.index.clone());
+                }
+                entries.pop(&key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // don't hold the lock while reading the archive, other titles may be cached
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let index = Arc::new(ArchiveIndex::load(path).await?);
+
+        let mut entries = entries.lock().await;
+
+        // the archive was replaced, the old index can't be used anymore
+        let stale_keys = entries
+            .iter()
+            .filter(|((id, cached_hash), _)| id == title_id && cached_hash != hash)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for stale_key in stale_keys {
+            entries.pop(&stale_key);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let cached = CachedIndex {
+            index: index.clone(),
+            loaded_at: Instant::now(),
+        };
+        if let Some((evicted_key, _)) = entries.push(key.clone(), cached) {
+            // `push` also returns the old value when the key was already there
+            if evicted_key != key {
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub async fn stats(&self) -> ArchiveCacheStats {
+        let (capacity, size) = match &self.entries {
+            Some(entries) => {
+                let entries = entries.lock().await;
+                (entries.cap().get(), entries.len())
+            }
+            None => (0, 0),
+        };
+
+        ArchiveCacheStats {
+            capacity,
+            size,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn is_fresh(&self, cached: &CachedIndex) -> bool {
+        match self.ttl {
+            Some(ttl) => cached.loaded_at.elapsed() < ttl,
+            None => true,
+        }
+    }
+}
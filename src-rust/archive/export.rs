@@ -0,0 +1,332 @@
+use std::{
+    io::{Cursor, Read, Seek, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use super::ArchiveIndex;
+
+/// Viewport used for pages whose dimensions can't be read, e.g. JPEG XL or AVIF.
+const DEFAULT_PAGE_DIMENSION: (u32, u32) = (1000, 1500);
+
+/// Title metadata from the database, written into exported archives.
+#[derive(Debug, Clone, Default)]
+pub struct ExportMetadata {
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    pub tags: Vec<String>,
+    /// When the archive was last modified, stamped into EPUBs so the same title always
+    /// exports to the same bytes.
+    pub modified: Option<SystemTime>,
+}
+
+impl ExportMetadata {
+    /// Split the release date into (year, month, day), as far as it goes.
+    fn release_parts(&self) -> Vec<u32> {
+        self.release
+            .as_deref()
+            .map(|release| {
+                release
+                    .chars()
+                    .take(10)
+                    .collect::<String>()
+                    .split('-')
+                    .map_while(|part| part.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Build a ComicInfo.xml (ComicRack schema 2.0) for the title.
+pub fn comic_info(metadata: &ExportMetadata, page_count: usize) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" \
+        xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+    );
+
+    let mut element = |name: &str, value: &str| {
+        xml.push_str(&format!("  <{0}>{1}</{0}>\n", name, xml_escape(value)));
+    };
+
+    element("Title", &metadata.title);
+    if let Some(description) = &metadata.description {
+        element("Summary", description);
+    }
+    for (name, value) in ["Year", "Month", "Day"]
+        .iter()
+        .zip(metadata.release_parts())
+    {
+        element(name, &value.to_string());
+    }
+    if let Some(author) = &metadata.author {
+        element("Writer", author);
+    }
+    if !metadata.tags.is_empty() {
+        element("Tags", &metadata.tags.join(", "));
+    }
+    element("PageCount", &page_count.to_string());
+
+    xml.push_str("  <Pages>\n    <Page Image=\"0\" Type=\"FrontCover\" />\n  </Pages>\n");
+    xml.push_str("</ComicInfo>\n");
+    xml
+}
+
+/// Options for files in an exported archive, dated `modified` rather than now so exports
+/// are byte for byte the same each time.
+fn file_options(modified: Option<SystemTime>) -> FileOptions {
+    let modified = DateTime::<Utc>::from(modified.unwrap_or(UNIX_EPOCH));
+    let time = zip::DateTime::from_date_and_time(
+        modified.year().try_into().unwrap_or_default(),
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8,
+    )
+    // before 1980
+    .unwrap_or_default();
+    FileOptions::default().last_modified_time(time)
+}
+
+/// Write a normalized CBZ: pages renamed in reading order, plus a ComicInfo.xml.
+pub fn write_cbz(
+    index: &ArchiveIndex,
+    pages: &[String],
+    comic_info: &str,
+    modified: Option<SystemTime>,
+    out: impl Write + Seek,
+) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new(out);
+    // pages are already compressed images
+    let stored = file_options(modified).compression_method(CompressionMethod::Stored);
+
+    for (i, page) in pages.iter().enumerate() {
+        zip.start_file(format!("{:04}.{}", i + 1, extension(page)), stored)?;
+        std::io::copy(&mut index.reader(page)?, &mut zip)?;
+    }
+
+    zip.start_file("ComicInfo.xml", file_options(modified))?;
+    zip.write_all(comic_info.as_bytes())?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// Write a fixed-layout EPUB 3 with one page per image.
+pub fn write_epub(
+    index: &ArchiveIndex,
+    pages: &[String],
+    metadata: &ExportMetadata,
+    out: impl Write + Seek,
+) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new(out);
+    let deflated = file_options(metadata.modified);
+    let stored = deflated.compression_method(CompressionMethod::Stored);
+
+    // the mimetype must be the first entry, uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+        \x20 <rootfiles>\n\
+        \x20   <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+        \x20 </rootfiles>\n\
+        </container>\n",
+    )?;
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+
+    for (i, page) in pages.iter().enumerate() {
+        let number = i + 1;
+        let image_name = format!("{:04}.{}", number, extension(page));
+
+        let mut buffer = Vec::new();
+        index.reader(page)?.read_to_end(&mut buffer)?;
+        let (width, height) = image::io::Reader::new(Cursor::new(&buffer))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or(DEFAULT_PAGE_DIMENSION);
+
+        zip.start_file(format!("OEBPS/images/{}", image_name), stored)?;
+        zip.write_all(&buffer)?;
+
+        zip.start_file(format!("OEBPS/pages/{:04}.xhtml", number), deflated)?;
+        zip.write_all(
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <!DOCTYPE html>\n\
+                <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+                <head>\n\
+                \x20 <title>{title} - {number}</title>\n\
+                \x20 <meta name=\"viewport\" content=\"width={width}, height={height}\"/>\n\
+                \x20 <style>body {{ margin: 0; }} img {{ width: {width}px; height: {height}px; }}</style>\n\
+                </head>\n\
+                <body><img src=\"../images/{image_name}\" alt=\"{number}\"/></body>\n\
+                </html>\n",
+                title = xml_escape(&metadata.title),
+            )
+            .as_bytes(),
+        )?;
+
+        manifest.push_str(&format!(
+            "    <item id=\"img{number}\" href=\"images/{image_name}\" media-type=\"{}\"{}/>\n\
+            \x20   <item id=\"page{number}\" href=\"pages/{number:04}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            media_type(page),
+            if i == 0 { " properties=\"cover-image\"" } else { "" },
+        ));
+        spine.push_str(&format!("    <itemref idref=\"page{}\"/>\n", number));
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <!DOCTYPE html>\n\
+            <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+            <head><title>{0}</title></head>\n\
+            <body>\n\
+            \x20 <nav epub:type=\"toc\"><ol><li><a href=\"pages/0001.xhtml\">{0}</a></li></ol></nav>\n\
+            </body>\n\
+            </html>\n",
+            xml_escape(&metadata.title)
+        )
+        .as_bytes(),
+    )?;
+
+    let mut dc = format!(
+        "    <dc:identifier id=\"id\">urn:yomuyume:{}</dc:identifier>\n\
+        \x20   <dc:title>{}</dc:title>\n\
+        \x20   <dc:language>und</dc:language>\n",
+        xml_escape(&metadata.id),
+        xml_escape(&metadata.title)
+    );
+    if let Some(author) = &metadata.author {
+        dc.push_str(&format!(
+            "    <dc:creator>{}</dc:creator>\n",
+            xml_escape(author)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        dc.push_str(&format!(
+            "    <dc:description>{}</dc:description>\n",
+            xml_escape(description)
+        ));
+    }
+    if let Some(release) = &metadata.release {
+        dc.push_str(&format!("    <dc:date>{}</dc:date>\n", xml_escape(release)));
+    }
+    for tag in &metadata.tags {
+        dc.push_str(&format!(
+            "    <dc:subject>{}</dc:subject>\n",
+            xml_escape(tag)
+        ));
+    }
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\" \
+            prefix=\"rendition: http://www.idpf.org/vocab/rendition/#\">\n\
+            \x20 <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+            {dc}\
+            \x20   <meta property=\"dcterms:modified\">{modified}</meta>\n\
+            \x20   <meta property=\"rendition:layout\">pre-paginated</meta>\n\
+            \x20   <meta property=\"rendition:orientation\">auto</meta>\n\
+            \x20   <meta property=\"rendition:spread\">none</meta>\n\
+            \x20 </metadata>\n\
+            \x20 <manifest>\n\
+            \x20   <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+            {manifest}\
+            \x20 </manifest>\n\
+            \x20 <spine>\n\
+            {spine}\
+            \x20 </spine>\n\
+            </package>\n",
+            modified = DateTime::<Utc>::from(metadata.modified.unwrap_or(UNIX_EPOCH))
+                .format("%Y-%m-%dT%H:%M:%SZ"),
+        )
+        .as_bytes(),
+    )?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn media_type(path: &str) -> String {
+    match extension(path).as_str() {
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        "tif" => "image/tiff".to_string(),
+        extension => format!("image/{}", extension),
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests_export {
+    use super::*;
+    use std::{fs::remove_file, time::Duration};
+
+    #[test]
+    fn byte_stable() {
+        let path = Path::new("test-export-stable.cbz");
+        let mut zip = ZipWriter::new(std::fs::File::create(path).unwrap());
+        zip.start_file("1.png", FileOptions::default()).unwrap();
+        zip.write_all(b"not an image").unwrap();
+        zip.finish().unwrap();
+        let index = ArchiveIndex::open(path).unwrap();
+        let pages = vec!["1.png".to_string()];
+        let metadata = ExportMetadata {
+            title: "Title".to_string(),
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            ..Default::default()
+        };
+
+        let export = |epub: bool| {
+            let mut out = Cursor::new(vec![]);
+            match epub {
+                true => write_epub(&index, &pages, &metadata, &mut out).unwrap(),
+                false => write_cbz(&index, &pages, "", metadata.modified, &mut out).unwrap(),
+            }
+            out.into_inner()
+        };
+        let epub = export(true);
+        let cbz = export(false);
+        // a second later, so anything dated now would differ
+        std::thread::sleep(Duration::from_millis(1100));
+        assert_eq!(export(true), epub);
+        assert_eq!(export(false), cbz);
+
+        remove_file(path).unwrap();
+    }
+}
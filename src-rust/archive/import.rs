@@ -0,0 +1,121 @@
+use std::io::Read;
+
+use super::ArchiveIndex;
+use crate::models::prelude::PersonRole;
+
+/// ComicInfo.xml elements listing the people of a title, with their role.
+const COMIC_INFO_ROLES: [(&str, PersonRole); 8] = [
+    ("Writer", PersonRole::Writer),
+    ("Penciller", PersonRole::Penciller),
+    ("Inker", PersonRole::Inker),
+    ("Colorist", PersonRole::Colorist),
+    ("Letterer", PersonRole::Letterer),
+    ("CoverArtist", PersonRole::CoverArtist),
+    ("Editor", PersonRole::Editor),
+    ("Translator", PersonRole::Translator),
+];
+
+/// Read the ComicInfo.xml of an archive, `None` when it has none. This is blocking.
+pub fn read_comic_info(index: &ArchiveIndex) -> anyhow::Result<Option<String>> {
+    // usually at the root, but any folder will do
+    let Some(name) = index
+        .names()
+        .filter(|name| {
+            name.rsplit('/')
+                .next()
+                .is_some_and(|file_name| file_name.eq_ignore_ascii_case("ComicInfo.xml"))
+        })
+        .min_by_key(|name| name.len())
+    else {
+        return Ok(None);
+    };
+
+    let mut xml = String::new();
+    index.reader(name)?.read_to_string(&mut xml)?;
+    Ok(Some(xml))
+}
+
+/// The people credited in a ComicInfo.xml, in the order of its elements.
+///
+/// Every element is a comma separated list of names.
+pub fn comic_info_people(xml: &str) -> Vec<(String, PersonRole)> {
+    COMIC_INFO_ROLES
+        .iter()
+        .filter_map(|(element, role)| {
+            let start = xml.find(&format!("<{}>", element))? + element.len() + 2;
+            let end = start + xml[start..].find(&format!("</{}>", element))?;
+            Some((xml_unescape(&xml[start..end]), *role))
+        })
+        .flat_map(|(names, role)| {
+            split_names(&names)
+                .into_iter()
+                .map(move |name| (name, role))
+        })
+        .collect()
+}
+
+/// Split a list of names like "Writer, Artist" or "Writer & Artist".
+pub fn split_names(names: &str) -> Vec<String> {
+    names
+        .split([',', ';', '&', '/'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests_import {
+    use super::*;
+    use crate::archive::{comic_info, ExportMetadata};
+
+    #[test]
+    fn people() {
+        let xml = "<ComicInfo>\n  <Title>A &amp; B</Title>\n  <Writer>Oda, Someone</Writer>\n  \
+            <Penciller>Oda</Penciller>\n  <Translator>Tom &amp; Jerry</Translator>\n  \
+            <Editor></Editor>\n</ComicInfo>";
+        assert_eq!(
+            comic_info_people(xml),
+            vec![
+                ("Oda".to_string(), PersonRole::Writer),
+                ("Someone".to_string(), PersonRole::Writer),
+                ("Oda".to_string(), PersonRole::Penciller),
+                ("Tom".to_string(), PersonRole::Translator),
+                ("Jerry".to_string(), PersonRole::Translator),
+            ]
+        );
+        assert!(comic_info_people("<ComicInfo><Writer>Oda</ComicInfo>").is_empty());
+    }
+
+    #[test]
+    fn exported() {
+        let metadata = ExportMetadata {
+            title: "Title".to_string(),
+            author: Some("<Oda>".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            comic_info_people(&comic_info(&metadata, 1)),
+            vec![("<Oda>".to_string(), PersonRole::Writer)]
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(split_names("Oda"), vec!["Oda"]);
+        assert_eq!(
+            split_names(" A, B & C;; D/E "),
+            vec!["A", "B", "C", "D", "E"]
+        );
+        assert!(split_names(" , ").is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+mod cache;
+mod export;
+mod import;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use axum::body::Body;
+use flate2::read::DeflateDecoder;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use tracing::warn;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::models::prelude::covers;
+
+pub use cache::*;
+pub use export::*;
+pub use import::*;
+
+/// Size of the in-memory pipe between the blocking decompressor and the response body.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Where a file lives inside a zip archive, read from its central directory.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub compression: CompressionMethod,
+    /// Offset of the (possibly compressed) data from the start of the archive.
+    pub data_start: u64,
+    pub compressed_size: u64,
+    pub size: u64,
+}
+
+impl ArchiveEntry {
+    /// Stored entries can be served straight from the archive file, so they support ranges.
+    pub fn is_stored(&self) -> bool {
+        self.compression == CompressionMethod::Stored
+    }
+}
+
+/// The central directory of a zip archive, so entries can be read without parsing it again.
+#[derive(Debug, Clone)]
+pub struct ArchiveIndex {
+    pub path: PathBuf,
+    entries: HashMap<String, ArchiveEntry>,
+}
+
+impl ArchiveIndex {
+    /// Read the central directory of the archive at `path`. This is blocking.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut zip =
+            ZipArchive::new(File::open(&path).map_err(|e| anyhow!("Open zip file error: {}", e))?)
+                .map_err(|e| anyhow!("Read zip file error: {}", e))?;
+
+        let mut entries = HashMap::with_capacity(zip.len());
+        for i in 0..zip.len() {
+            let file = zip
+                .by_index_raw(i)
+                .map_err(|e| anyhow!("Read zip entry error: {}", e))?;
+            if file.is_dir() {
+                continue;
+            }
+            entries.insert(
+                file.name().to_string(),
+                ArchiveEntry {
+                    compression: file.compression(),
+                    data_start: file.data_start(),
+                    compressed_size: file.compressed_size(),
+                    size: file.size(),
+                },
+            );
+        }
+
+        Ok(Self { path, entries })
+    }
+
+    /// Read the central directory of the archive at `path` on the blocking pool.
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || Self::open(path)).await?
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ArchiveEntry> {
+        self.entries.get(name)
+    }
+
+    /// Names of the files in the archive, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    fn entry(&self, name: &str) -> anyhow::Result<ArchiveEntry> {
+        self.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Get file error: {} not found in archive", name))
+    }
+
+    /// Open a blocking reader over the decompressed content of an entry.
+    pub fn reader(&self, name: &str) -> anyhow::Result<Box<dyn Read + Send>> {
+        entry_reader(&self.path, name, &self.entry(name)?)
+    }
+
+    /// Stream an entry as a response body.
+    ///
+    /// `range` is only honored for stored entries, which are read straight from the archive
+    /// file. Everything else is decompressed on the blocking pool and piped into the body.
+    pub async fn stream(&self, name: &str, range: Option<Range<u64>>) -> anyhow::Result<Body> {
+        let entry = self.entry(name)?;
+
+        if entry.is_stored() {
+            let range = range.unwrap_or(0..entry.size);
+            let mut file = tokio::fs::File::open(&self.path)
+                .await
+                .map_err(|e| anyhow!("Open zip file error: {}", e))?;
+            file.seek(SeekFrom::Start(entry.data_start + range.start))
+                .await?;
+            return Ok(Body::from_stream(ReaderStream::new(
+                file.take(range.end - range.start),
+            )));
+        }
+
+        let path = self.path.clone();
+        let name = name.to_string();
+        Ok(blocking_body(move |writer| {
+            std::io::copy(&mut entry_reader(&path, &name, &entry)?, writer)?;
+            Ok(())
+        }))
+    }
+}
+
+/// Read the cover of a title, from its archive or from disk for custom covers. This is blocking.
+pub fn read_cover(title_path: &str, cover: &covers::Model) -> anyhow::Result<Vec<u8>> {
+    if let Some(custom_path) = cover.custom_path(title_path) {
+        return std::fs::read(custom_path).map_err(|e| anyhow!("Read cover error: {}", e));
+    }
+
+    let mut buffer = Vec::new();
+    ArchiveIndex::open(title_path)?
+        .reader(&cover.path)?
+        .read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Build a response body from a blocking writer, run on the blocking pool.
+///
+/// Errors can't be reported to the client once the body has started, so they're only logged.
+pub fn blocking_body(
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()> + Send + 'static,
+) -> Body {
+    let (writer, body_reader) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+    let mut writer = SyncIoBridge::new(writer);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = write(&mut writer) {
+            // most likely the client went away
+            warn!("response stream interrupted: {}", e);
+        }
+    });
+
+    Body::from_stream(ReaderStream::new(body_reader))
+}
+
+/// Open a blocking reader over the decompressed content of an entry.
+fn entry_reader(
+    path: &Path,
+    name: &str,
+    entry: &ArchiveEntry,
+) -> anyhow::Result<Box<dyn Read + Send>> {
+    let mut file = File::open(path).map_err(|e| anyhow!("Open zip file error: {}", e))?;
+
+    match entry.compression {
+        CompressionMethod::Stored => {
+            file.seek(SeekFrom::Start(entry.data_start))?;
+            Ok(Box::new(file.take(entry.size)))
+        }
+        CompressionMethod::Deflated => {
+            file.seek(SeekFrom::Start(entry.data_start))?;
+            Ok(Box::new(DeflateDecoder::new(
+                file.take(entry.compressed_size),
+            )))
+        }
+        // rare in comic archives, let the zip crate deal with it
+        _ => {
+            let mut zip =
+                ZipArchive::new(file).map_err(|e| anyhow!("Read zip file error: {}", e))?;
+            let mut buffer = Vec::with_capacity(entry.size as usize);
+            zip.by_name(name)
+                .map_err(|e| anyhow!("Get file error: {}", e))?
+                .read_to_end(&mut buffer)?;
+            Ok(Box::new(std::io::Cursor::new(buffer)))
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::models::cover_rules::CoverRule;
+
 const VERSION_NAMES: [&str; 31] = [
     "Highly Responsive to Prayers",
     "Story of Eastern Wonderland",
@@ -42,6 +44,7 @@ pub struct Config {
 
     pub jwt_secret: String,
     pub jwt_maxage_day: chrono::Duration,
+    pub admin_usernames: Vec<String>,
 
     pub smtp_host: Option<String>,
     pub smtp_username: Option<String>,
@@ -51,11 +54,21 @@ pub struct Config {
 
     pub djxl_path: Option<String>,
     pub ffmpeg_path: Option<String>,
+    pub temp_dir: String,
+
+    pub prefetch_pages: usize,
+
+    pub archive_cache_capacity: usize,
+    pub archive_cache_ttl: Option<std::time::Duration>,
+
+    /// How covers are picked, unless a category sets its own `cover_rules`.
+    pub cover_rules: Vec<CoverRule>,
 
     // Internal variables
     pub cover_filestems: Vec<&'static str>,
     pub native_img_formats: Vec<&'static str>,
     pub extended_img_formats: Vec<&'static str>,
+    pub video_formats: Vec<&'static str>,
     pub blurhash_dimension_cap: f32,
     pub ratio_percision: u32,
 }
@@ -73,6 +86,15 @@ impl Config {
     }
 
     pub fn init() -> Self {
+        let cover_filestems = vec!["cover", "thumbnail", "folder"];
+        let cover_rules = match Self::may_get("COVER_RULES") {
+            Some(raw) => CoverRule::parse_list(&raw).unwrap_or_else(|e| {
+                tracing::warn!("{}, using the default cover rules.", e);
+                CoverRule::defaults(&cover_filestems)
+            }),
+            None => CoverRule::defaults(&cover_filestems),
+        };
+
         Self {
             app_name: Self::get_env("APP_NAME", Some("Yomuyume")),
             server_address: Self::get_env("SERVER_ADDRESS", Some("0.0.0.0")),
@@ -89,6 +111,11 @@ impl Config {
                     .unwrap_or(30),
             )
             .expect("JWT_MAXAGE_DAY was not set"),
+            admin_usernames: Self::get_env("ADMIN_USERNAMES", Some(""))
+                .split(',')
+                .map(|username| username.trim().to_string())
+                .filter(|username| !username.is_empty())
+                .collect(),
 
             smtp_host: Self::may_get("SMTP_HOST"),
             smtp_username: Self::may_get("SMTP_USERNAME"),
@@ -98,12 +125,27 @@ impl Config {
 
             ffmpeg_path: Self::may_get("FFMPEG_PATH"),
             djxl_path: Self::may_get("DJXL_PATH"),
+            temp_dir: Self::get_env("TEMP_DIR", Some("/tmp")),
+
+            prefetch_pages: Self::get_env("PREFETCH_PAGES", Some("3"))
+                .parse()
+                .unwrap_or(3),
+
+            archive_cache_capacity: Self::get_env("ARCHIVE_CACHE_CAPACITY", Some("64"))
+                .parse()
+                .unwrap_or(64),
+            archive_cache_ttl: Self::may_get("ARCHIVE_CACHE_TTL_SECS")
+                .and_then(|secs| secs.parse().ok())
+                .map(std::time::Duration::from_secs),
+
+            cover_rules,
 
-            cover_filestems: vec!["cover", "thumbnail", "folder"],
+            cover_filestems,
             native_img_formats: vec!["png", "jpg", "jpeg", "gif", "bmp", "tiff", "tif", "webp"],
             extended_img_formats: vec![
                 "png", "jpg", "jpeg", "gif", "bmp", "tiff", "tif", "webp", "jxl", "avif",
             ],
+            video_formats: vec!["mp4", "webm"],
             blurhash_dimension_cap: 20.0,
             ratio_percision: 1000,
         }
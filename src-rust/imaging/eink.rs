@@ -0,0 +1,153 @@
+use image::{
+    imageops::{self, colorops::ColorMap, FilterType},
+    DynamicImage, Luma,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// Gray levels of most e-ink panels.
+const EINK_GRAY_LEVELS: u8 = 16;
+
+/// How pages are rendered for an e-ink device.
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(default)]
+pub struct EinkProfile {
+    /// Screen width in pixels, pages are scaled down to fit.
+    pub width: u32,
+    /// Screen height in pixels, pages are scaled down to fit.
+    pub height: u32,
+    /// Below 1 darkens midtones, above 1 lightens them.
+    pub gamma: f32,
+    /// Contrast change in percent, negative values reduce it.
+    pub contrast: f32,
+    /// Dither to the 16 gray levels of the panel instead of letting it round.
+    pub dither: bool,
+}
+
+impl Default for EinkProfile {
+    fn default() -> Self {
+        Self {
+            width: 1264,
+            height: 1680,
+            gamma: 1.0,
+            contrast: 0.0,
+            dither: true,
+        }
+    }
+}
+
+impl EinkProfile {
+    /// Check the profile values, returning why it's invalid.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(1..=8192).contains(&self.width) || !(1..=8192).contains(&self.height) {
+            return Err("Width and height must be between 1 and 8192.");
+        }
+        if !(0.1..=10.0).contains(&self.gamma) {
+            return Err("Gamma must be between 0.1 and 10.");
+        }
+        if !(-100.0..=100.0).contains(&self.contrast) {
+            return Err("Contrast must be between -100 and 100.");
+        }
+        Ok(())
+    }
+
+    /// Render a page for the device: scale, convert to grayscale, adjust and dither.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        // scale first, dithering must happen at the final resolution
+        let image = match image.width() > self.width || image.height() > self.height {
+            true => image.resize(self.width, self.height, FilterType::Lanczos3),
+            false => image.clone(),
+        };
+
+        let mut gray = image.to_luma8();
+
+        if self.gamma != 1.0 {
+            let table: Vec<u8> = (0..=255u8)
+                .map(|value| (255.0 * (value as f32 / 255.0).powf(1.0 / self.gamma)).round() as u8)
+                .collect();
+            gray.pixels_mut()
+                .for_each(|pixel| pixel[0] = table[pixel[0] as usize]);
+        }
+
+        if self.contrast != 0.0 {
+            gray = imageops::contrast(&gray, self.contrast);
+        }
+
+        if self.dither {
+            imageops::dither(&mut gray, &GrayLevels(EINK_GRAY_LEVELS));
+        }
+
+        DynamicImage::ImageLuma8(gray)
+    }
+}
+
+/// Evenly spaced gray levels, for dithering.
+struct GrayLevels(u8);
+
+impl GrayLevels {
+    fn step(&self) -> f32 {
+        255.0 / (self.0 - 1) as f32
+    }
+}
+
+impl ColorMap for GrayLevels {
+    type Color = Luma<u8>;
+
+    fn index_of(&self, color: &Luma<u8>) -> usize {
+        (color[0] as f32 / self.step()).round() as usize
+    }
+
+    fn lookup(&self, index: usize) -> Option<Luma<u8>> {
+        match index < self.0 as usize {
+            true => Some(Luma([(index as f32 * self.step()).round() as u8])),
+            false => None,
+        }
+    }
+
+    fn has_lookup(&self) -> bool {
+        true
+    }
+
+    fn map_color(&self, color: &mut Luma<u8>) {
+        if let Some(mapped) = self.lookup(self.index_of(color)) {
+            *color = mapped;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_eink {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashSet;
+
+    #[test]
+    fn gray_levels() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 64, |x, y| {
+            Rgb([x as u8, y as u8 * 4, 128])
+        }));
+        let rendered = EinkProfile::default().apply(&image).to_luma8();
+
+        let levels = rendered
+            .pixels()
+            .map(|pixel| pixel[0])
+            .collect::<HashSet<_>>();
+        assert!(levels.len() <= EINK_GRAY_LEVELS as usize);
+        assert!(levels.iter().all(|level| level % 17 == 0));
+    }
+
+    #[test]
+    fn fit_screen() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(2000, 3000));
+        let profile = EinkProfile {
+            width: 600,
+            height: 800,
+            ..Default::default()
+        };
+        let rendered = profile.apply(&image);
+
+        assert_eq!((rendered.width(), rendered.height()), (533, 800));
+    }
+}
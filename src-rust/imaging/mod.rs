@@ -0,0 +1,415 @@
+mod eink;
+mod phash;
+mod video;
+
+use std::{fmt, io::Cursor, ops::Range, str::FromStr};
+
+use anyhow::anyhow;
+use image::{
+    codecs::jpeg::JpegEncoder,
+    imageops::{self, FilterType},
+    DynamicImage, GrayImage, ImageFormat, Rgb, RgbImage,
+};
+
+pub use eink::*;
+pub use phash::*;
+pub use video::*;
+
+/// Images are downscaled to this many pixels on their longest side before computing the blurhash.
+const BLURHASH_SAMPLE_SIZE: u32 = 64;
+
+/// Quality used when a transformed JPEG page is encoded again.
+const JPEG_QUALITY: u8 = 90;
+
+/// How far (in luma) a pixel can be from the border color and still count as border.
+const TRIM_TOLERANCE: u8 = 24;
+
+/// Fraction of pixels in a row or column allowed to stray from the border color,
+/// so dust and scan noise don't stop the trimming.
+const TRIM_NOISE: f32 = 0.005;
+
+/// Pages at least this many times taller than wide are sliced.
+pub const LONG_STRIP_MIN_RATIO: u32 = 3;
+
+/// Slices are at most this many times taller than wide.
+const SLICE_MAX_RATIO: u32 = 2;
+
+/// Size of one cover in a mosaic, 2:3 like most covers.
+const MOSAIC_TILE_SIZE: (u32, u32) = (300, 450);
+
+/// Covers in a mosaic, on a 2x2 grid.
+pub const MOSAIC_COVERS: usize = 4;
+
+/// One side of a double-page spread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Decode an image, keeping track of its format so it can be encoded back the same way.
+pub fn decode(buffer: &[u8]) -> anyhow::Result<(DynamicImage, ImageFormat)> {
+    let format = image::guess_format(buffer).map_err(|e| anyhow!("Guess format error: {}", e))?;
+    let image = image::load_from_memory_with_format(buffer, format)
+        .map_err(|e| anyhow!("Decode image error: {}", e))?;
+    Ok((image, format))
+}
+
+/// Encode a transformed page. JPEGs stay JPEGs, everything else becomes a lossless PNG.
+///
+/// Returns the encoded image and its content type.
+pub fn encode(
+    image: &DynamicImage,
+    format: ImageFormat,
+) -> anyhow::Result<(Vec<u8>, &'static str)> {
+    let mut buffer = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let image = DynamicImage::ImageRgb8(image.to_rgb8());
+            image
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY))
+                .map_err(|e| anyhow!("Encode image error: {}", e))?;
+            Ok((buffer, "image/jpeg"))
+        }
+        _ => {
+            image
+                .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
+                .map_err(|e| anyhow!("Encode image error: {}", e))?;
+            Ok((buffer, "image/png"))
+        }
+    }
+}
+
+/// Compute the blurhash of an image, with more components along its longer side.
+pub fn blurhash(image: &DynamicImage) -> anyhow::Result<String> {
+    let sample = image
+        .resize(
+            BLURHASH_SAMPLE_SIZE,
+            BLURHASH_SAMPLE_SIZE,
+            FilterType::Triangle,
+        )
+        .to_rgba8();
+    let (components_x, components_y) = if image.width() >= image.height() {
+        (4, 3)
+    } else {
+        (3, 4)
+    };
+
+    blurhash::encode(
+        components_x,
+        components_y,
+        sample.width(),
+        sample.height(),
+        sample.as_raw(),
+    )
+    .map_err(|e| anyhow!("Can't encode blurhash: {}", e))
+}
+
+/// A rectangle inside an image, stored as `x,y,width,height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropBox {
+    /// The whole image.
+    pub fn full(image: &DynamicImage) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    /// One half of a spread.
+    pub fn half(image: &DynamicImage, side: Side) -> Self {
+        let half = image.width() / 2;
+        match side {
+            Side::Left => Self {
+                width: half,
+                ..Self::full(image)
+            },
+            Side::Right => Self {
+                x: half,
+                width: image.width() - half,
+                ..Self::full(image)
+            },
+        }
+    }
+
+    /// A horizontal band of the image, e.g. one slice of a long strip.
+    pub fn rows(image: &DynamicImage, rows: Range<u32>) -> Self {
+        Self {
+            y: rows.start,
+            height: rows.end.saturating_sub(rows.start),
+            ..Self::full(image)
+        }
+    }
+
+    /// The part of both boxes that overlaps, or `None` if they don't.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+
+        match right > x && bottom > y {
+            true => Some(Self {
+                x,
+                y,
+                width: right - x,
+                height: bottom - y,
+            }),
+            false => None,
+        }
+    }
+
+    /// Crop the image to this box, clamped to the image bounds.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        match self.intersect(&Self::full(image)) {
+            Some(crop) => image.crop_imm(crop.x, crop.y, crop.width, crop.height),
+            None => image.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CropBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl FromStr for CropBox {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Parse crop box error: {}", e))?;
+
+        match values[..] {
+            [x, y, width, height] => Ok(Self {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(anyhow!("Parse crop box error: expected x,y,width,height")),
+        }
+    }
+}
+
+/// Find the page inside uniform white or black borders.
+///
+/// The border color is taken from the corners. Blank pages are returned whole.
+pub fn detect_borders(image: &DynamicImage) -> CropBox {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    if width == 0 || height == 0 {
+        return CropBox::full(image);
+    }
+
+    let mut corners = [
+        luma.get_pixel(0, 0)[0],
+        luma.get_pixel(width - 1, 0)[0],
+        luma.get_pixel(0, height - 1)[0],
+        luma.get_pixel(width - 1, height - 1)[0],
+    ];
+    corners.sort_unstable();
+    let background = corners[1];
+
+    let is_border_row = |y: u32| is_uniform(&luma, background, (0..width).map(|x| (x, y)));
+    let is_border_column = |x: u32| is_uniform(&luma, background, (0..height).map(|y| (x, y)));
+
+    let Some(top) = (0..height).find(|&y| !is_border_row(y)) else {
+        return CropBox::full(image);
+    };
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !is_border_row(y))
+        .unwrap_or(top);
+    let left = (0..width).find(|&x| !is_border_column(x)).unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|&x| !is_border_column(x))
+        .unwrap_or(left);
+
+    CropBox {
+        x: left,
+        y: top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    }
+}
+
+/// Find where to cut a very tall page so each slice has a bounded height.
+///
+/// Cuts are placed on uniform rows, e.g. the gaps between panels, when there's one in the
+/// last quarter of a slice. Returns the `y` offsets from 0 to the height of the page, or
+/// nothing when the page isn't tall enough to be sliced.
+pub fn slice_boundaries(image: &DynamicImage) -> Vec<u32> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height < width.saturating_mul(LONG_STRIP_MIN_RATIO) {
+        return vec![];
+    }
+
+    let luma = image.to_luma8();
+    let is_gap = |y: u32| {
+        let background = luma.get_pixel(0, y)[0];
+        is_uniform(&luma, background, (0..width).map(|x| (x, y)))
+    };
+
+    let max_height = width * SLICE_MAX_RATIO;
+    let mut boundaries = vec![0];
+    let mut start = 0;
+    while height - start > max_height {
+        let end = start + max_height;
+        let cut = (start + max_height * 3 / 4..end)
+            .rev()
+            .find(|&y| is_gap(y))
+            .unwrap_or(end);
+        boundaries.push(cut);
+        start = cut;
+    }
+    boundaries.push(height);
+
+    boundaries
+}
+
+/// Put covers side by side on a 2x2 grid, each cropped to fill its tile.
+pub fn mosaic(covers: &[DynamicImage]) -> DynamicImage {
+    let (tile_width, tile_height) = MOSAIC_TILE_SIZE;
+    let mut mosaic = RgbImage::from_pixel(tile_width * 2, tile_height * 2, Rgb([255, 255, 255]));
+    for (i, cover) in covers.iter().take(MOSAIC_COVERS).enumerate() {
+        let tile = cover
+            .resize_to_fill(tile_width, tile_height, FilterType::Triangle)
+            .to_rgb8();
+        imageops::replace(
+            &mut mosaic,
+            &tile,
+            (i as u32 % 2 * tile_width) as i64,
+            (i as u32 / 2 * tile_height) as i64,
+        );
+    }
+    DynamicImage::ImageRgb8(mosaic)
+}
+
+fn is_uniform(
+    luma: &GrayImage,
+    background: u8,
+    pixels: impl ExactSizeIterator<Item = (u32, u32)>,
+) -> bool {
+    let allowed = (pixels.len() as f32 * TRIM_NOISE) as usize;
+    pixels
+        .filter(|&(x, y)| luma.get_pixel(x, y)[0].abs_diff(background) > TRIM_TOLERANCE)
+        .nth(allowed)
+        .is_none()
+}
+
+#[cfg(test)]
+mod tests_imaging {
+    use super::*;
+
+    /// A white page with a black rectangle at (x, y, width, height).
+    fn page(width: u32, height: u32, content: CropBox) -> DynamicImage {
+        let mut image = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+        for x in content.x..content.x + content.width {
+            for y in content.y..content.y + content.height {
+                image.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn parse() {
+        let crop_box = CropBox {
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 4,
+        };
+        assert_eq!(crop_box.to_string().parse::<CropBox>().unwrap(), crop_box);
+        assert!("1,2,3".parse::<CropBox>().is_err());
+        assert!("a,2,3,4".parse::<CropBox>().is_err());
+    }
+
+    #[test]
+    fn borders() {
+        let content = CropBox {
+            x: 10,
+            y: 20,
+            width: 30,
+            height: 40,
+        };
+        assert_eq!(detect_borders(&page(100, 100, content)), content);
+    }
+
+    #[test]
+    fn blank_page() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(50, 50, Rgb([255, 255, 255])));
+        assert_eq!(detect_borders(&image), CropBox::full(&image));
+    }
+
+    #[test]
+    fn slices() {
+        // a 100x500 gradient strip with a white gap at y = 180
+        let mut image = RgbImage::from_fn(100, 500, |x, _| Rgb([(x * 2) as u8; 3]));
+        for x in 0..100 {
+            image.put_pixel(x, 180, Rgb([255, 255, 255]));
+        }
+
+        assert_eq!(
+            slice_boundaries(&DynamicImage::ImageRgb8(image)),
+            vec![0, 180, 380, 500]
+        );
+        assert!(slice_boundaries(&DynamicImage::ImageRgb8(RgbImage::new(100, 200))).is_empty());
+    }
+
+    #[test]
+    fn half_of_trimmed_spread() {
+        let image = page(
+            200,
+            100,
+            CropBox {
+                x: 10,
+                y: 10,
+                width: 180,
+                height: 80,
+            },
+        );
+        let trimmed = detect_borders(&image);
+        assert_eq!(
+            CropBox::half(&image, Side::Right).intersect(&trimmed),
+            Some(CropBox {
+                x: 100,
+                y: 10,
+                width: 90,
+                height: 80,
+            })
+        );
+    }
+
+    #[test]
+    fn mosaic_grid() {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [0, 0, 0]];
+        let covers = colors
+            .iter()
+            .map(|color| DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 320, Rgb(*color))))
+            .collect::<Vec<_>>();
+        let mosaic = mosaic(&covers).to_rgb8();
+
+        assert_eq!(mosaic.dimensions(), (600, 900));
+        assert_eq!(mosaic.get_pixel(10, 10).0, colors[0]);
+        assert_eq!(mosaic.get_pixel(590, 10).0, colors[1]);
+        assert_eq!(mosaic.get_pixel(10, 890).0, colors[2]);
+        assert_eq!(mosaic.get_pixel(590, 890).0, colors[3]);
+    }
+}
@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+
+use image::{imageops::FilterType, DynamicImage};
+
+/// Images are reduced to this many pixels per side before the DCT.
+const PHASH_SAMPLE_SIZE: usize = 32;
+/// Only the lowest frequencies are kept, 8x8 of them make a 64 bit hash.
+const PHASH_FREQUENCIES: usize = 8;
+
+/// Compute the perceptual hash of an image (DCT pHash).
+///
+/// Images that look alike, e.g. the same scan at another size or quality, get hashes only a
+/// few bits apart.
+pub fn phash(image: &DynamicImage) -> u64 {
+    let size = PHASH_SAMPLE_SIZE;
+    let sample = image
+        .resize_exact(size as u32, size as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels = sample
+        .pixels()
+        .map(|pixel| pixel[0] as f64)
+        .collect::<Vec<_>>();
+
+    // the 2D DCT is separable, transform the rows then the columns
+    let cosines = (0..PHASH_FREQUENCIES)
+        .map(|u| {
+            (0..size)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * PI / (2 * size) as f64).cos())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let rows = (0..size)
+        .flat_map(|y| {
+            let row = &pixels[y * size..(y + 1) * size];
+            cosines
+                .iter()
+                .map(|cosine| row.iter().zip(cosine).map(|(p, c)| p * c).sum::<f64>())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let frequencies = cosines
+        .iter()
+        .flat_map(|cosine| {
+            (0..PHASH_FREQUENCIES)
+                .map(|u| {
+                    (0..size)
+                        .map(|y| rows[y * PHASH_FREQUENCIES + u] * cosine[y])
+                        .sum::<f64>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // the DC term is the average brightness, leave it out of the median
+    let mut sorted = frequencies[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    frequencies
+        .iter()
+        .enumerate()
+        .fold(0, |hash, (i, frequency)| match *frequency > median {
+            true => hash | (1 << i),
+            false => hash,
+        })
+}
+
+/// How alike two perceptual hashes are, from 0 to 1.
+pub fn phash_similarity(a: u64, b: u64) -> f64 {
+    1.0 - (a ^ b).count_ones() as f64 / 64.0
+}
+
+#[cfg(test)]
+mod tests_phash {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn page(seed: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(400, 600, |x, y| {
+            let value = ((x * seed / 7 + y * (seed % 5 + 1) / 3) % 256) as u8;
+            match (x / 50 + y / (40 + seed)) % 3 {
+                0 => Rgb([value, value, value]),
+                1 => Rgb([255 - value, 255 - value, 255 - value]),
+                _ => Rgb([255, 255, 255]),
+            }
+        }))
+    }
+
+    #[test]
+    fn same_page_resized() {
+        let original = page(3);
+        let resized = original.resize_exact(267, 400, FilterType::Lanczos3);
+        let brightened = original.brighten(12);
+
+        assert!(phash_similarity(phash(&original), phash(&resized)) >= 0.9);
+        assert!(phash_similarity(phash(&original), phash(&brightened)) >= 0.9);
+    }
+
+    #[test]
+    fn different_pages() {
+        assert!(phash_similarity(phash(&page(3)), phash(&page(11))) < 0.8);
+    }
+
+    #[test]
+    fn similarity() {
+        assert_eq!(phash_similarity(0, 0), 1.0);
+        assert_eq!(phash_similarity(0, u64::MAX), 0.0);
+        assert_eq!(phash_similarity(0b1011, 0b0011), 1.0 - 1.0 / 64.0);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::anyhow;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, DynamicImage, ImageFormat,
+};
+
+use crate::models::prelude::CustomID;
+
+/// Check if an image has more than one frame: animated GIF, APNG or animated WebP.
+pub fn is_animated(buffer: &[u8]) -> bool {
+    match image::guess_format(buffer) {
+        Ok(ImageFormat::Gif) => GifDecoder::new(Cursor::new(buffer))
+            .map(|decoder| decoder.into_frames().take(2).count() > 1)
+            .unwrap_or(false),
+        Ok(ImageFormat::Png) => PngDecoder::new(Cursor::new(buffer))
+            .map(|decoder| decoder.is_apng())
+            .unwrap_or(false),
+        Ok(ImageFormat::WebP) => WebPDecoder::new(Cursor::new(buffer))
+            .map(|decoder| decoder.has_animation())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Extract the first frame of a video with ffmpeg. This is blocking.
+///
+/// The video is written to `temp_dir` first, as mp4 files can't always be read from a pipe.
+pub fn video_still(
+    ffmpeg_path: &str,
+    temp_dir: impl AsRef<Path>,
+    buffer: &[u8],
+    extension: &str,
+) -> anyhow::Result<DynamicImage> {
+    let input = TempFile::write(temp_dir, extension, buffer)?;
+
+    let output = Command::new(ffmpeg_path)
+        .args(["-v", "error", "-i"])
+        .arg(&input.0)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"])
+        .output()
+        .map_err(|e| anyhow!("Run ffmpeg error: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    image::load_from_memory_with_format(&output.stdout, ImageFormat::Png)
+        .map_err(|e| anyhow!("Decode still error: {}", e))
+}
+
+/// Transcode an animated image or a video to an H.264 mp4 that plays in every browser.
+/// This is blocking.
+pub fn transcode(
+    ffmpeg_path: &str,
+    temp_dir: impl AsRef<Path>,
+    buffer: &[u8],
+    extension: &str,
+    output: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let input = TempFile::write(temp_dir, extension, buffer)?;
+
+    let result = Command::new(ffmpeg_path)
+        .args(["-v", "error", "-y", "-i"])
+        .arg(&input.0)
+        .args([
+            "-c:v",
+            "libx264",
+            "-pix_fmt",
+            "yuv420p",
+            // yuv420p needs even dimensions
+            "-vf",
+            "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+            "-c:a",
+            "aac",
+            "-movflags",
+            "+faststart",
+            "-f",
+            "mp4",
+        ])
+        .arg(output.as_ref())
+        .output()
+        .map_err(|e| anyhow!("Run ffmpeg error: {}", e))?;
+    if !result.status.success() {
+        return Err(anyhow!(
+            "ffmpeg error: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// A file removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn write(dir: impl AsRef<Path>, extension: &str, buffer: &[u8]) -> anyhow::Result<Self> {
+        let path = dir
+            .as_ref()
+            .join(format!("yomuyume-{}.{}", CustomID::new(), extension));
+        std::fs::write(&path, buffer).map_err(|e| anyhow!("Write temp file error: {}", e))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
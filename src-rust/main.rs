@@ -1,13 +1,16 @@
 use crate::{
+    analyzer::Analyzer,
+    archive::ArchiveCache,
     config::Config,
     migrator::Migrator,
     routes::{auth, ApiDoc},
 };
 use axum::{
+    extract::DefaultBodyLimit,
     http::StatusCode,
     middleware::from_fn_with_state as apply,
     response::{IntoResponse, Response},
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use routes::*;
@@ -21,7 +24,10 @@ use utoipa::OpenApi;
 use utoipa_redoc::{Redoc, Servable};
 use utoipa_swagger_ui::SwaggerUi;
 
+mod analyzer;
+mod archive;
 mod config;
+mod imaging;
 mod library_scanner;
 mod migrator;
 mod models;
@@ -33,6 +39,8 @@ pub struct AppState {
     config: Config,
     scanning_complete: Mutex<bool>,
     scanning_progress: Mutex<f64>,
+    archive_cache: ArchiveCache,
+    search_cache: SearchCache,
 }
 
 #[derive(Debug)]
@@ -93,6 +101,13 @@ async fn main() -> Result<(), DbErr> {
     assert!(schema_manager.has_table("covers").await?);
     assert!(schema_manager.has_table("favorites").await?);
     assert!(schema_manager.has_table("progresses").await?);
+    assert!(schema_manager.has_table("category_covers").await?);
+    assert!(schema_manager.has_table("titles_search").await?);
+    assert!(schema_manager.has_table("titles_search_vocab").await?);
+    assert!(schema_manager.has_table("saved_filters").await?);
+    assert!(schema_manager.has_table("people").await?);
+    assert!(schema_manager.has_table("person_aliases").await?);
+    assert!(schema_manager.has_table("title_people").await?);
 
     info!("database migrations complete!");
 
@@ -101,6 +116,8 @@ async fn main() -> Result<(), DbErr> {
         config: config.clone(),
         scanning_complete: Mutex::new(false),
         scanning_progress: Mutex::new(0.0),
+        archive_cache: ArchiveCache::new(config.archive_cache_capacity, config.archive_cache_ttl),
+        search_cache: SearchCache::default(),
     });
 
     let app = Router::new()
@@ -118,8 +135,19 @@ async fn main() -> Result<(), DbErr> {
             "/api/index",
             Router::new()
                 .route("/filter", post(post_filter))
+                .route("/search", get(get_search))
+                .route("/suggest", get(get_suggest))
+                .route("/query/check", get(get_query_check))
                 .route("/categories", get(get_categories))
                 .route("/title/:title_id", get(get_title))
+                .route("/saved", get(get_saved_filters).post(post_saved_filter))
+                .route(
+                    "/saved/:id",
+                    put(put_saved_filter).delete(delete_saved_filter),
+                )
+                .route("/saved/:id/titles", get(get_saved_filter_titles))
+                .route("/person/:person_id", get(get_person))
+                .route("/person/:person_id/titles", get(get_person_titles))
                 .layer(apply(app_state.clone(), auth)),
         )
         .nest(
@@ -133,6 +161,7 @@ async fn main() -> Result<(), DbErr> {
                 .route("/bookmark/:id", put(put_bookmark).delete(delete_bookmark))
                 .route("/favorite/:id", put(put_favorite).delete(delete_favorite))
                 .route("/progress/:title_id/:page", put(put_progress))
+                .route("/eink", get(get_eink).put(put_eink).delete(delete_eink))
                 .layer(apply(app_state.clone(), auth)),
         )
         .nest(
@@ -140,6 +169,7 @@ async fn main() -> Result<(), DbErr> {
             Router::new()
                 .route("/tags", get(get_tags))
                 .route("/scanning_progress", get(get_scanning_progress))
+                .route("/archive_cache", get(get_archive_cache))
                 .layer(apply(app_state.clone(), auth)),
         )
         .nest(
@@ -147,6 +177,24 @@ async fn main() -> Result<(), DbErr> {
             Router::new()
                 .route("/page/:page_id", get(get_page))
                 .route("/cover/:title_id", get(get_cover))
+                .route("/category-cover/:category_id", get(get_category_cover))
+                .route("/title/:title_id/pages", get(get_batch))
+                .route("/title/:title_id/download", get(get_download))
+                .layer(apply(app_state.clone(), auth)),
+        )
+        .nest(
+            "/api/admin",
+            Router::new()
+                .route("/duplicates", get(get_duplicates))
+                .route("/duplicates/resolve", post(post_resolve_duplicate))
+                .route("/duplicates/:title_id", delete(delete_duplicate))
+                .route(
+                    "/title/:title_id/cover",
+                    put(put_cover)
+                        .post(post_cover)
+                        .layer(DefaultBodyLimit::max(MAX_COVER_SIZE)),
+                )
+                .layer(apply(app_state.clone(), admin))
                 .layer(apply(app_state.clone(), auth)),
         )
         .nest(
@@ -174,6 +222,13 @@ async fn main() -> Result<(), DbErr> {
         };
     });
 
+    let analyzer_state = app_state.clone();
+    let analyzer_handle = tokio::spawn(async move {
+        if let Err(e) = Analyzer::new(analyzer_state).run().await {
+            error!("analyzer error: {}", e);
+        };
+    });
+
     let library_scanner_handle = tokio::spawn(async move {
         if let Err(e) = library_scanner::Scanner::new(app_state.clone())
             .await
@@ -186,6 +241,7 @@ async fn main() -> Result<(), DbErr> {
 
     let _ = server_handle.await;
     let _ = library_scanner_handle.await;
+    let _ = analyzer_handle.await;
 
     Ok(())
 }
@@ -0,0 +1,56 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231115_000004_create_pages_table::Pages;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240615_000011_add_page_details"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // sqlite only supports one column per ALTER TABLE
+        for mut column in [
+            ColumnDef::new(PageDetails::Width).integer().to_owned(),
+            ColumnDef::new(PageDetails::Height).integer().to_owned(),
+            ColumnDef::new(PageDetails::Size).big_integer().to_owned(),
+            ColumnDef::new(PageDetails::Blurhash).string().to_owned(),
+        ] {
+            let table = Table::alter()
+                .table(Pages::Table)
+                .add_column(&mut column)
+                .to_owned();
+            manager.alter_table(table).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            PageDetails::Width,
+            PageDetails::Height,
+            PageDetails::Size,
+            PageDetails::Blurhash,
+        ] {
+            let table = Table::alter()
+                .table(Pages::Table)
+                .drop_column(column)
+                .to_owned();
+            manager.alter_table(table).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum PageDetails {
+    Width,
+    Height,
+    Size,
+    Blurhash,
+}
@@ -0,0 +1,73 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20231115_000003_create_titles_table::Titles, m_20231115_000004_create_pages_table::Pages,
+    m_20240615_000011_add_page_details::PageDetails,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240618_000012_add_spreads"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(
+                ColumnDef::new(Spreads::ReadingDirection)
+                    .string_len(3)
+                    .not_null()
+                    .default("ltr"),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(
+                ColumnDef::new(Spreads::IsSpread)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // pages analyzed before this migration, same threshold as the analyzer
+        let update = Query::update()
+            .table(Pages::Table)
+            .value(Spreads::IsSpread, true)
+            .and_where(
+                Expr::expr(Expr::col(PageDetails::Width).mul(5))
+                    .gte(Expr::col(PageDetails::Height).mul(6)),
+            )
+            .to_owned();
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(Spreads::IsSpread)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(Spreads::ReadingDirection)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum Spreads {
+    ReadingDirection,
+    IsSpread,
+}
@@ -0,0 +1,36 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231115_000004_create_pages_table::Pages;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240620_000013_add_page_trim_box"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(ColumnDef::new(PageTrimBox::TrimBox).string())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(PageTrimBox::TrimBox)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum PageTrimBox {
+    TrimBox,
+}
@@ -0,0 +1,65 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20231115_000003_create_titles_table::Titles, m_20231115_000004_create_pages_table::Pages,
+    m_20240615_000011_add_page_details::PageDetails,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240622_000014_add_long_strips"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(
+                ColumnDef::new(LongStrips::LongStrip)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(ColumnDef::new(LongStrips::Slices).string())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // let the analyzer slice the tall pages it has already seen
+        let update = Query::update()
+            .table(Pages::Table)
+            .value(PageDetails::Blurhash, Option::<String>::None)
+            .and_where(Expr::col(PageDetails::Height).gte(Expr::col(PageDetails::Width).mul(3)))
+            .to_owned();
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(LongStrips::Slices)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(LongStrips::LongStrip)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum LongStrips {
+    LongStrip,
+    Slices,
+}
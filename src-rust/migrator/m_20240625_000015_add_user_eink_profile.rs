@@ -0,0 +1,36 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231113_000001_create_users_table::Users;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240625_000015_add_user_eink_profile"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Users::Table)
+            .add_column(ColumnDef::new(UserEinkProfile::EinkProfile).string())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Users::Table)
+            .drop_column(UserEinkProfile::EinkProfile)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserEinkProfile {
+    EinkProfile,
+}
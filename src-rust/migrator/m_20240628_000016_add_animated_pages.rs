@@ -0,0 +1,55 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20231115_000004_create_pages_table::Pages, m_20240615_000011_add_page_details::PageDetails,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240628_000016_add_animated_pages"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(
+                ColumnDef::new(AnimatedPages::IsAnimated)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // let the analyzer look again at the formats that can be animated
+        let mut formats = Condition::any();
+        for extension in ["gif", "png", "webp", "mp4", "webm"] {
+            formats = formats.add(Expr::col(Pages::Path).like(format!("%.{}", extension)));
+        }
+        let update = Query::update()
+            .table(Pages::Table)
+            .value(PageDetails::Blurhash, Option::<String>::None)
+            .cond_where(formats)
+            .to_owned();
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(AnimatedPages::IsAnimated)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum AnimatedPages {
+    IsAnimated,
+}
@@ -0,0 +1,83 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20231115_000003_create_titles_table::Titles, m_20231115_000004_create_pages_table::Pages,
+    m_20240615_000011_add_page_details::PageDetails,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240702_000017_add_duplicates"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(ColumnDef::new(Duplicates::Phash).big_integer().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(ColumnDef::new(Duplicates::DuplicateOf).string().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Pages::Table)
+            .add_column(
+                ColumnDef::new(Duplicates::PhashPending)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // let the analyzer hash the pages it could decode before, they keep their details
+        // until then
+        let update = Query::update()
+            .table(Pages::Table)
+            .value(Duplicates::PhashPending, true)
+            .cond_where(
+                Cond::all()
+                    .add(Expr::col(Duplicates::Phash).is_null())
+                    .add(Expr::col(PageDetails::Blurhash).ne("")),
+            )
+            .to_owned();
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(Duplicates::DuplicateOf)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(Duplicates::PhashPending)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        let table = Table::alter()
+            .table(Pages::Table)
+            .drop_column(Duplicates::Phash)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum Duplicates {
+    Phash,
+    PhashPending,
+    DuplicateOf,
+}
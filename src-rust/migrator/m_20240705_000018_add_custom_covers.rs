@@ -0,0 +1,41 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231212_000008_create_covers_table::Covers;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240705_000018_add_custom_covers"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Covers::Table)
+            .add_column(
+                ColumnDef::new(CustomCovers::IsCustom)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Covers::Table)
+            .drop_column(CustomCovers::IsCustom)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum CustomCovers {
+    IsCustom,
+}
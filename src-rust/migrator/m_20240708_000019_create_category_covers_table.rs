@@ -0,0 +1,53 @@
+use super::m_20231115_000002_create_categories_table::Categories;
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240708_000019_create_category_covers_table"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::create()
+            .table(CategoryCovers::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(CategoryCovers::Id).string().primary_key())
+            .foreign_key(
+                ForeignKey::create()
+                    .name("fk-category_cover-category_id")
+                    .from(CategoryCovers::Table, CategoryCovers::Id)
+                    .to(Categories::Table, Categories::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .col(
+                ColumnDef::new(CategoryCovers::Source)
+                    .string_len(6)
+                    .not_null(),
+            )
+            .col(ColumnDef::new(CategoryCovers::Path).string().not_null())
+            .col(ColumnDef::new(CategoryCovers::Blurhash).string().not_null())
+            .col(ColumnDef::new(CategoryCovers::Ratio).integer().not_null())
+            .to_owned();
+        manager.create_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::drop().table(CategoryCovers::Table).to_owned();
+        manager.drop_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum CategoryCovers {
+    Table,
+    Id,
+    Source,
+    Path,
+    Blurhash,
+    Ratio,
+}
@@ -0,0 +1,47 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20231212_000008_create_covers_table::Covers,
+    m_20240705_000018_add_custom_covers::CustomCovers,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240710_000020_add_cover_rules"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Covers::Table)
+            .add_column(ColumnDef::new(CoverRules::Rule).string().null())
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // other covers are picked again by the analyzer
+        let update = Query::update()
+            .table(Covers::Table)
+            .value(CoverRules::Rule, "custom")
+            .cond_where(Expr::col(CustomCovers::IsCustom).eq(true))
+            .to_owned();
+        manager.exec_stmt(update).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Covers::Table)
+            .drop_column(CoverRules::Rule)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum CoverRules {
+    Rule,
+}
@@ -0,0 +1,172 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240712_000021_create_titles_search_table"
+    }
+}
+
+/// Statements that index again the titles whose ID matches `titles`, e.g. `= NEW.id`, in
+/// each of the search `tables`.
+///
+/// FTS5 rows are keyed by integers, `titles_search_ids` maps them to title IDs.
+pub fn reindex(tables: &[&str], titles: &str) -> String {
+    tables
+        .iter()
+        .map(|table| {
+            format!(
+                "DELETE FROM {table} WHERE rowid IN
+                    (SELECT id FROM titles_search_ids WHERE title_id {titles});
+                INSERT INTO {table} (rowid, title, author, description, tags, pages)
+                    SELECT ids.id, t.title, t.author, t.description,
+                        (SELECT group_concat(tags.name, ' ') FROM titles_tags
+                            JOIN tags ON tags.id = titles_tags.tag_id
+                            WHERE titles_tags.title_id = t.id),
+                        (SELECT group_concat(pages.description, ' ') FROM pages
+                            WHERE pages.title_id = t.id AND pages.description IS NOT NULL)
+                    FROM titles t JOIN titles_search_ids ids ON ids.title_id = t.id
+                    WHERE t.id {titles};"
+            )
+        })
+        .collect()
+}
+
+/// Names of the triggers from `create_triggers`.
+pub const TRIGGERS: [&str; 9] = [
+    "titles_search_insert",
+    "titles_search_update",
+    "titles_search_delete",
+    "titles_search_tag_insert",
+    "titles_search_tag_delete",
+    "titles_search_tag_rename",
+    "titles_search_page_insert",
+    "titles_search_page_update",
+    "titles_search_page_delete",
+];
+
+/// Statements creating the triggers that keep the search `tables` in sync with titles, their
+/// tags and the descriptions of their pages.
+pub fn create_triggers(tables: &[&str]) -> Vec<String> {
+    let delete = tables
+        .iter()
+        .map(|table| {
+            format!(
+                "DELETE FROM {table} WHERE rowid IN
+                    (SELECT id FROM titles_search_ids WHERE title_id = OLD.id);"
+            )
+        })
+        .collect::<String>();
+    vec![
+        format!(
+            "CREATE TRIGGER titles_search_insert AFTER INSERT ON titles BEGIN
+                INSERT INTO titles_search_ids (title_id) VALUES (NEW.id);
+                {}
+            END",
+            reindex(tables, "= NEW.id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_update
+            AFTER UPDATE OF title, author, description ON titles BEGIN
+                {}
+            END",
+            reindex(tables, "= NEW.id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_delete AFTER DELETE ON titles BEGIN
+                {}
+                DELETE FROM titles_search_ids WHERE title_id = OLD.id;
+            END",
+            delete
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_tag_insert AFTER INSERT ON titles_tags BEGIN
+                {}
+            END",
+            reindex(tables, "= NEW.title_id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_tag_delete AFTER DELETE ON titles_tags BEGIN
+                {}
+            END",
+            reindex(tables, "= OLD.title_id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_tag_rename AFTER UPDATE OF name ON tags BEGIN
+                {}
+            END",
+            reindex(
+                tables,
+                "IN (SELECT title_id FROM titles_tags WHERE tag_id = NEW.id)"
+            )
+        ),
+        // most pages have no description, skip them while scanning
+        format!(
+            "CREATE TRIGGER titles_search_page_insert AFTER INSERT ON pages
+            WHEN NEW.description IS NOT NULL BEGIN
+                {}
+            END",
+            reindex(tables, "= NEW.title_id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_page_update AFTER UPDATE OF description ON pages
+            WHEN NEW.description IS NOT OLD.description BEGIN
+                {}
+            END",
+            reindex(tables, "= NEW.title_id")
+        ),
+        format!(
+            "CREATE TRIGGER titles_search_page_delete AFTER DELETE ON pages
+            WHEN OLD.description IS NOT NULL BEGIN
+                {}
+            END",
+            reindex(tables, "= OLD.title_id")
+        ),
+    ]
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let tables = ["titles_search"];
+        let statements = [
+            vec![
+                "CREATE TABLE titles_search_ids (
+                    id INTEGER PRIMARY KEY,
+                    title_id TEXT NOT NULL UNIQUE
+                )"
+                .to_string(),
+                "CREATE VIRTUAL TABLE titles_search USING fts5(
+                    title, author, description, tags, pages,
+                    tokenize = 'unicode61 remove_diacritics 2'
+                )"
+                .to_string(),
+            ],
+            create_triggers(&tables),
+            vec![
+                "INSERT INTO titles_search_ids (title_id) SELECT id FROM titles".to_string(),
+                reindex(&tables, "IS NOT NULL"),
+            ],
+        ];
+        for statement in statements.concat() {
+            db.execute_unprepared(&statement).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for trigger in TRIGGERS {
+            db.execute_unprepared(&format!("DROP TRIGGER IF EXISTS {}", trigger))
+                .await?;
+        }
+        db.execute_unprepared("DROP TABLE IF EXISTS titles_search")
+            .await?;
+        db.execute_unprepared("DROP TABLE IF EXISTS titles_search_ids")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use super::m_20231113_000001_create_users_table::Users;
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240715_000022_create_saved_filters_table"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::create()
+            .table(SavedFilters::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(SavedFilters::Id).string().primary_key())
+            .col(ColumnDef::new(SavedFilters::UserId).string().not_null())
+            .foreign_key(
+                ForeignKey::create()
+                    .name("fk-saved_filter-user_id")
+                    .from(SavedFilters::Table, SavedFilters::UserId)
+                    .to(Users::Table, Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .col(ColumnDef::new(SavedFilters::Name).string().not_null())
+            .col(ColumnDef::new(SavedFilters::Filter).string().not_null())
+            .col(
+                ColumnDef::new(SavedFilters::Pinned)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .col(ColumnDef::new(SavedFilters::CreatedAt).string().not_null())
+            .col(ColumnDef::new(SavedFilters::UpdatedAt).string().not_null())
+            .index(
+                Index::create()
+                    .name("idx-saved_filter-user_id-name")
+                    .col(SavedFilters::UserId)
+                    .col(SavedFilters::Name)
+                    .unique(),
+            )
+            .to_owned();
+        manager.create_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::drop().table(SavedFilters::Table).to_owned();
+        manager.drop_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum SavedFilters {
+    Table,
+    Id,
+    UserId,
+    Name,
+    Filter,
+    Pinned,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,179 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231115_000003_create_titles_table::Titles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240720_000024_create_people_tables"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::create()
+            .table(People::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(People::Id).string().primary_key())
+            .col(
+                ColumnDef::new(People::Name)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .to_owned();
+        manager.create_table(table).await?;
+
+        let table = Table::create()
+            .table(PersonAliases::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(PersonAliases::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(PersonAliases::PersonId).string().not_null())
+            .foreign_key(
+                ForeignKey::create()
+                    .name("fk-person_alias-person_id")
+                    .from(PersonAliases::Table, PersonAliases::PersonId)
+                    .to(People::Table, People::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .col(
+                ColumnDef::new(PersonAliases::Name)
+                    .string()
+                    .not_null()
+                    .unique_key(),
+            )
+            .to_owned();
+        manager.create_table(table).await?;
+
+        let table = Table::create()
+            .table(TitlePeople::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(TitlePeople::Id)
+                    .integer()
+                    .not_null()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(TitlePeople::TitleId).string().not_null())
+            .foreign_key(
+                ForeignKey::create()
+                    .name("fk-title_person-title_id")
+                    .from(TitlePeople::Table, TitlePeople::TitleId)
+                    .to(Titles::Table, Titles::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .col(ColumnDef::new(TitlePeople::PersonId).string().not_null())
+            .foreign_key(
+                ForeignKey::create()
+                    .name("fk-title_person-person_id")
+                    .from(TitlePeople::Table, TitlePeople::PersonId)
+                    .to(People::Table, People::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .col(ColumnDef::new(TitlePeople::Role).string_len(12).not_null())
+            .index(
+                Index::create()
+                    .name("idx-title_person-title_id-person_id-role")
+                    .col(TitlePeople::TitleId)
+                    .col(TitlePeople::PersonId)
+                    .col(TitlePeople::Role)
+                    .unique(),
+            )
+            .to_owned();
+        manager.create_table(table).await?;
+
+        // "other works by" looks titles up by person
+        let index = Index::create()
+            .name("idx-title_person-person_id")
+            .table(TitlePeople::Table)
+            .col(TitlePeople::PersonId)
+            .to_owned();
+        manager.create_index(index).await?;
+
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(
+                ColumnDef::new(TitlePeopleAnalyzed::PeopleAnalyzed)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        // credit the people again once the scanner updates the archive or author
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE TRIGGER titles_people_reanalyze
+                AFTER UPDATE OF hash, author ON titles
+                WHEN NEW.hash IS NOT OLD.hash OR NEW.author IS NOT OLD.author BEGIN
+                    UPDATE titles SET people_analyzed = FALSE WHERE id = NEW.id;
+                END",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TRIGGER IF EXISTS titles_people_reanalyze")
+            .await?;
+
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(TitlePeopleAnalyzed::PeopleAnalyzed)
+            .to_owned();
+        manager.alter_table(table).await?;
+
+        for table in [
+            TitlePeople::Table.into_iden(),
+            PersonAliases::Table.into_iden(),
+            People::Table.into_iden(),
+        ] {
+            let table = Table::drop().table(table).to_owned();
+            manager.drop_table(table).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum People {
+    Table,
+    Id,
+    Name,
+}
+
+#[derive(Iden)]
+pub enum PersonAliases {
+    Table,
+    Id,
+    PersonId,
+    Name,
+}
+
+#[derive(Iden)]
+pub enum TitlePeople {
+    Table,
+    Id,
+    TitleId,
+    PersonId,
+    Role,
+}
+
+#[derive(Iden)]
+pub enum TitlePeopleAnalyzed {
+    PeopleAnalyzed,
+}
@@ -0,0 +1,37 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231115_000003_create_titles_table::Titles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240722_000025_add_title_settings_modified"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // NULL, so the analyzer reads the settings of every title once
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(ColumnDef::new(TitleSettings::SettingsModified).big_integer())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(TitleSettings::SettingsModified)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum TitleSettings {
+    SettingsModified,
+}
@@ -0,0 +1,37 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231212_000008_create_covers_table::Covers;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240724_000026_add_cover_rules_hash"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // NULL, so covers picked before are checked against the current rules once
+        let table = Table::alter()
+            .table(Covers::Table)
+            .add_column(ColumnDef::new(CoverRulesHash::RulesHash).string().null())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Covers::Table)
+            .drop_column(CoverRulesHash::RulesHash)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum CoverRulesHash {
+    RulesHash,
+}
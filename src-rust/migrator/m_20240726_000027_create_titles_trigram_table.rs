@@ -0,0 +1,58 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20240712_000021_create_titles_search_table::{create_triggers, TRIGGERS};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240726_000027_create_titles_trigram_table"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // substrings of 3 characters or more, e.g. inside titles written without spaces
+        db.execute_unprepared(
+            "CREATE VIRTUAL TABLE titles_trigram USING fts5(
+                title, author, description, tags, pages,
+                tokenize = 'trigram'
+            )",
+        )
+        .await?;
+
+        replace_triggers(manager, &["titles_search", "titles_trigram"]).await?;
+
+        db.execute_unprepared(
+            "INSERT INTO titles_trigram (rowid, title, author, description, tags, pages)
+                SELECT rowid, title, author, description, tags, pages FROM titles_search",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        replace_triggers(manager, &["titles_search"]).await?;
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TABLE IF EXISTS titles_trigram")
+            .await?;
+        Ok(())
+    }
+}
+
+/// Keep only `tables` in sync with titles.
+async fn replace_triggers(manager: &SchemaManager<'_>, tables: &[&str]) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+    for trigger in TRIGGERS {
+        db.execute_unprepared(&format!("DROP TRIGGER IF EXISTS {}", trigger))
+            .await?;
+    }
+    for statement in create_triggers(tables) {
+        db.execute_unprepared(&statement).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use axum::async_trait;
+use sea_orm_migration::prelude::*;
+
+use super::m_20231115_000003_create_titles_table::Titles;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20240728_000028_add_title_people_modified"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // NULL, so the analyzer credits the people of every title with a toml once more
+        let table = Table::alter()
+            .table(Titles::Table)
+            .add_column(ColumnDef::new(TitlePeopleModified::PeopleModified).big_integer())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(Titles::Table)
+            .drop_column(TitlePeopleModified::PeopleModified)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(Iden)]
+pub enum TitlePeopleModified {
+    PeopleModified,
+}
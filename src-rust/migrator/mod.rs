@@ -11,6 +11,24 @@ mod m_20231212_000007_create_bookmarks_table;
 mod m_20231212_000008_create_covers_table;
 mod m_20231212_000009_create_favorites_table;
 mod m_20231212_000010_create_progresses_table;
+mod m_20240615_000011_add_page_details;
+mod m_20240618_000012_add_spreads;
+mod m_20240620_000013_add_page_trim_box;
+mod m_20240622_000014_add_long_strips;
+mod m_20240625_000015_add_user_eink_profile;
+mod m_20240628_000016_add_animated_pages;
+mod m_20240702_000017_add_duplicates;
+mod m_20240705_000018_add_custom_covers;
+mod m_20240708_000019_create_category_covers_table;
+mod m_20240710_000020_add_cover_rules;
+mod m_20240712_000021_create_titles_search_table;
+mod m_20240715_000022_create_saved_filters_table;
+mod m_20240718_000023_create_titles_search_vocab;
+mod m_20240720_000024_create_people_tables;
+mod m_20240722_000025_add_title_settings_modified;
+mod m_20240724_000026_add_cover_rules_hash;
+mod m_20240726_000027_create_titles_trigram_table;
+mod m_20240728_000028_add_title_people_modified;
 
 pub struct Migrator;
 
@@ -28,6 +46,24 @@ impl MigratorTrait for Migrator {
             Box::new(m_20231212_000008_create_covers_table::Migration),
             Box::new(m_20231212_000009_create_favorites_table::Migration),
             Box::new(m_20231212_000010_create_progresses_table::Migration),
+            Box::new(m_20240615_000011_add_page_details::Migration),
+            Box::new(m_20240618_000012_add_spreads::Migration),
+            Box::new(m_20240620_000013_add_page_trim_box::Migration),
+            Box::new(m_20240622_000014_add_long_strips::Migration),
+            Box::new(m_20240625_000015_add_user_eink_profile::Migration),
+            Box::new(m_20240628_000016_add_animated_pages::Migration),
+            Box::new(m_20240702_000017_add_duplicates::Migration),
+            Box::new(m_20240705_000018_add_custom_covers::Migration),
+            Box::new(m_20240708_000019_create_category_covers_table::Migration),
+            Box::new(m_20240710_000020_add_cover_rules::Migration),
+            Box::new(m_20240712_000021_create_titles_search_table::Migration),
+            Box::new(m_20240715_000022_create_saved_filters_table::Migration),
+            Box::new(m_20240718_000023_create_titles_search_vocab::Migration),
+            Box::new(m_20240720_000024_create_people_tables::Migration),
+            Box::new(m_20240722_000025_add_title_settings_modified::Migration),
+            Box::new(m_20240724_000026_add_cover_rules_hash::Migration),
+            Box::new(m_20240726_000027_create_titles_trigram_table::Migration),
+            Box::new(m_20240728_000028_add_title_people_modified::Migration),
         ]
     }
 }
@@ -20,8 +20,8 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::titles::Entity")]
     Titles,
-    #[sea_orm(has_one = "super::covers::Entity")]
-    Covers,
+    #[sea_orm(has_one = "super::category_covers::Entity")]
+    CategoryCovers,
 }
 
 impl Related<super::titles::Entity> for Entity {
@@ -30,9 +30,9 @@ impl Related<super::titles::Entity> for Entity {
     }
 }
 
-impl Related<super::covers::Entity> for Entity {
+impl Related<super::category_covers::Entity> for Entity {
     fn to() -> RelationDef {
-        Relation::Covers.def()
+        Relation::CategoryCovers.def()
     }
 }
 
@@ -0,0 +1,51 @@
+use sea_orm::entity::prelude::*;
+use utoipa::ToSchema;
+
+use crate::models::prelude::{CategoryCoverSource, CategoryID, TitleID};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, ToSchema)]
+#[schema(as = CategoryCover)]
+#[sea_orm(table_name = "category_covers")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: CategoryID,
+    pub source: CategoryCoverSource,
+    /// Path of the image for `File`, comma separated title IDs for `Title` and `Mosaic`.
+    pub path: String,
+    pub blurhash: String,
+    pub ratio: u32,
+}
+
+impl Model {
+    /// The titles whose covers make up this one.
+    pub fn title_ids(&self) -> Vec<TitleID> {
+        match self.source {
+            CategoryCoverSource::File => vec![],
+            _ => self
+                .path
+                .split(',')
+                .filter_map(|id| TitleID::from(id.to_string()).ok())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::categories::Entity",
+        from = "Column::Id",
+        to = "super::categories::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Categories,
+}
+
+impl Related<super::categories::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Categories.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,306 @@
+use std::{io::Cursor, path::Path};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::models::prelude::pages;
+
+/// A way to pick the cover of a title among its pages.
+///
+/// Rules are tried in order and the first one that matches a page wins, e.g.
+/// `[{ rule = "stems", stems = ["cover"] }, { rule = "first_page" }]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum CoverRule {
+    /// A page named one of `stems`, without its extension and ignoring case.
+    Stems { stems: Vec<String> },
+    /// The first page whose path matches `pattern`.
+    Regex { pattern: CoverPattern },
+    /// The first page.
+    FirstPage,
+    /// The largest image among the first `pages` pages, e.g. a color cover before grayscale pages.
+    Largest { pages: usize },
+    /// The first page whose width divided by its height is between `min` and `max`.
+    AspectRatio { min: f64, max: f64 },
+}
+
+/// The regex of a cover rule, compiled once when the rules are read.
+///
+/// A pattern that doesn't compile matches no page, `CoverRule::parse_list` rejects it and
+/// the analyzer warns about it in a category TOML.
+#[derive(Debug, Clone)]
+pub struct CoverPattern {
+    source: String,
+    regex: Result<Regex, regex::Error>,
+}
+
+impl CoverPattern {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            regex: Regex::new(source),
+        }
+    }
+
+    /// Why the pattern doesn't compile.
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.regex.as_ref().err()
+    }
+}
+
+impl PartialEq for CoverPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Serialize for CoverPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for CoverPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|source| Self::new(&source))
+    }
+}
+
+#[derive(Deserialize)]
+struct CoverRules {
+    rules: Vec<CoverRule>,
+}
+
+impl CoverRule {
+    /// The name of the rule, as written in `rule = "..."`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Stems { .. } => "stems",
+            Self::Regex { .. } => "regex",
+            Self::FirstPage => "first_page",
+            Self::Largest { .. } => "largest",
+            Self::AspectRatio { .. } => "aspect_ratio",
+        }
+    }
+
+    /// Look for a page `stems`, then use the first page.
+    pub fn defaults(stems: &[&str]) -> Vec<CoverRule> {
+        vec![
+            Self::Stems {
+                stems: stems.iter().map(|stem| stem.to_string()).collect(),
+            },
+            Self::FirstPage,
+        ]
+    }
+
+    /// Parse a TOML array of rules, as in the `cover_rules` of a category.
+    pub fn parse_list(raw: &str) -> Result<Vec<CoverRule>, String> {
+        let rules = toml::from_str::<CoverRules>(&format!("rules = {}", raw))
+            .map_err(|e| format!("can't parse cover rules: {}", e))?
+            .rules;
+        for rule in &rules {
+            if let Self::Regex { pattern } = rule {
+                if let Some(e) = pattern.error() {
+                    return Err(format!("invalid cover regex: {}", e));
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// The page this rule picks, among pages in reading order.
+    fn pick<'a>(&self, pages: &'a [pages::Model]) -> Option<&'a pages::Model> {
+        match self {
+            Self::Stems { stems } => pages.iter().find(|page| {
+                Path::new(&page.path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy())
+                    .is_some_and(|stem| stems.iter().any(|s| s.eq_ignore_ascii_case(&stem)))
+            }),
+            Self::Regex { pattern } => {
+                let regex = pattern.regex.as_ref().ok()?;
+                pages.iter().find(|page| regex.is_match(&page.path))
+            }
+            Self::FirstPage => pages.first(),
+            Self::Largest { pages: count } => pages
+                .iter()
+                .take(*count)
+                .filter_map(|page| Some((page, page.width? as u64 * page.height? as u64)))
+                .fold(
+                    None,
+                    |largest: Option<(&pages::Model, u64)>, (page, area)| match largest {
+                        Some((_, largest_area)) if largest_area >= area => largest,
+                        _ => Some((page, area)),
+                    },
+                )
+                .map(|(page, _)| page),
+            Self::AspectRatio { min, max } => {
+                pages.iter().find(|page| match (page.width, page.height) {
+                    (Some(width), Some(height)) if height > 0 => {
+                        (*min..=*max).contains(&(width as f64 / height as f64))
+                    }
+                    _ => false,
+                })
+            }
+        }
+    }
+}
+
+/// Pick the cover among pages in reading order, along with the rule that picked it.
+pub fn pick_cover<'a, 'r>(
+    rules: &'r [CoverRule],
+    pages: &'a [pages::Model],
+) -> Option<(&'a pages::Model, &'r CoverRule)> {
+    rules
+        .iter()
+        .find_map(|rule| rule.pick(pages).map(|page| (page, rule)))
+}
+
+/// Identify a list of rules, so covers can be picked again when the rules change.
+pub fn rules_hash(rules: &[CoverRule]) -> String {
+    let rules = serde_json::to_string(rules).unwrap_or_default();
+    let hash = murmur3::murmur3_32(&mut Cursor::new(rules), 0).unwrap_or_default();
+    format!("{:08x}", hash)
+}
+
+#[cfg(test)]
+mod tests_cover_rules {
+    use super::*;
+    use crate::models::prelude::CustomID;
+
+    fn page(path: &str, width: u32, height: u32) -> pages::Model {
+        pages::Model {
+            id: CustomID::new(),
+            title_id: CustomID::new(),
+            path: path.to_string(),
+            description: None,
+            width: Some(width),
+            height: Some(height),
+            size: None,
+            blurhash: Some(String::new()),
+            is_spread: false,
+            trim_box: None,
+            slices: None,
+            is_animated: false,
+            phash: None,
+            phash_pending: false,
+        }
+    }
+
+    fn pages() -> Vec<pages::Model> {
+        vec![
+            page("000.jpg", 1600, 1200),
+            page("001.jpg", 800, 1200),
+            page("002.jpg", 1000, 1500),
+            page("Cover.png", 800, 1200),
+        ]
+    }
+
+    fn picked(rules: &[CoverRule]) -> Option<(String, &'static str)> {
+        let pages = pages();
+        pick_cover(rules, &pages).map(|(page, rule)| (page.path.clone(), rule.name()))
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let rules = CoverRule::defaults(&["cover", "folder"]);
+        assert_eq!(picked(&rules), Some(("Cover.png".to_string(), "stems")));
+
+        let rules = CoverRule::defaults(&["folder"]);
+        assert_eq!(picked(&rules), Some(("000.jpg".to_string(), "first_page")));
+    }
+
+    #[test]
+    fn regex() {
+        let rules = [CoverRule::Regex {
+            pattern: CoverPattern::new(r"^0*1\."),
+        }];
+        assert_eq!(picked(&rules), Some(("001.jpg".to_string(), "regex")));
+
+        // never picks anything, rather than failing to read the category
+        let rules = [CoverRule::Regex {
+            pattern: CoverPattern::new("("),
+        }];
+        assert_eq!(picked(&rules), None);
+    }
+
+    #[test]
+    fn largest() {
+        let rules = [CoverRule::Largest { pages: 3 }];
+        assert_eq!(picked(&rules), Some(("000.jpg".to_string(), "largest")));
+
+        let pages = pages();
+        let rules = [CoverRule::Largest { pages: 2 }];
+        assert_eq!(
+            pick_cover(&rules, &pages[1..]).map(|(page, _)| page.path.as_str()),
+            Some("002.jpg")
+        );
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        let rules = [
+            CoverRule::AspectRatio { min: 2.0, max: 3.0 },
+            CoverRule::AspectRatio { min: 0.6, max: 0.7 },
+        ];
+        assert_eq!(
+            picked(&rules),
+            Some(("001.jpg".to_string(), "aspect_ratio"))
+        );
+    }
+
+    #[test]
+    fn hashed() {
+        let rules = CoverRule::defaults(&["cover"]);
+        assert_eq!(rules_hash(&rules), rules_hash(&rules.clone()));
+        assert_ne!(rules_hash(&rules), rules_hash(&rules[1..]));
+        assert_ne!(
+            rules_hash(&rules),
+            rules_hash(&CoverRule::defaults(&["folder"]))
+        );
+    }
+
+    #[test]
+    fn no_match() {
+        assert_eq!(picked(&[]), None);
+        assert_eq!(pick_cover(&CoverRule::defaults(&["cover"]), &[]), None);
+    }
+
+    #[test]
+    fn parse_list() {
+        let rules = CoverRule::parse_list(
+            r#"[{ rule = "stems", stems = ["cover"] }, { rule = "largest", pages = 3 }, { rule = "first_page" }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                CoverRule::Stems {
+                    stems: vec!["cover".to_string()]
+                },
+                CoverRule::Largest { pages: 3 },
+                CoverRule::FirstPage,
+            ]
+        );
+
+        assert!(CoverRule::parse_list(r#"[{ rule = "regex", pattern = "(" }]"#).is_err());
+        assert_eq!(
+            CoverRule::parse_list(r#"[{ rule = "regex", pattern = "^cover" }]"#).unwrap(),
+            vec![CoverRule::Regex {
+                pattern: CoverPattern::new("^cover")
+            }]
+        );
+        assert!(CoverRule::parse_list(r#"[{ rule = "newest" }]"#).is_err());
+    }
+
+    #[test]
+    fn pattern_error() {
+        assert!(CoverPattern::new("^cover").error().is_none());
+        let rules: CoverRules =
+            toml::from_str(r#"rules = [{ rule = "regex", pattern = "(" }]"#).unwrap();
+        let CoverRule::Regex { pattern } = &rules.rules[0] else {
+            panic!("not a regex rule");
+        };
+        assert!(pattern.error().is_some());
+    }
+}
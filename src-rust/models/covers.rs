@@ -9,9 +9,18 @@ use crate::models::prelude::CustomID;
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: CustomID,
+    /// Path of the cover inside the archive, or file name of a custom cover.
     pub path: String,
     pub blurhash: String,
     pub ratio: u32,
+    /// Uploaded image stored next to the archive of the title, instead of one of its pages.
+    pub is_custom: bool,
+    /// What picked the cover: `toml`, `custom`, the name of a cover rule, or `none` when no
+    /// rule matched. `None` until the analyzer has applied the cover rules.
+    pub rule: Option<String>,
+    /// Hash of the cover rules `rule` was picked with, the cover is picked again when the
+    /// rules of its category or `COVER_RULES` change.
+    pub rules_hash: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -46,4 +55,12 @@ impl Related<super::titles::Entity> for Entity {
     }
 }
 
+impl Model {
+    /// Where a custom cover is stored, next to the archive at `title_path`.
+    pub fn custom_path(&self, title_path: &str) -> Option<std::path::PathBuf> {
+        self.is_custom
+            .then(|| std::path::Path::new(title_path).with_file_name(&self.path))
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,44 @@
+use super::prelude::*;
+use crate::migrator::Migrator;
+
+use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, Set};
+use sea_orm_migration::MigratorTrait;
+
+/// An empty library in memory, with every migration run.
+pub async fn memory_db() -> DatabaseConnection {
+    let db = Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    db
+}
+
+pub async fn insert_category(db: &DatabaseConnection, name: &str) -> CategoryID {
+    let id = CustomID::new();
+    categories::ActiveModel {
+        id: Set(id.clone()),
+        name: Set(name.to_string()),
+        description: Set(None),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    id
+}
+
+pub async fn insert_user(db: &DatabaseConnection, username: &str) -> UserID {
+    let id = CustomID::new();
+    users::ActiveModel {
+        id: Set(id.clone()),
+        username: Set(username.to_string()),
+        email: Set(format!("{}@example.com", username)),
+        profile_picture: Set(None),
+        created_at: Set(String::new()),
+        updated_at: Set(String::new()),
+        password: Set(String::new()),
+        is_verified: Set(true),
+        eink_profile: Set(None),
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    id
+}
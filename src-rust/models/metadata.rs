@@ -2,10 +2,14 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 use tracing::info;
 
-use crate::models::prelude::CategoryID;
+use crate::models::{
+    cover_rules::CoverRule,
+    prelude::{CategoryID, PersonRole, ReadingDirection},
+};
 
 // Read a toml file, create it if not exists
 fn try_read_toml(path: &Path) -> Result<String, String> {
@@ -22,14 +26,32 @@ fn try_read_toml(path: &Path) -> Result<String, String> {
     }
 }
 
+/// Last modification time of a toml file, in milliseconds since the epoch, `None` when it
+/// doesn't exist. The extension is automatically added to the path.
+pub fn toml_modified(path: &Path) -> Option<i64> {
+    std::fs::metadata(path.with_extension("toml"))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_millis() as i64)
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TitleMetadata {
     pub title: Option<String>,
     pub description: Option<String>,
     pub cover: Option<String>,
+    /// Image next to the archive used as the cover instead of `cover`, set by uploading one.
+    pub custom_cover: Option<String>,
     pub author: Option<String>,
     pub release: Option<String>,
     pub tags: Option<Vec<String>>,
+    pub reading_direction: Option<ReadingDirection>,
+    /// Overrides `long_strip` of the category.
+    pub long_strip: Option<bool>,
+    /// Credited along with the ones of the ComicInfo.xml inside the archive, `author` is only
+    /// split into people when neither has any.
+    pub people: Option<Vec<PersonMetadata>>,
 
     /// "page file name" = "description"
     pub descriptions: Option<HashMap<String, String>>,
@@ -38,6 +60,21 @@ pub struct TitleMetadata {
     pub path: PathBuf,
 }
 
+/// ```toml
+/// [[people]]
+/// name = "Oda Eiichirō"
+/// roles = ["writer", "artist"]
+/// aliases = ["Eiichiro Oda"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersonMetadata {
+    pub name: String,
+    /// Credited as `author` when left out.
+    pub roles: Option<Vec<PersonRole>>,
+    /// Other spellings of the name, so they're found as the same person.
+    pub aliases: Option<Vec<String>>,
+}
+
 impl TitleMetadata {
     /// Create a new TitleMetadata from a toml file. The extension
     /// is automatically added to the path.
@@ -79,6 +116,16 @@ impl TitleMetadata {
     /// Save the cover path to the metadata file
     pub fn set_cover(&mut self, value: String) -> Result<(), String> {
         self.cover = Some(value);
+        self.save()
+    }
+
+    /// Save the custom cover file name to the metadata file, `None` to use `cover` again.
+    pub fn set_custom_cover(&mut self, value: Option<String>) -> Result<(), String> {
+        self.custom_cover = value;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
         let toml_string: String = match toml::to_string(self) {
             Ok(s) => s,
             Err(e) => return Err(format!("can't convert to toml: {}", e)),
@@ -96,6 +143,10 @@ pub struct CategoryMetadata {
     pub name: Option<String>,
     pub description: Option<String>,
     pub cover: Option<String>,
+    /// Read the titles of this category as long vertical strips.
+    pub long_strip: Option<bool>,
+    /// How the covers of its titles are picked, instead of `COVER_RULES`.
+    pub cover_rules: Option<Vec<CoverRule>>,
 
     #[serde(skip)]
     pub path: PathBuf,
@@ -146,9 +197,13 @@ mod tests_title {
         assert_eq!(metadata.title, None);
         assert_eq!(metadata.description, None);
         assert_eq!(metadata.cover, None);
+        assert_eq!(metadata.custom_cover, None);
         assert_eq!(metadata.author, None);
         assert_eq!(metadata.release, None);
         assert_eq!(metadata.tags, None);
+        assert_eq!(metadata.reading_direction, None);
+        assert_eq!(metadata.long_strip, None);
+        assert_eq!(metadata.people, None);
         assert_eq!(metadata.descriptions, None);
 
         assert!(metadata.path.exists());
@@ -168,6 +223,62 @@ mod tests_title {
         remove_file("test-title-cover.toml").unwrap();
     }
 
+    #[test]
+    fn set_custom_cover() {
+        let path = Path::new("test-title-custom-cover.toml");
+        let mut metadata = TitleMetadata::from(path).unwrap();
+        metadata.set_cover("test.png".to_string()).unwrap();
+        metadata
+            .set_custom_cover(Some("test-title-custom-cover.cover.jpg".to_string()))
+            .unwrap();
+
+        let metadata = TitleMetadata::from(path).unwrap();
+        assert_eq!(metadata.cover.as_deref(), Some("test.png"));
+        assert_eq!(
+            metadata.custom_cover.as_deref(),
+            Some("test-title-custom-cover.cover.jpg")
+        );
+        remove_file("test-title-custom-cover.toml").unwrap();
+    }
+
+    #[test]
+    fn reading_direction() {
+        let path = Path::new("test-title-reading-direction.toml");
+        std::fs::write(path, "reading_direction = \"rtl\"").unwrap();
+        let metadata = TitleMetadata::from(path).unwrap();
+
+        assert_eq!(metadata.reading_direction, Some(ReadingDirection::Rtl));
+        remove_file("test-title-reading-direction.toml").unwrap();
+    }
+
+    #[test]
+    fn people() {
+        let path = Path::new("test-title-people.toml");
+        std::fs::write(
+            path,
+            "[[people]]\nname = \"Oda\"\nroles = [\"writer\", \"cover_artist\"]\n\
+            aliases = [\"Oda E.\"]\n\n[[people]]\nname = \"Someone\"\n",
+        )
+        .unwrap();
+        let metadata = TitleMetadata::from(path).unwrap();
+
+        assert_eq!(
+            metadata.people,
+            Some(vec![
+                PersonMetadata {
+                    name: "Oda".to_string(),
+                    roles: Some(vec![PersonRole::Writer, PersonRole::CoverArtist]),
+                    aliases: Some(vec!["Oda E.".to_string()]),
+                },
+                PersonMetadata {
+                    name: "Someone".to_string(),
+                    ..Default::default()
+                },
+            ])
+        );
+        remove_file("test-title-people.toml").unwrap();
+    }
+
     #[test]
     fn path_no_ext() {
         let path = Path::new("test-title-no-ext");
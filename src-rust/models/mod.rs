@@ -1,12 +1,20 @@
 pub mod auth;
 pub mod bookmarks;
 pub mod categories;
+pub mod category_covers;
+pub mod cover_rules;
 pub mod covers;
 pub mod favorites;
+#[cfg(test)]
+pub mod fixtures;
 pub mod metadata;
 pub mod pages;
+pub mod people;
+pub mod person_aliases;
 pub mod progresses;
+pub mod saved_filters;
 pub mod tags;
+pub mod title_people;
 pub mod titles;
 pub mod titles_tags;
 pub mod users;
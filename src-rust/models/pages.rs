@@ -15,6 +15,51 @@ pub struct Model {
     pub title_id: TitleID,
     pub path: String,
     pub description: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Size in bytes of the decompressed image.
+    pub size: Option<i64>,
+    /// Empty when the page couldn't be decoded, `None` until it has been analyzed.
+    pub blurhash: Option<String>,
+    /// Two pages scanned as one wide image.
+    pub is_spread: bool,
+    /// `x,y,width,height` of the page without its borders, computed on the first `?trim=true`.
+    pub trim_box: Option<String>,
+    /// Comma separated `y` offsets where a very tall page is cut into slices, from 0 to its height.
+    pub slices: Option<String>,
+    /// Animated image or video, fetch it with `?transcode=true` to get an mp4.
+    pub is_animated: bool,
+    /// 64 bit perceptual hash, compared to find duplicate titles.
+    pub phash: Option<i64>,
+    /// Analyzed before perceptual hashes were added, the analyzer hashes it again.
+    pub phash_pending: bool,
+}
+
+impl Model {
+    /// The vertical ranges of the slices of a tall page, empty when it isn't sliced.
+    pub fn slices(&self) -> Vec<std::ops::Range<u32>> {
+        let boundaries = self
+            .slices
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|offset| offset.trim().parse::<u32>().ok())
+            .collect::<Vec<_>>();
+
+        boundaries
+            .windows(2)
+            .filter(|pair| pair[0] < pair[1])
+            .map(|pair| pair[0]..pair[1])
+            .collect()
+    }
+
+    /// How many pages this page is shown as, more than one for sliced pages of long strips.
+    pub fn virtual_count(&self, long_strip: bool) -> usize {
+        match long_strip {
+            true => self.slices().len().max(1),
+            false => 1,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
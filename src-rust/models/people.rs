@@ -0,0 +1,38 @@
+use sea_orm::entity::prelude::*;
+use utoipa::ToSchema;
+
+use crate::models::prelude::CustomID;
+
+pub type PersonID = CustomID;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, ToSchema)]
+#[schema(as = Person)]
+#[sea_orm(table_name = "people")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: PersonID,
+    /// The first spelling the analyzer came across, other ones are aliases.
+    pub name: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::person_aliases::Entity")]
+    PersonAliases,
+    #[sea_orm(has_many = "super::title_people::Entity")]
+    TitlePeople,
+}
+
+impl Related<super::person_aliases::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PersonAliases.def()
+    }
+}
+
+impl Related<super::title_people::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TitlePeople.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
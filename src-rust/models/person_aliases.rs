@@ -0,0 +1,35 @@
+use sea_orm::entity::prelude::*;
+use utoipa::ToSchema;
+
+use crate::models::prelude::PersonID;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, ToSchema)]
+#[schema(as = PersonAlias)]
+#[sea_orm(table_name = "person_aliases")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    pub person_id: PersonID,
+    /// Another spelling of the name, unique among all people.
+    pub name: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::people::Entity",
+        from = "Column::PersonId",
+        to = "super::people::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    People,
+}
+
+impl Related<super::people::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::People.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,19 +1,29 @@
 pub use super::bookmarks::Entity as Bookmarks;
 pub use super::categories::Entity as Categories;
+pub use super::category_covers::Entity as CategoryCovers;
 pub use super::covers::Entity as Covers;
 pub use super::favorites::Entity as Favorites;
 pub use super::pages::Entity as Pages;
+pub use super::people::Entity as People;
+pub use super::person_aliases::Entity as PersonAliases;
 pub use super::progresses::Entity as Progresses;
+pub use super::saved_filters::Entity as SavedFilters;
 pub use super::tags::Entity as Tags;
+pub use super::title_people::Entity as TitlePeople;
 pub use super::titles::Entity as Titles;
 pub use super::titles_tags::Entity as TitlesTags;
 pub use super::users::Entity as Users;
 
 pub use super::categories::CategoryID;
 pub use super::pages::PageID;
+pub use super::people::PersonID;
+pub use super::saved_filters::SavedFilterID;
 pub use super::titles::TitleID;
 pub use super::users::UserID;
 
+pub use super::types::category_cover_source::CategoryCoverSource;
 pub use super::types::custom_id::CustomID;
+pub use super::types::person_role::PersonRole;
+pub use super::types::reading_direction::ReadingDirection;
 
 pub use super::*;
@@ -0,0 +1,43 @@
+use sea_orm::entity::prelude::*;
+use utoipa::ToSchema;
+
+use crate::models::prelude::{CustomID, UserID};
+
+pub type SavedFilterID = CustomID;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, ToSchema)]
+#[schema(as = SavedFilter)]
+#[sea_orm(table_name = "saved_filters")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: SavedFilterID,
+    pub user_id: UserID,
+    /// Unique among the saved filters of the user.
+    pub name: String,
+    /// JSON of the saved `FilterRequestBody`, without a cursor.
+    pub filter: String,
+    /// Shown on the home screen as a smart collection.
+    pub pinned: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
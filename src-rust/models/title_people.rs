@@ -0,0 +1,50 @@
+use sea_orm::entity::prelude::*;
+use utoipa::ToSchema;
+
+use crate::models::prelude::{PersonID, PersonRole, TitleID};
+
+/// A person credited on a title, once per role.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, ToSchema)]
+#[schema(as = TitlePerson)]
+#[sea_orm(table_name = "title_people")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: u32,
+    pub title_id: TitleID,
+    pub person_id: PersonID,
+    pub role: PersonRole,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::people::Entity",
+        from = "Column::PersonId",
+        to = "super::people::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    People,
+    #[sea_orm(
+        belongs_to = "super::titles::Entity",
+        from = "Column::TitleId",
+        to = "super::titles::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Titles,
+}
+
+impl Related<super::people::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::People.def()
+    }
+}
+
+impl Related<super::titles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Titles.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -2,7 +2,7 @@ use sea_orm::entity::prelude::*;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::models::prelude::{CategoryID, CustomID};
+use crate::models::prelude::{CategoryID, CustomID, ReadingDirection};
 
 pub type TitleID = CustomID;
 
@@ -19,6 +19,20 @@ pub struct Model {
     pub release: Option<String>,
     pub hash: String,
     pub path: String,
+    pub reading_direction: ReadingDirection,
+    /// Webtoon style title, tall pages are served as slices.
+    pub long_strip: bool,
+    /// Modification times of the TOML of the title and of its category added up, in
+    /// milliseconds, when `reading_direction` and `long_strip` were last read from them.
+    pub settings_modified: Option<i64>,
+    /// ID of the title this one is hidden from the index as a copy of.
+    pub duplicate_of: Option<String>,
+    /// Whether the analyzer has credited the people of the title, reset when the archive or
+    /// author changes.
+    pub people_analyzed: bool,
+    /// Modification time of the TOML of the title, in milliseconds, when its people were last
+    /// credited. They're credited again when it changes.
+    pub people_modified: Option<i64>,
     pub date_added: String,
     pub date_updated: String,
 }
@@ -45,6 +59,8 @@ pub enum Relation {
     Favorites,
     #[sea_orm(has_many = "super::progresses::Entity")]
     Progresses,
+    #[sea_orm(has_many = "super::title_people::Entity")]
+    TitlePeople,
 }
 
 impl Related<super::categories::Entity> for Entity {
@@ -88,4 +104,11 @@ impl Related<super::progresses::Entity> for Entity {
         Relation::Progresses.def()
     }
 }
+
+impl Related<super::title_people::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TitlePeople.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
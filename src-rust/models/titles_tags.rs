@@ -15,7 +15,7 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(
         belongs_to = "super::tags::Entity",
-        from = "Column::TitleId",
+        from = "Column::TagId",
         to = "super::tags::Column::Id",
         on_update = "NoAction",
         on_delete = "Cascade"
@@ -0,0 +1,33 @@
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// Where the cover of a category comes from.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(6))")]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum CategoryCoverSource {
+    /// The `cover` image of the category TOML.
+    #[sea_orm(string_value = "file")]
+    File,
+    /// The cover of its first title.
+    #[sea_orm(string_value = "title")]
+    Title,
+    /// The covers of its first four titles, side by side.
+    #[sea_orm(string_value = "mosaic")]
+    Mosaic,
+}
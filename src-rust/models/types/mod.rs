@@ -1 +1,4 @@
+pub(super) mod category_cover_source;
 pub(super) mod custom_id;
+pub(super) mod person_role;
+pub(super) mod reading_direction;
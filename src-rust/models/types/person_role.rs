@@ -0,0 +1,50 @@
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// What a person did on a title.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(12))")]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum PersonRole {
+    /// Credited without a role, e.g. from the `author` of the title.
+    #[default]
+    #[sea_orm(string_value = "author")]
+    Author,
+    #[sea_orm(string_value = "writer")]
+    Writer,
+    #[sea_orm(string_value = "artist")]
+    Artist,
+    #[sea_orm(string_value = "penciller")]
+    Penciller,
+    #[sea_orm(string_value = "inker")]
+    Inker,
+    #[sea_orm(string_value = "colorist")]
+    Colorist,
+    #[sea_orm(string_value = "letterer")]
+    Letterer,
+    #[sea_orm(string_value = "cover_artist")]
+    CoverArtist,
+    #[sea_orm(string_value = "editor")]
+    Editor,
+    #[sea_orm(string_value = "translator")]
+    Translator,
+}
@@ -0,0 +1,30 @@
+use sea_orm::{DeriveActiveEnum, EnumIter};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// Which side a title starts from. Manga is usually read right to left.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(3))")]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum ReadingDirection {
+    #[default]
+    #[sea_orm(string_value = "ltr")]
+    Ltr,
+    #[sea_orm(string_value = "rtl")]
+    Rtl,
+}
@@ -19,6 +19,8 @@ pub struct Model {
     pub updated_at: String,
     pub password: String,
     pub is_verified: bool,
+    /// JSON of the saved `EinkProfile`, used when a page is requested with `eink=true`.
+    pub eink_profile: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
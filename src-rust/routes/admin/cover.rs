@@ -0,0 +1,251 @@
+use std::{io::Read, path::Path as FsPath, sync::Arc};
+
+use crate::{
+    imaging,
+    models::{metadata::TitleMetadata, prelude::*},
+    AppError, AppState, GenericResponseBody,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use image::ImageFormat;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+/// Largest custom cover that can be uploaded, in bytes.
+pub const MAX_COVER_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CoverRequestBody {
+    /// A page of the title.
+    pub page_id: String,
+}
+
+/// Use a page of a title as its cover.
+///
+/// The choice is saved as `cover` in the TOML file of the title, so it's kept across rescans.
+#[utoipa::path(put, path = "/api/admin/title/{title_id}/cover", request_body = CoverRequestBody, responses(
+    (status = 200, description = "Set cover successful", body = GenericResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 403, description = "Forbidden", body = String),
+    (status = 404, description = "Title or page not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn put_cover(
+    State(data): State<Arc<AppState>>,
+    Path(title_id): Path<String>,
+    Json(body): Json<CoverRequestBody>,
+) -> Result<Response, AppError> {
+    let title_id = match CustomID::from(title_id) {
+        Ok(id) => id,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
+    let title = match Titles::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title: {}", e)))?
+    {
+        Some(title) => title,
+        None => return Ok((StatusCode::NOT_FOUND, "Title not found.").into_response()),
+    };
+
+    let page = match Pages::find()
+        .filter(pages::Column::Id.eq(body.page_id))
+        .filter(pages::Column::TitleId.eq(&title.id))
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find page: {}", e)))?
+    {
+        Some(page) => page,
+        None => return Ok((StatusCode::NOT_FOUND, "Page not found.").into_response()),
+    };
+
+    // the analyzer has usually done the work already
+    let details = match (&page.blurhash, page.width, page.height) {
+        (Some(blurhash), Some(width), Some(height)) if !blurhash.is_empty() => {
+            (blurhash.clone(), width, height)
+        }
+        _ => {
+            let index = data
+                .archive_cache
+                .get(&title.id, &title.hash, &title.path)
+                .await?;
+            let path = page.path.clone();
+            let decoded = tokio::task::spawn_blocking(move || {
+                let mut buffer = Vec::new();
+                index.reader(&path)?.read_to_end(&mut buffer)?;
+                let (image, _) = imaging::decode(&buffer)?;
+                anyhow::Ok((imaging::blurhash(&image)?, image.width(), image.height()))
+            })
+            .await?;
+            match decoded {
+                Ok(details) => details,
+                Err(e) => {
+                    return Ok((
+                        StatusCode::BAD_REQUEST,
+                        format!("Page can't be used as a cover: {}", e),
+                    )
+                        .into_response())
+                }
+            }
+        }
+    };
+
+    let title_path = title.path.clone();
+    let page_path = page.path.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut metadata = TitleMetadata::from(FsPath::new(&title_path))?;
+        remove_custom_cover(&title_path, &metadata);
+        metadata.custom_cover = None;
+        metadata.set_cover(page_path)
+    })
+    .await?
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't save cover: {}", e)))?;
+
+    save_cover(&data, &title.id, page.path, false, details).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("Set cover successful.")),
+    )
+        .into_response())
+}
+
+/// Upload an image as the cover of a title.
+///
+/// It's stored next to the archive as `<archive name>.cover.<ext>` and saved as `custom_cover`
+/// in the TOML file of the title, so it's kept across rescans.
+#[utoipa::path(post, path = "/api/admin/title/{title_id}/cover", request_body(content = Vec<u8>, content_type = "image/*"), responses(
+    (status = 200, description = "Upload cover successful", body = GenericResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 403, description = "Forbidden", body = String),
+    (status = 404, description = "Title not found", body = String),
+    (status = 413, description = "Cover too large", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn post_cover(
+    State(data): State<Arc<AppState>>,
+    Path(title_id): Path<String>,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let title_id = match CustomID::from(title_id) {
+        Ok(id) => id,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
+    let title = match Titles::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title: {}", e)))?
+    {
+        Some(title) => title,
+        None => return Ok((StatusCode::NOT_FOUND, "Title not found.").into_response()),
+    };
+
+    let decoded = tokio::task::spawn_blocking(move || {
+        let (image, format) = imaging::decode(&body)?;
+        anyhow::Ok((
+            body,
+            format,
+            (imaging::blurhash(&image)?, image.width(), image.height()),
+        ))
+    })
+    .await?;
+    let (buffer, format, details) = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.to_string()).into_response()),
+    };
+    let extension = match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        ImageFormat::Gif => "gif",
+        _ => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                "Covers must be JPEG, PNG, WebP or GIF.",
+            )
+                .into_response())
+        }
+    };
+
+    let file_name = FsPath::new(&title.path)
+        .with_extension(format!("cover.{}", extension))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let title_path = title.path.clone();
+    let saved_file_name = file_name.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut metadata = TitleMetadata::from(FsPath::new(&title_path))?;
+        remove_custom_cover(&title_path, &metadata);
+        std::fs::write(
+            FsPath::new(&title_path).with_file_name(&saved_file_name),
+            buffer,
+        )
+        .map_err(|e| format!("can't write cover: {}", e))?;
+        metadata.set_custom_cover(Some(saved_file_name))
+    })
+    .await?
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't save cover: {}", e)))?;
+
+    save_cover(&data, &title.id, file_name, true, details).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("Upload cover successful.")),
+    )
+        .into_response())
+}
+
+/// Remove the uploaded cover of a title, if it has one. This is blocking.
+fn remove_custom_cover(title_path: &str, metadata: &TitleMetadata) {
+    if let Some(custom_cover) = &metadata.custom_cover {
+        let _ = std::fs::remove_file(FsPath::new(title_path).with_file_name(custom_cover));
+    }
+}
+
+/// Point the cover of a title to `path`, with the blurhash and dimensions of the new image.
+async fn save_cover(
+    data: &AppState,
+    title_id: &TitleID,
+    path: String,
+    is_custom: bool,
+    (blurhash, width, height): (String, u32, u32),
+) -> Result<(), AppError> {
+    let cover = covers::ActiveModel {
+        id: Set(title_id.clone()),
+        path: Set(path),
+        blurhash: Set(blurhash),
+        ratio: Set(width * data.config.ratio_percision / height.max(1)),
+        is_custom: Set(is_custom),
+        rule: Set(Some(match is_custom {
+            true => "custom".to_string(),
+            false => "toml".to_string(),
+        })),
+        // checked against the cover rules once, which leaves a TOML cover as it is
+        rules_hash: Set(None),
+    };
+
+    let exists = Covers::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find cover: {}", e)))?
+        .is_some();
+    match exists {
+        true => cover.update(&data.db).await.map(|_| ()),
+        false => cover.insert(&data.db).await.map(|_| ()),
+    }
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't save cover: {}", e)))
+}
@@ -0,0 +1,524 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use crate::{imaging, models::prelude::*, AppError, AppState, GenericResponseBody};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+/// Pages hashed per title on top of the cover, spread evenly through the title.
+const DUPLICATE_SAMPLE_PAGES: usize = 8;
+/// Titles scoring below this are not listed unless `min_score` asks for them.
+const DEFAULT_MIN_SCORE: f64 = 0.9;
+/// Hashes are split into this many bands, titles sharing a band are compared.
+const HASH_BANDS: u32 = 4;
+/// Hashes with fewer bits set or unset than this are blank or solid pages, found in many
+/// unrelated titles, so they don't make titles compared.
+const MIN_HASH_BITS: u32 = 8;
+/// Bands shared by more titles than this are too common to tell anything, e.g. a scanlation
+/// group's credits page, so they don't make titles compared either.
+const MAX_BUCKET_TITLES: usize = 64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct DuplicatesQuery {
+    /// Lowest similarity to list, from 0 to 1. Defaults to 0.9.
+    pub min_score: Option<f64>,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DuplicateTitleResponseBody {
+    pub id: String,
+    pub title: String,
+    pub path: String,
+    pub page_count: usize,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DuplicateResponseBody {
+    pub titles: [DuplicateTitleResponseBody; 2],
+    /// How alike the sampled pages of both titles are, from 0 to 1.
+    pub score: f64,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DuplicatesResponseBody {
+    pub data: Vec<DuplicateResponseBody>,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ResolveDuplicateRequestBody {
+    /// The title staying in the index.
+    pub keep: String,
+    /// The title hidden as a copy of `keep`.
+    pub hide: String,
+    /// Move the bookmarks, favorites and progresses of `hide` to `keep`.
+    pub merge: Option<bool>,
+}
+
+/// The perceptual hashes of a title, pages in path order.
+#[derive(Debug, Clone, Default)]
+struct TitleHashes {
+    cover: Option<u64>,
+    pages: Vec<u64>,
+}
+
+impl TitleHashes {
+    /// The cover and a few pages spread evenly through the title.
+    fn sample(&self) -> Vec<u64> {
+        let step = (self.pages.len() / DUPLICATE_SAMPLE_PAGES).max(1);
+        self.cover
+            .into_iter()
+            .chain(
+                self.pages
+                    .iter()
+                    .step_by(step)
+                    .take(DUPLICATE_SAMPLE_PAGES)
+                    .copied(),
+            )
+            .collect()
+    }
+
+    /// How well the sample of this title is found in `other`, from 0 to 1.
+    ///
+    /// Every page is searched for, so an extra or missing page doesn't shift the comparison.
+    fn found_in(&self, other: &Self) -> f64 {
+        let sample = self.sample();
+        if sample.is_empty() {
+            return 0.0;
+        }
+
+        sample
+            .iter()
+            .map(|hash| {
+                other
+                    .cover
+                    .iter()
+                    .chain(&other.pages)
+                    .map(|other_hash| imaging::phash_similarity(*hash, *other_hash))
+                    .fold(0.0, f64::max)
+            })
+            .sum::<f64>()
+            / sample.len() as f64
+    }
+
+    fn score(&self, other: &Self) -> f64 {
+        (self.found_in(other) + other.found_in(self)) / 2.0
+    }
+}
+
+/// Pairs of titles with a sampled hash sharing a band with the other, so near identical
+/// pages are always caught while unrelated titles aren't compared. Blank pages and bands most
+/// titles share are left out, or most of the library would be compared.
+fn find_candidates(titles: &[TitleHashes]) -> BTreeSet<(usize, usize)> {
+    let band_bits = 64 / HASH_BANDS;
+    let mut buckets: HashMap<(u32, u64), BTreeSet<usize>> = HashMap::new();
+    for (i, title) in titles.iter().enumerate() {
+        for hash in title.sample() {
+            if !(MIN_HASH_BITS..=64 - MIN_HASH_BITS).contains(&hash.count_ones()) {
+                continue;
+            }
+            for band in 0..HASH_BANDS {
+                let value = (hash >> (band * band_bits)) & ((1 << band_bits) - 1);
+                buckets.entry((band, value)).or_default().insert(i);
+            }
+        }
+    }
+
+    buckets
+        .values()
+        .filter(|bucket| bucket.len() <= MAX_BUCKET_TITLES)
+        .flat_map(|bucket| {
+            bucket.iter().flat_map(move |a| {
+                bucket
+                    .range(a + 1..)
+                    .map(move |b| (*a, *b))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
+
+/// Find the pairs of titles scoring at least `min_score`, best first.
+fn find_duplicates(titles: &[TitleHashes], min_score: f64) -> Vec<(usize, usize, f64)> {
+    let mut duplicates = find_candidates(titles)
+        .into_iter()
+        .map(|(a, b)| (a, b, titles[a].score(&titles[b])))
+        .filter(|(_, _, score)| *score >= min_score)
+        .collect::<Vec<_>>();
+    duplicates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    duplicates
+}
+
+/// List probable duplicate titles, from the perceptual hashes of their cover and pages.
+///
+/// Titles already hidden as duplicates are left out.
+#[utoipa::path(get, path = "/api/admin/duplicates", params(DuplicatesQuery), responses(
+    (status = 200, description = "Fetch duplicates successful", body = DuplicatesResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 403, description = "Forbidden", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_duplicates(
+    State(data): State<Arc<AppState>>,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<Response, AppError> {
+    let min_score = query.min_score.unwrap_or(DEFAULT_MIN_SCORE);
+
+    let titles = Titles::find()
+        .filter(titles::Column::DuplicateOf.is_null())
+        .order_by_asc(titles::Column::Path)
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?;
+    let covers = Covers::find()
+        .select_only()
+        .column(covers::Column::Id)
+        .column(covers::Column::Path)
+        .into_tuple::<(TitleID, String)>()
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find covers: {}", e)))?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let pages = Pages::find()
+        .select_only()
+        .column(pages::Column::TitleId)
+        .column(pages::Column::Path)
+        .column(pages::Column::Phash)
+        .filter(pages::Column::Phash.is_not_null())
+        .order_by_asc(pages::Column::Path)
+        .into_tuple::<(TitleID, String, i64)>()
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find pages: {}", e)))?;
+
+    let mut hashes: HashMap<TitleID, TitleHashes> = HashMap::new();
+    for (title_id, path, phash) in pages {
+        let title_hashes = hashes.entry(title_id.clone()).or_default();
+        match covers.get(&title_id) == Some(&path) {
+            true => title_hashes.cover = Some(phash as u64),
+            false => title_hashes.pages.push(phash as u64),
+        }
+    }
+    let hashes = titles
+        .iter()
+        .map(|title| hashes.remove(&title.id).unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let (hashes, duplicates) = tokio::task::spawn_blocking(move || {
+        let duplicates = find_duplicates(&hashes, min_score);
+        (hashes, duplicates)
+    })
+    .await?;
+
+    let response_title = |i: usize| DuplicateTitleResponseBody {
+        id: titles[i].id.to_string(),
+        title: titles[i].title.clone(),
+        path: titles[i].path.clone(),
+        page_count: hashes[i].pages.len() + hashes[i].cover.iter().count(),
+    };
+    let data = duplicates
+        .into_iter()
+        .map(|(a, b, score)| DuplicateResponseBody {
+            titles: [response_title(a), response_title(b)],
+            score,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(DuplicatesResponseBody { data })).into_response())
+}
+
+/// Hide a title as a copy of another, optionally moving its user data to the one kept.
+///
+/// When both titles have a progress for the same user, the latest read wins.
+#[utoipa::path(post, path = "/api/admin/duplicates/resolve", request_body = ResolveDuplicateRequestBody, responses(
+    (status = 200, description = "Resolve duplicate successful", body = GenericResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 403, description = "Forbidden", body = String),
+    (status = 404, description = "Title not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn post_resolve_duplicate(
+    State(data): State<Arc<AppState>>,
+    Json(body): Json<ResolveDuplicateRequestBody>,
+) -> Result<Response, AppError> {
+    let (keep_id, hide_id) = match (CustomID::from(body.keep), CustomID::from(body.hide)) {
+        (Ok(keep_id), Ok(hide_id)) => (keep_id, hide_id),
+        (Err(e), _) | (_, Err(e)) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+    if keep_id == hide_id {
+        return Ok((StatusCode::BAD_REQUEST, "Can't hide a title as itself.").into_response());
+    }
+
+    let titles = Titles::find()
+        .filter(titles::Column::Id.is_in([&keep_id, &hide_id]))
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?;
+    let keep = match titles.iter().find(|title| title.id == keep_id) {
+        Some(keep) => keep,
+        None => return Ok((StatusCode::NOT_FOUND, "Title to keep not found.").into_response()),
+    };
+    if !titles.iter().any(|title| title.id == hide_id) {
+        return Ok((StatusCode::NOT_FOUND, "Title to hide not found.").into_response());
+    }
+    if keep.duplicate_of.is_some() {
+        return Ok((StatusCode::BAD_REQUEST, "Title to keep is hidden.").into_response());
+    }
+
+    let txn = data
+        .db
+        .begin()
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't begin transaction: {}", e)))?;
+
+    titles::ActiveModel {
+        id: Set(hide_id.clone()),
+        duplicate_of: Set(Some(keep_id.to_string())),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't update title: {}", e)))?;
+
+    // copies of the hidden title are copies of the one kept now
+    Titles::update_many()
+        .col_expr(
+            titles::Column::DuplicateOf,
+            sea_orm::sea_query::Expr::value(&keep_id),
+        )
+        .filter(titles::Column::DuplicateOf.eq(&hide_id))
+        .exec(&txn)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't update titles: {}", e)))?;
+
+    if body.merge == Some(true) {
+        merge_user_data(&txn, &hide_id, &keep_id)
+            .await
+            .map_err(|e| AppError::from(anyhow::anyhow!("Can't merge user data: {}", e)))?;
+    }
+
+    txn.commit()
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't commit transaction: {}", e)))?;
+    // hidden titles aren't suggested
+    data.search_cache.clear().await;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("Resolve duplicate successful.")),
+    )
+        .into_response())
+}
+
+/// Show a title hidden as a duplicate again.
+#[utoipa::path(delete, path = "/api/admin/duplicates/{title_id}", responses(
+    (status = 200, description = "Unhide title successful", body = GenericResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 403, description = "Forbidden", body = String),
+    (status = 404, description = "Title not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn delete_duplicate(
+    State(data): State<Arc<AppState>>,
+    Path(title_id): Path<String>,
+) -> Result<Response, AppError> {
+    let title_id = match CustomID::from(title_id) {
+        Ok(id) => id,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
+    let title = match Titles::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title: {}", e)))?
+    {
+        Some(title) => title,
+        None => return Ok((StatusCode::NOT_FOUND, "Title not found.").into_response()),
+    };
+    if title.duplicate_of.is_none() {
+        return Ok((StatusCode::BAD_REQUEST, "Title is not hidden.").into_response());
+    }
+
+    titles::ActiveModel {
+        id: Set(title.id),
+        duplicate_of: Set(None),
+        ..Default::default()
+    }
+    .update(&data.db)
+    .await
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't update title: {}", e)))?;
+    data.search_cache.clear().await;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("Unhide title successful.")),
+    )
+        .into_response())
+}
+
+/// Move the bookmarks, favorites and progresses of `from` to `to`, without duplicating them.
+async fn merge_user_data(
+    db: &impl ConnectionTrait,
+    from: &TitleID,
+    to: &TitleID,
+) -> Result<(), DbErr> {
+    for bookmark in Bookmarks::find()
+        .filter(bookmarks::Column::TitleId.eq(from))
+        .all(db)
+        .await?
+    {
+        let exists = Bookmarks::find()
+            .filter(bookmarks::Column::TitleId.eq(to))
+            .filter(bookmarks::Column::UserId.eq(&bookmark.user_id))
+            .one(db)
+            .await?
+            .is_some();
+        let mut bookmark: bookmarks::ActiveModel = bookmark.into();
+        match exists {
+            true => {
+                bookmark.delete(db).await?;
+            }
+            false => {
+                bookmark.title_id = Set(to.clone());
+                bookmark.update(db).await?;
+            }
+        }
+    }
+
+    for favorite in Favorites::find()
+        .filter(favorites::Column::TitleId.eq(from))
+        .all(db)
+        .await?
+    {
+        let exists = Favorites::find()
+            .filter(favorites::Column::TitleId.eq(to))
+            .filter(favorites::Column::UserId.eq(&favorite.user_id))
+            .one(db)
+            .await?
+            .is_some();
+        let mut favorite: favorites::ActiveModel = favorite.into();
+        match exists {
+            true => {
+                favorite.delete(db).await?;
+            }
+            false => {
+                favorite.title_id = Set(to.clone());
+                favorite.update(db).await?;
+            }
+        }
+    }
+
+    for progress in Progresses::find()
+        .filter(progresses::Column::TitleId.eq(from))
+        .all(db)
+        .await?
+    {
+        let existing = Progresses::find()
+            .filter(progresses::Column::TitleId.eq(to))
+            .filter(progresses::Column::UserId.eq(&progress.user_id))
+            .one(db)
+            .await?;
+        match existing {
+            Some(existing) => {
+                if progress.last_read_at > existing.last_read_at {
+                    progresses::ActiveModel {
+                        id: Set(existing.id),
+                        last_read_at: Set(progress.last_read_at.clone()),
+                        page: Set(progress.page),
+                        ..Default::default()
+                    }
+                    .update(db)
+                    .await?;
+                }
+                progresses::ActiveModel::from(progress).delete(db).await?;
+            }
+            None => {
+                let mut progress: progresses::ActiveModel = progress.into();
+                progress.title_id = Set(to.clone());
+                progress.update(db).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_duplicates {
+    use super::*;
+
+    fn title(cover: u64, pages: &[u64]) -> TitleHashes {
+        TitleHashes {
+            cover: Some(cover),
+            pages: pages.to_vec(),
+        }
+    }
+
+    #[test]
+    fn repack_with_extra_page() {
+        let pages = (1..=20)
+            .map(|i: u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect::<Vec<_>>();
+        let original = title(0xdead_beef, &pages);
+        // a credits page up front shifts every page by one
+        let repack = title(
+            0xdead_beef,
+            &[vec![0x0123_4567_89ab_cdef], pages.clone()].concat(),
+        );
+        let other = title(
+            !0xdead_beef,
+            &pages.iter().map(|hash| !hash).collect::<Vec<_>>(),
+        );
+
+        let duplicates = find_duplicates(&[original, other, repack], DEFAULT_MIN_SCORE);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].0, duplicates[0].1), (0, 2));
+        assert!(duplicates[0].2 > 0.95);
+    }
+
+    #[test]
+    fn common_pages() {
+        let unique = |i: u64, page: u64| (i * 100 + page).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        // every title has a blank page and the same credits page
+        let mut titles = (0..200)
+            .map(|i| title(0, &[0x0f0f_0f0f_0f0f_0f0f, unique(i, 1), unique(i, 2)]))
+            .collect::<Vec<_>>();
+        titles.push(titles[10].clone());
+
+        // only the copy is compared
+        assert_eq!(find_candidates(&titles).len(), 1);
+        let duplicates = find_duplicates(&titles, DEFAULT_MIN_SCORE);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].0, duplicates[0].1), (10, 200));
+    }
+
+    #[test]
+    fn no_hashes() {
+        let empty = TitleHashes::default();
+
+        assert_eq!(empty.score(&title(1, &[2, 3])), 0.0);
+        assert!(find_duplicates(&[empty.clone(), empty], 0.0).is_empty());
+    }
+}
@@ -0,0 +1,5 @@
+mod cover;
+mod duplicates;
+
+pub use cover::*;
+pub use duplicates::*;
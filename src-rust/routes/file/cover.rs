@@ -1,24 +1,144 @@
-use std::{fs::File, io::Read, path::PathBuf, sync::Arc};
+use std::{io::Cursor, path::PathBuf, sync::Arc};
 
-use crate::{models::prelude::*, AppError, AppState};
+use super::{
+    archive_modified, cache_headers, disk_file_response, entry_etag, entry_response, file_response,
+    generated_file, is_not_modified, still_response, FileQuery,
+};
+use crate::{archive::read_cover, imaging, models::prelude::*, AppError, AppState};
 
 use axum::{
-    extract::{Path, State},
-    http::{header, StatusCode},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
+use image::ImageFormat;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect};
-use zip::ZipArchive;
 
-#[utoipa::path(get, path = "/api/file/cover/{id}", responses(
+/// Fetch the cover of a title. Animated covers and videos are sent as a PNG of their first frame.
+///
+/// Custom covers are read from disk and can change without the archive, so they are never
+/// marked as immutable.
+#[utoipa::path(get, path = "/api/file/cover/{id}", params(FileQuery), responses(
     (status = 200, description = "Fetch cover successful", body = Vec<u8>),
+    (status = 206, description = "Fetch part of a cover successful", body = Vec<u8>),
+    (status = 304, description = "Cover not modified"),
     (status = 401, description = "Unauthorized", body = String),
     (status = 404, description = "Cover not found", body = String),
+    (status = 416, description = "Range not satisfiable"),
     (status = 500, description = "Internal server error", body = String),
 ))]
 pub async fn get_cover(
     State(data): State<Arc<AppState>>,
     Path(title_id): Path<String>,
+    Query(query): Query<FileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    title_cover_response(&data, title_id, query.v.as_deref(), &headers).await
+}
+
+/// Fetch the cover of a category: its own image, the cover of its first title, or a mosaic
+/// of the covers of its first four titles.
+#[utoipa::path(get, path = "/api/file/category-cover/{id}", responses(
+    (status = 200, description = "Fetch category cover successful", body = Vec<u8>),
+    (status = 206, description = "Fetch part of a category cover successful", body = Vec<u8>),
+    (status = 304, description = "Category cover not modified"),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Category cover not found", body = String),
+    (status = 416, description = "Range not satisfiable"),
+    (status = 500, description = "Internal server error", body = String),
+))]
+pub async fn get_category_cover(
+    State(data): State<Arc<AppState>>,
+    Path(category_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let category_cover = match CategoryCovers::find()
+        .filter(category_covers::Column::Id.eq(category_id))
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find category cover: {}", e)))?
+    {
+        Some(cover) => cover,
+        None => return Ok((StatusCode::NOT_FOUND, "Category cover not found.").into_response()),
+    };
+
+    let title_ids = category_cover.title_ids();
+    match category_cover.source {
+        CategoryCoverSource::File => {
+            return disk_file_response(&category_cover.path, &headers).await;
+        }
+        CategoryCoverSource::Title => {
+            let title_id = title_ids
+                .first()
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            return title_cover_response(&data, title_id, None, &headers).await;
+        }
+        CategoryCoverSource::Mosaic => {}
+    }
+
+    let mut covered = Titles::find()
+        .find_also_related(Covers)
+        .filter(titles::Column::Id.is_in(&title_ids))
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?
+        .into_iter()
+        .filter_map(|(title, cover)| cover.map(|cover| (title, cover)))
+        .collect::<Vec<_>>();
+    covered.sort_by_key(|(title, _)| title_ids.iter().position(|id| *id == title.id));
+    if covered.len() != title_ids.len() {
+        return Ok((StatusCode::NOT_FOUND, "Category cover not found.").into_response());
+    }
+
+    // changes whenever one of the covers does
+    let fingerprint = murmur3::murmur3_32(
+        &mut Cursor::new(
+            covered
+                .iter()
+                .map(|(title, cover)| {
+                    format!(
+                        "{}:{}:{}:{}",
+                        title.id, title.hash, cover.path, cover.is_custom
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        0,
+    )
+    .unwrap_or_default();
+
+    let etag = entry_etag(&format!("{:08x}", fingerprint), "mosaic");
+    let mut response_headers = cache_headers(&etag, None, false);
+    if is_not_modified(&headers, &etag, None) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let mosaic_path = generated_file(
+        PathBuf::from(&data.config.temp_dir).join("yomuyume-category-covers"),
+        category_cover.id.as_ref(),
+        format!("{}-{:08x}.jpg", category_cover.id, fingerprint),
+        move |path| {
+            let images = covered
+                .iter()
+                .map(|(title, cover)| Ok(imaging::decode(&read_cover(&title.path, cover)?)?.0))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let (buffer, _) = imaging::encode(&imaging::mosaic(&images), ImageFormat::Jpeg)?;
+            Ok(std::fs::write(path, buffer)?)
+        },
+    )
+    .await?;
+
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg"));
+    file_response(&mosaic_path, &headers, &etag, response_headers).await
+}
+
+async fn title_cover_response(
+    data: &AppState,
+    title_id: String,
+    v: Option<&str>,
+    headers: &HeaderMap,
 ) -> Result<Response, AppError> {
     let cover_model = match Covers::find()
         .filter(covers::Column::Id.eq(title_id))
@@ -30,47 +150,54 @@ pub async fn get_cover(
         None => return Ok((StatusCode::NOT_FOUND, "Cover not found.".to_string()).into_response()),
     };
 
-    let title_model_path = match titles::Entity::find()
+    let (title_model_path, title_model_hash) = match titles::Entity::find()
         .select_only()
         .column(titles::Column::Path)
-        .filter(titles::Column::Id.eq(cover_model.id))
-        .into_tuple::<String>()
+        .column(titles::Column::Hash)
+        .filter(titles::Column::Id.eq(&cover_model.id))
+        .into_tuple::<(String, String)>()
         .one(&data.db)
         .await
         .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title path: {}", e)))?
     {
-        Some(path) => path,
+        Some(path_and_hash) => path_and_hash,
         None => return Ok((StatusCode::NOT_FOUND, "Title not found.".to_string()).into_response()),
     };
 
-    // zip file -> cover file -> buffer
-    let mut zip = ZipArchive::new(
-        File::open(title_model_path)
-            .map_err(|e| AppError::from(anyhow::anyhow!("Open zip file error: {}", e)))?,
-    )
-    .map_err(|e| AppError::from(anyhow::anyhow!("Read zip file error: {}", e)))?;
-    let mut file = zip
-        .by_name(cover_model.path.as_ref())
-        .map_err(|e| AppError::from(anyhow::anyhow!("Get cover file error: {}", e)))?;
-
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| AppError::from(anyhow::anyhow!("Read cover file error: {}", e)))?;
-
-    Ok((
-        StatusCode::OK,
-        [(
-            header::CONTENT_TYPE,
-            format!(
-                "image/{}",
-                PathBuf::from(cover_model.path)
-                    .extension()
-                    .map(|s| s.to_str().unwrap_or(""))
-                    .unwrap_or("")
-                    .to_ascii_lowercase()
-            ),
-        )],
-        buffer,
-    )
-        .into_response())
+    if let Some(custom_path) = cover_model.custom_path(&title_model_path) {
+        return disk_file_response(&custom_path, headers).await;
+    }
+
+    let is_animated = Pages::find()
+        .select_only()
+        .column(pages::Column::IsAnimated)
+        .filter(pages::Column::TitleId.eq(&cover_model.id))
+        .filter(pages::Column::Path.eq(&cover_model.path))
+        .into_tuple::<bool>()
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find cover page: {}", e)))?
+        .unwrap_or(false);
+
+    let etag = match is_animated {
+        true => entry_etag(&title_model_hash, &format!("{}#still", cover_model.path)),
+        false => entry_etag(&title_model_hash, &cover_model.path),
+    };
+    let last_modified = archive_modified(&title_model_path).await;
+    let cache_headers = cache_headers(&etag, last_modified, v == Some(title_model_hash.as_str()));
+
+    if is_not_modified(headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let index = data
+        .archive_cache
+        .get(&cover_model.id, &title_model_hash, &title_model_path)
+        .await?;
+
+    if is_animated {
+        return still_response(&data.config, index, &cover_model.path, cache_headers).await;
+    }
+
+    entry_response(&index, &cover_model.path, headers, &etag, cache_headers).await
 }
@@ -0,0 +1,183 @@
+use std::{io::Cursor, sync::Arc};
+
+use super::{
+    archive_modified, cache_headers, content_type, entry_etag, is_not_modified, preload_links,
+};
+use crate::{
+    archive::blocking_body, models::prelude::*, routes::find_ordered_pages, AppError, AppState,
+};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::EntityTrait;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// Upper bound on how many pages one batch can carry.
+const MAX_BATCH_PAGES: usize = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ToSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchFormat {
+    /// `multipart/mixed`, one part per page, streamed as pages are read.
+    #[default]
+    Multipart,
+    /// A stored (uncompressed) zip, built in memory.
+    Zip,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct BatchQuery {
+    /// Index of the first page, in the same order as `/api/index/title/{title_id}`.
+    pub start: Option<usize>,
+    /// Number of pages, capped at 50.
+    pub count: Option<usize>,
+    pub format: Option<BatchFormat>,
+    /// The archive hash of the title. When it matches, the response is marked as immutable.
+    pub v: Option<String>,
+}
+
+/// Fetch a range of pages of a title in one response.
+///
+/// Each multipart part carries the page ID in `Content-ID`, zip entries are named
+/// `<index>-<page id>.<ext>`. The `Link` header hints at the pages after the range.
+#[utoipa::path(get, path = "/api/file/title/{title_id}/pages", params(BatchQuery), responses(
+    (status = 200, description = "Fetch pages successful.", body = Vec<u8>),
+    (status = 304, description = "Pages not modified"),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Title not found", body = String),
+    (status = 500, description = "Internal server error", body = String),
+))]
+pub async fn get_batch(
+    State(data): State<Arc<AppState>>,
+    Path(title_id): Path<String>,
+    Query(query): Query<BatchQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let title_id = match CustomID::from(title_id) {
+        Ok(id) => id,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
+    let title = match Titles::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title: {}", e)))?
+    {
+        Some(title) => title,
+        None => return Ok((StatusCode::NOT_FOUND, "Title not found.").into_response()),
+    };
+
+    let start = query.start.unwrap_or(0);
+    let count = query.count.unwrap_or(MAX_BATCH_PAGES).min(MAX_BATCH_PAGES);
+    let format = query.format.unwrap_or_default();
+
+    let pages = find_ordered_pages(&data.db, &title.id)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find pages: {}", e)))?;
+
+    if count == 0 || start >= pages.len() {
+        return Ok((StatusCode::BAD_REQUEST, "Page range out of bounds.").into_response());
+    }
+
+    let end = (start + count).min(pages.len());
+    let etag = entry_etag(
+        &title.hash,
+        &format!("pages/{}-{}.{:?}", start, end, format),
+    );
+    let last_modified = archive_modified(&title.path).await;
+    let mut response_headers = cache_headers(
+        &etag,
+        last_modified,
+        query.v.as_deref() == Some(title.hash.as_str()),
+    );
+
+    if is_not_modified(&headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    if let Some(links) = preload_links(
+        pages
+            .iter()
+            .skip(end)
+            .take(data.config.prefetch_pages)
+            .map(|page| &page.id),
+        &title.hash,
+    ) {
+        response_headers.insert(header::LINK, links);
+    }
+
+    let index = data
+        .archive_cache
+        .get(&title.id, &title.hash, &title.path)
+        .await?;
+    let batch = pages[start..end]
+        .iter()
+        .map(|page| (page.id.to_string(), page.path.clone()))
+        .collect::<Vec<_>>();
+
+    let body = match format {
+        BatchFormat::Multipart => {
+            let boundary = format!("yomuyume-{}", CustomID::new());
+            if let Ok(value) =
+                HeaderValue::from_str(&format!("multipart/mixed; boundary={}", boundary))
+            {
+                response_headers.insert(header::CONTENT_TYPE, value);
+            }
+
+            blocking_body(move |writer| {
+                for (page_id, path) in batch {
+                    let size = index.get(&path).map(|entry| entry.size).unwrap_or_default();
+                    write!(
+                        writer,
+                        "--{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nContent-ID: <{}>\r\n\r\n",
+                        boundary,
+                        content_type(&path),
+                        size,
+                        page_id
+                    )?;
+                    std::io::copy(&mut index.reader(&path)?, writer)?;
+                    write!(writer, "\r\n")?;
+                }
+                write!(writer, "--{}--\r\n", boundary)?;
+                Ok(())
+            })
+        }
+        BatchFormat::Zip => {
+            let buffer = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+                let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+                // pages are already compressed images
+                let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+                for (i, (page_id, path)) in batch.into_iter().enumerate() {
+                    let extension = std::path::Path::new(&path)
+                        .extension()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or_default()
+                        .to_ascii_lowercase();
+                    zip.start_file(
+                        format!("{:04}-{}.{}", start + i, page_id, extension),
+                        options,
+                    )?;
+                    std::io::copy(&mut index.reader(&path)?, &mut zip)?;
+                }
+                Ok(zip.finish()?.into_inner())
+            })
+            .await??;
+
+            response_headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/zip"),
+            );
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(buffer.len()));
+            Body::from(buffer)
+        }
+    };
+
+    Ok((StatusCode::OK, response_headers, body).into_response())
+}
@@ -0,0 +1,243 @@
+use std::{
+    io::{BufWriter, Cursor},
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use super::{
+    archive_modified, cache_headers, entry_etag, file_response, generated_file, is_not_modified,
+};
+use crate::{
+    archive::{comic_info, write_cbz, write_epub, ExportMetadata},
+    models::prelude::*,
+    routes::find_ordered_pages,
+    AppError, AppState,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ToSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadFormat {
+    /// The archive as it is in the library.
+    #[default]
+    Original,
+    /// Pages renamed in reading order, with a ComicInfo.xml from the database metadata.
+    Cbz,
+    /// A fixed-layout EPUB 3, one page per image.
+    Epub,
+}
+
+impl DownloadFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Original => "zip",
+            Self::Cbz => "cbz",
+            Self::Epub => "epub",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Original => "application/zip",
+            Self::Cbz => "application/vnd.comicbook+zip",
+            Self::Epub => "application/epub+zip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct DownloadQuery {
+    pub format: Option<DownloadFormat>,
+    /// The archive hash of the title. When it matches, the response is marked as immutable.
+    pub v: Option<String>,
+}
+
+/// Download a whole title for offline reading.
+///
+/// Converted files are generated once into `TEMP_DIR` and reused until the archive or
+/// its metadata changes. All formats support `Range` to resume interrupted downloads.
+#[utoipa::path(get, path = "/api/file/title/{title_id}/download", params(DownloadQuery), responses(
+    (status = 200, description = "Download successful.", body = Vec<u8>),
+    (status = 206, description = "Partial download successful.", body = Vec<u8>),
+    (status = 304, description = "Download not modified"),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Title not found", body = String),
+    (status = 416, description = "Range not satisfiable"),
+    (status = 500, description = "Internal server error", body = String),
+))]
+pub async fn get_download(
+    State(data): State<Arc<AppState>>,
+    Path(title_id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let title_id = match CustomID::from(title_id) {
+        Ok(id) => id,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
+    let title = match Titles::find_by_id(title_id)
+        .one(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find title: {}", e)))?
+    {
+        Some(title) => title,
+        None => return Ok((StatusCode::NOT_FOUND, "Title not found.").into_response()),
+    };
+
+    let format = query.format.unwrap_or_default();
+    let last_modified = archive_modified(&title.path).await;
+
+    if format == DownloadFormat::Original {
+        let etag = entry_etag(&title.hash, "download");
+        let mut response_headers = cache_headers(
+            &etag,
+            last_modified,
+            query.v.as_deref() == Some(title.hash.as_str()),
+        );
+        if is_not_modified(&headers, &etag, last_modified) {
+            return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+        }
+
+        let extension = FsPath::new(&title.path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or(format.extension());
+        response_headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        response_headers.insert(
+            header::CONTENT_DISPOSITION,
+            attachment(&format!("{}.{}", title.title, extension)),
+        );
+
+        return file_response(&title.path, &headers, &etag, response_headers).await;
+    }
+
+    let pages = find_ordered_pages(&data.db, &title.id)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find pages: {}", e)))?
+        .into_iter()
+        .map(|page| page.path)
+        .collect::<Vec<_>>();
+
+    let tag_ids = TitlesTags::find()
+        .filter(titles_tags::Column::TitleId.eq(&title.id))
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags: {}", e)))?
+        .into_iter()
+        .map(|title_tag| title_tag.tag_id)
+        .collect::<Vec<_>>();
+    let tags = Tags::find()
+        .filter(tags::Column::Id.is_in(tag_ids))
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags: {}", e)))?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect::<Vec<_>>();
+
+    let metadata = ExportMetadata {
+        id: title.id.to_string(),
+        title: title.title.clone(),
+        author: title.author.clone(),
+        description: title.description.clone(),
+        release: title.release.clone(),
+        tags,
+        modified: last_modified,
+    };
+    let comic_info = comic_info(&metadata, pages.len());
+
+    // changes whenever the archive, the page list or the metadata does, the generated file
+    // is the same otherwise, so resumed downloads can be stitched together
+    let fingerprint = murmur3::murmur3_32(
+        &mut Cursor::new(format!(
+            "{}\n{:?}\n{}\n{}",
+            title.hash,
+            last_modified,
+            pages.join("\n"),
+            comic_info
+        )),
+        0,
+    )
+    .unwrap_or_default();
+
+    let etag = entry_etag(
+        &title.hash,
+        &format!("download.{}.{:08x}", format.extension(), fingerprint),
+    );
+    let mut response_headers = cache_headers(
+        &etag,
+        last_modified,
+        query.v.as_deref() == Some(title.hash.as_str()),
+    );
+    if is_not_modified(&headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let index = data
+        .archive_cache
+        .get(&title.id, &title.hash, &title.path)
+        .await?;
+    let download_path = generated_file(
+        PathBuf::from(&data.config.temp_dir).join("yomuyume-downloads"),
+        title.id.as_ref(),
+        format!("{}-{:08x}.{}", title.id, fingerprint, format.extension()),
+        move |path| {
+            let out = BufWriter::new(std::fs::File::create(path)?);
+            match format {
+                DownloadFormat::Epub => write_epub(&index, &pages, &metadata, out),
+                _ => write_cbz(&index, &pages, &comic_info, last_modified, out),
+            }
+        },
+    )
+    .await?;
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        attachment(&format!("{}.{}", title.title, format.extension())),
+    );
+
+    file_response(&download_path, &headers, &etag, response_headers).await
+}
+
+/// Build a `Content-Disposition: attachment` header, with an ASCII fallback for the filename.
+fn attachment(filename: &str) -> HeaderValue {
+    let fallback = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    let encoded = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect::<String>();
+
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+}
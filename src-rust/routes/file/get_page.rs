@@ -1,24 +1,115 @@
-use std::{fs::File, io::Read, path::PathBuf, sync::Arc};
+use std::{
+    io::Read,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
 
-use crate::{models::prelude::*, AppError, AppState};
+use super::{
+    archive_modified, cache_headers, entry_etag, entry_response, file_response, find_next_page_ids,
+    generated_file, is_not_modified, preload_links, transformed_response,
+};
+use crate::{
+    imaging::{self, CropBox, EinkProfile, Side},
+    models::prelude::*,
+    AppError, AppState,
+};
 
 use axum::{
-    extract::{Path, State},
-    http::{header, StatusCode},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Extension,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
-use zip::ZipArchive;
+use image::ImageFormat;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Half of a spread, in reading order.
+#[derive(Debug, Clone, Copy, PartialEq, ToSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpreadHalf {
+    /// The right half for right-to-left titles, the left half otherwise.
+    First,
+    Second,
+}
+
+impl SpreadHalf {
+    fn side(&self, reading_direction: ReadingDirection) -> Side {
+        match (self, reading_direction) {
+            (Self::First, ReadingDirection::Ltr) | (Self::Second, ReadingDirection::Rtl) => {
+                Side::Left
+            }
+            (Self::First, ReadingDirection::Rtl) | (Self::Second, ReadingDirection::Ltr) => {
+                Side::Right
+            }
+        }
+    }
+}
 
-#[utoipa::path(get, path = "/api/file/page/{page_id}", responses(
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct PageQuery {
+    /// The archive hash of the title, as returned by `/api/index/title/{title_id}`.
+    /// When it matches the current hash, the response is marked as immutable.
+    pub v: Option<String>,
+    /// Only serve one half of a spread, so it can be shown as two pages.
+    pub half: Option<SpreadHalf>,
+    /// Crop uniform white or black borders around the page.
+    pub trim: Option<bool>,
+    /// Only serve one slice of a tall page of a long strip title, counting from 0.
+    pub slice: Option<usize>,
+    /// Render the page for e-ink with the saved profile of the user, or the default one.
+    /// Only e-ink devices should ask for it, the response isn't marked as immutable as the
+    /// profile may change.
+    pub eink: Option<bool>,
+    /// Transcode an animated page to an H.264 mp4, when `FFMPEG_PATH` is set.
+    /// Other transforms don't apply to animated pages.
+    pub transcode: Option<bool>,
+}
+
+impl PageQuery {
+    /// Describe the requested transforms, `None` when the page is served as is.
+    fn transforms(&self, eink: Option<&EinkProfile>) -> Option<String> {
+        let mut transforms = Vec::new();
+        if let Some(half) = self.half {
+            transforms.push(format!("half={:?}", half));
+        }
+        if let Some(slice) = self.slice {
+            transforms.push(format!("slice={}", slice));
+        }
+        if self.trim == Some(true) {
+            transforms.push("trim".to_string());
+        }
+        if let Some(eink) = eink {
+            transforms.push(format!(
+                "eink={}",
+                serde_json::to_string(eink).unwrap_or_default()
+            ));
+        }
+
+        match transforms.is_empty() {
+            true => None,
+            false => Some(transforms.join("&")),
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/api/file/page/{page_id}", params(PageQuery), responses(
     (status = 200, description = "Fetch page successful.", body = Vec<u8>),
+    (status = 206, description = "Fetch part of a page successful.", body = Vec<u8>),
+    (status = 304, description = "Page not modified"),
+    (status = 400, description = "Page is not a spread or has no such slice", body = String),
     (status = 401, description = "Unauthorized", body = String),
     (status = 404, description = "Page not found", body = String),
+    (status = 416, description = "Range not satisfiable"),
     (status = 500, description = "Internal server error", body = String),
 ))]
 pub async fn get_page(
     State(data): State<Arc<AppState>>,
     Path(page_id): Path<String>,
+    Query(query): Query<PageQuery>,
+    Extension(user): Extension<users::Model>,
+    headers: HeaderMap,
 ) -> Result<Response, AppError> {
     let page_in_db = match Pages::find()
         .filter(pages::Column::Id.contains(page_id))
@@ -40,33 +131,151 @@ pub async fn get_page(
         None => return Ok((StatusCode::NOT_FOUND, "Title not found.".to_string()).into_response()),
     };
 
-    // zip file -> page file -> buffer
-    let mut zip = ZipArchive::new(
-        File::open(title_in_db.path)
-            .map_err(|e| AppError::from(anyhow::anyhow!("Open zip file error: {}", e)))?,
-    )
-    .map_err(|e| AppError::from(anyhow::anyhow!("Read zip file error: {}", e)))?;
-    let mut file = zip
-        .by_name(page_in_db.path.as_ref())
-        .map_err(|e| AppError::from(anyhow::anyhow!("Get page file error: {}", e)))?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| AppError::from(anyhow::anyhow!("Read page file error: {}", e)))?;
-
-    Ok((
-        StatusCode::OK,
-        [(
-            header::CONTENT_TYPE,
-            format!(
-                "image/{}",
-                PathBuf::from(page_in_db.path)
-                    .extension()
-                    .map(|s| s.to_str().unwrap_or(""))
-                    .unwrap_or("")
-                    .to_ascii_lowercase()
-            ),
-        )],
-        buffer,
-    )
-        .into_response())
+    if query.half.is_some() && !page_in_db.is_spread {
+        return Ok((StatusCode::BAD_REQUEST, "Page is not a spread.").into_response());
+    }
+
+    let slice = match query.slice {
+        Some(slice) if title_in_db.long_strip => match page_in_db.slices().get(slice) {
+            Some(rows) => Some(rows.clone()),
+            None => return Ok((StatusCode::BAD_REQUEST, "Page has no such slice.").into_response()),
+        },
+        Some(_) => {
+            return Ok((StatusCode::BAD_REQUEST, "Title is not a long strip.").into_response())
+        }
+        None => None,
+    };
+
+    let eink = (query.eink == Some(true)).then(|| {
+        user.eink_profile
+            .as_deref()
+            .and_then(|profile| serde_json::from_str::<EinkProfile>(profile).ok())
+            .unwrap_or_default()
+    });
+
+    let transcode = page_in_db.is_animated
+        && query.transcode == Some(true)
+        && data.config.ffmpeg_path.is_some();
+    let transforms = match page_in_db.is_animated {
+        true => transcode.then(|| "transcode".to_string()),
+        false => query.transforms(eink.as_ref()),
+    };
+    let etag = match &transforms {
+        Some(transforms) => entry_etag(
+            &title_in_db.hash,
+            &format!("{}#{}", page_in_db.path, transforms),
+        ),
+        None => entry_etag(&title_in_db.hash, &page_in_db.path),
+    };
+    let last_modified = archive_modified(&title_in_db.path).await;
+    let mut response_headers = cache_headers(
+        &etag,
+        last_modified,
+        query.v.as_deref() == Some(title_in_db.hash.as_str()) && eink.is_none(),
+    );
+
+    if is_not_modified(&headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    // hint the client about the next pages so it can start fetching them now
+    let next_pages = find_next_page_ids(&data.db, &page_in_db, data.config.prefetch_pages)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find pages: {}", e)))?;
+    if let Some(links) = preload_links(&next_pages, &title_in_db.hash) {
+        response_headers.insert(header::LINK, links);
+    }
+
+    let index = data
+        .archive_cache
+        .get(&title_in_db.id, &title_in_db.hash, &title_in_db.path)
+        .await?;
+
+    if transcode {
+        let ffmpeg_path = data.config.ffmpeg_path.clone().unwrap_or_default();
+        let temp_dir = data.config.temp_dir.clone();
+        let entry_path = page_in_db.path.clone();
+        let extension = PathBuf::from(&entry_path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let transcoded_path = generated_file(
+            PathBuf::from(&data.config.temp_dir).join("yomuyume-transcodes"),
+            page_in_db.id.as_ref(),
+            format!("{}-{}.mp4", page_in_db.id, title_in_db.hash),
+            move |path| {
+                let mut buffer = Vec::new();
+                index.reader(&entry_path)?.read_to_end(&mut buffer)?;
+                imaging::transcode(&ffmpeg_path, temp_dir, &buffer, &extension, path)
+            },
+        )
+        .await?;
+
+        response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("video/mp4"));
+        return file_response(&transcoded_path, &headers, &etag, response_headers).await;
+    }
+
+    if transforms.is_some() {
+        let side = query
+            .half
+            .map(|half| half.side(title_in_db.reading_direction));
+        let trim = query.trim == Some(true);
+        let cached_trim_box = page_in_db
+            .trim_box
+            .as_deref()
+            .and_then(|trim_box| trim_box.parse::<CropBox>().ok());
+        let detected_trim_box = Arc::new(OnceLock::new());
+        // e-ink panels are grayscale, PNG keeps the dithering intact
+        let output = eink.as_ref().map(|_| ImageFormat::Png);
+
+        let response = transformed_response(
+            index,
+            &page_in_db.path,
+            {
+                let detected_trim_box = detected_trim_box.clone();
+                move |image| {
+                    let mut crop = match side {
+                        Some(side) => CropBox::half(&image, side),
+                        None => CropBox::full(&image),
+                    };
+                    if let Some(rows) = slice {
+                        let rows = CropBox::rows(&image, rows);
+                        crop = crop.intersect(&rows).unwrap_or(rows);
+                    }
+                    if trim {
+                        let trim_box = cached_trim_box.unwrap_or_else(|| {
+                            *detected_trim_box.get_or_init(|| imaging::detect_borders(&image))
+                        });
+                        crop = crop.intersect(&trim_box).unwrap_or(crop);
+                    }
+
+                    let image = crop.apply(&image);
+                    match eink {
+                        Some(eink) => eink.apply(&image),
+                        None => image,
+                    }
+                }
+            },
+            output,
+            response_headers,
+        )
+        .await?;
+
+        // borders only need to be found once per page
+        if let Some(trim_box) = detected_trim_box.get() {
+            pages::ActiveModel {
+                id: Set(page_in_db.id.clone()),
+                trim_box: Set(Some(trim_box.to_string())),
+                ..Default::default()
+            }
+            .update(&data.db)
+            .await
+            .map_err(|e| AppError::from(anyhow::anyhow!("Can't update page: {}", e)))?;
+        }
+
+        return Ok(response);
+    }
+
+    entry_response(&index, &page_in_db.path, &headers, &etag, response_headers).await
 }
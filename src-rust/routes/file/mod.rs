@@ -1,5 +1,626 @@
 mod cover;
+mod get_batch;
+mod get_download;
 mod get_page;
 
+use std::{
+    io::{Cursor, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{archive::ArchiveIndex, config::Config, imaging, models::prelude::*, AppError};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{DynamicImage, ImageFormat};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use utoipa::IntoParams;
+
 pub use cover::*;
+pub use get_batch::*;
+pub use get_download::*;
 pub use get_page::*;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct FileQuery {
+    /// The archive hash of the title, as returned by `/api/index/title/{title_id}`.
+    /// When it matches the current hash, the response is marked as immutable.
+    pub v: Option<String>,
+}
+
+/// Build a strong ETag for a file inside an archive, from the archive hash and the entry path.
+fn entry_etag(hash: &str, entry_path: &str) -> String {
+    let path_hash =
+        murmur3::murmur3_x64_128(&mut Cursor::new(entry_path.as_bytes()), 0).unwrap_or_default();
+    format!("\"{}-{:032x}\"", hash, path_hash)
+}
+
+/// Get the last modified time of the archive on disk.
+async fn archive_modified(archive_path: impl AsRef<Path>) -> Option<SystemTime> {
+    tokio::fs::metadata(archive_path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Check if the client's cached copy is still valid, using `If-None-Match` first and
+/// falling back to `If-Modified-Since`.
+fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    let if_modified_since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+
+    match (if_modified_since, last_modified) {
+        // HTTP dates only have a precision of one second
+        (Some(since), Some(modified)) => {
+            let since = since.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            modified.as_secs() <= since.as_secs()
+        }
+        _ => false,
+    }
+}
+
+/// Build the caching headers shared by 200 and 304 responses.
+fn cache_headers(etag: &str, last_modified: Option<SystemTime>, immutable: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, value);
+    }
+
+    if let Some(last_modified) = last_modified {
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(last_modified)) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+
+    // files are behind auth, so they must never end up in a shared cache
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(match immutable {
+            true => "private, max-age=31536000, immutable",
+            false => "private, no-cache",
+        }),
+    );
+
+    headers
+}
+
+/// Build a `Link` header asking the client to preload the given pages.
+fn preload_links<'a>(
+    page_ids: impl IntoIterator<Item = &'a PageID>,
+    hash: &str,
+) -> Option<HeaderValue> {
+    let links = page_ids
+        .into_iter()
+        .map(|page_id| {
+            format!(
+                "</api/file/page/{}?v={}>; rel=preload; as=image",
+                page_id, hash
+            )
+        })
+        .collect::<Vec<_>>();
+
+    match links.is_empty() {
+        true => None,
+        false => HeaderValue::from_str(&links.join(", ")).ok(),
+    }
+}
+
+/// IDs of the `limit` pages read after `page`, in the order of `find_ordered_pages`: the
+/// cover first, then the other pages by path.
+///
+/// Only the IDs of those pages are fetched, as this runs on every page request.
+async fn find_next_page_ids(
+    db: &DatabaseConnection,
+    page: &pages::Model,
+    limit: usize,
+) -> Result<Vec<PageID>, DbErr> {
+    let cover_path = Covers::find_by_id(&page.title_id)
+        .select_only()
+        .column(covers::Column::Path)
+        .into_tuple::<String>()
+        .one(db)
+        .await?;
+
+    Pages::find()
+        .select_only()
+        .column(pages::Column::Id)
+        .filter(pages::Column::TitleId.eq(&page.title_id))
+        .apply_if(cover_path.as_ref(), |select, cover_path| {
+            select.filter(pages::Column::Path.ne(cover_path))
+        })
+        // every other page comes after the cover
+        .apply_if(
+            (cover_path.as_ref() != Some(&page.path)).then_some(&page.path),
+            |select, path| select.filter(pages::Column::Path.gt(path)),
+        )
+        .order_by_asc(pages::Column::Path)
+        .limit(limit as u64)
+        .into_tuple::<PageID>()
+        .all(db)
+        .await
+}
+
+/// Guess the content type of a file inside an archive from its extension.
+fn content_type(entry_path: &str) -> String {
+    let extension = PathBuf::from(entry_path)
+        .extension()
+        .map(|s| s.to_str().unwrap_or(""))
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "mp4" | "webm" => format!("video/{}", extension),
+        _ => format!("image/{}", extension),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RangeRequest {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+/// Parse a single-range `Range: bytes=...` header against a file of `size` bytes.
+///
+/// Multiple ranges, malformed headers and stale `If-Range` validators fall back to the
+/// whole file, as allowed by RFC 9110.
+fn parse_range(headers: &HeaderMap, etag: &str, size: u64) -> RangeRequest {
+    let Some(range) = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return RangeRequest::Full;
+    };
+
+    if let Some(if_range) = headers.get(header::IF_RANGE) {
+        if if_range.to_str().ok() != Some(etag) {
+            return RangeRequest::Full;
+        }
+    }
+
+    let Some((start, end)) = range
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return RangeRequest::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, size.saturating_sub(1)),
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+            _ => return RangeRequest::Full,
+        },
+    };
+
+    if size == 0 || start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial(start..end + 1)
+}
+
+/// Set `Content-Range`/`Content-Length` for a body of `size` bytes and pick the status code.
+///
+/// Returns `None` along with the status when the whole body should be sent.
+fn apply_range(
+    range: RangeRequest,
+    size: u64,
+    response_headers: &mut HeaderMap,
+) -> (StatusCode, Option<Range<u64>>) {
+    let (status, range) = match range {
+        RangeRequest::Full => (StatusCode::OK, None),
+        RangeRequest::Partial(range) => {
+            if let Ok(value) =
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", range.start, range.end - 1, size))
+            {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            (StatusCode::PARTIAL_CONTENT, Some(range))
+        }
+        RangeRequest::Unsatisfiable => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", size)) {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            return (StatusCode::RANGE_NOT_SATISFIABLE, None);
+        }
+    };
+
+    let content_length = range
+        .as_ref()
+        .map(|range| range.end - range.start)
+        .unwrap_or(size);
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    (status, range)
+}
+
+/// Stream a file from an archive, honoring `Range` for entries stored without compression.
+async fn entry_response(
+    index: &ArchiveIndex,
+    entry_path: &str,
+    headers: &HeaderMap,
+    etag: &str,
+    mut response_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let entry = match index.get(entry_path) {
+        Some(entry) => entry,
+        None => {
+            return Ok((StatusCode::NOT_FOUND, "File not found in archive.").into_response());
+        }
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&content_type(entry_path)) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+
+    let range = match entry.is_stored() {
+        true => {
+            response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+            parse_range(headers, etag, entry.size)
+        }
+        false => RangeRequest::Full,
+    };
+
+    let (status, range) = apply_range(range, entry.size, &mut response_headers);
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok((status, response_headers).into_response());
+    }
+
+    let body = index.stream(entry_path, range).await?;
+
+    Ok((status, response_headers, body).into_response())
+}
+
+/// Decode a file from an archive, apply `transform` to it on the blocking pool and send
+/// the result, in the `output` format or the original one. Transformed images don't
+/// support `Range`.
+async fn transformed_response(
+    index: Arc<ArchiveIndex>,
+    entry_path: &str,
+    transform: impl FnOnce(DynamicImage) -> DynamicImage + Send + 'static,
+    output: Option<ImageFormat>,
+    mut response_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if index.get(entry_path).is_none() {
+        return Ok((StatusCode::NOT_FOUND, "File not found in archive.").into_response());
+    }
+
+    let entry_path = entry_path.to_string();
+    let (buffer, content_type) = tokio::task::spawn_blocking(move || {
+        let mut buffer = Vec::new();
+        index.reader(&entry_path)?.read_to_end(&mut buffer)?;
+        let (image, format) = imaging::decode(&buffer)?;
+        imaging::encode(&transform(image), output.unwrap_or(format))
+    })
+    .await?
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't transform image: {}", e)))?;
+
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(buffer.len()));
+
+    Ok((StatusCode::OK, response_headers, Body::from(buffer)).into_response())
+}
+
+/// Send the first frame of an animated image or a video from an archive as a PNG.
+/// Videos need `FFMPEG_PATH`.
+async fn still_response(
+    config: &Config,
+    index: Arc<ArchiveIndex>,
+    entry_path: &str,
+    mut response_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if index.get(entry_path).is_none() {
+        return Ok((StatusCode::NOT_FOUND, "File not found in archive.").into_response());
+    }
+
+    let extension = PathBuf::from(entry_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let ffmpeg_path = match config.video_formats.contains(&extension.as_str()) {
+        true => Some(config.ffmpeg_path.clone().ok_or_else(|| {
+            AppError::from(anyhow::anyhow!(
+                "Can't extract still: FFMPEG_PATH is not set"
+            ))
+        })?),
+        false => None,
+    };
+    let temp_dir = config.temp_dir.clone();
+
+    let entry_path = entry_path.to_string();
+    let (buffer, content_type) = tokio::task::spawn_blocking(move || {
+        let mut buffer = Vec::new();
+        index.reader(&entry_path)?.read_to_end(&mut buffer)?;
+        let still = match ffmpeg_path {
+            Some(ffmpeg_path) => imaging::video_still(&ffmpeg_path, temp_dir, &buffer, &extension)?,
+            None => imaging::decode(&buffer)?.0,
+        };
+        imaging::encode(&still, ImageFormat::Png)
+    })
+    .await?
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't extract still: {}", e)))?;
+
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(buffer.len()));
+
+    Ok((StatusCode::OK, response_headers, Body::from(buffer)).into_response())
+}
+
+/// Get a file generated into `dir`, generating it on the blocking pool if it isn't there yet.
+///
+/// Files are named `<owner>-<fingerprint>.<ext>`. Generating one removes the files of the
+/// same owner and extension, as they are outdated.
+async fn generated_file(
+    dir: PathBuf,
+    owner: &str,
+    file_name: String,
+    generate: impl FnOnce(&Path) -> anyhow::Result<()> + Send + 'static,
+) -> Result<PathBuf, AppError> {
+    let path = dir.join(&file_name);
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Ok(path);
+    }
+
+    let stale_prefix = format!("{}-", owner);
+    let generated_path = path.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        std::fs::create_dir_all(&dir)?;
+
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let stale_path = entry.path();
+            let is_stale = stale_path != path
+                && stale_path.extension() == path.extension()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(&stale_prefix);
+            if is_stale {
+                let _ = std::fs::remove_file(stale_path);
+            }
+        }
+
+        // write next to the final file, then move it in place so readers never see half of it
+        let partial_path = path.with_extension(format!("{}.part", CustomID::new()));
+        if let Err(e) = generate(&partial_path) {
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(e);
+        }
+        std::fs::rename(&partial_path, &path)?;
+        Ok(())
+    })
+    .await?
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't generate {}: {}", file_name, e)))?;
+
+    Ok(generated_path)
+}
+
+/// Send an image stored on disk, outside of any archive. It can change at any time, so it's
+/// never marked as immutable.
+async fn disk_file_response(
+    path: impl AsRef<Path>,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let path = path.as_ref();
+    let last_modified = archive_modified(path).await;
+    let modified_secs = last_modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs())
+        .unwrap_or_default();
+    let etag = entry_etag(&modified_secs.to_string(), &path.to_string_lossy());
+    let mut response_headers = cache_headers(&etag, last_modified, false);
+
+    if is_not_modified(headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&content_type(&path.to_string_lossy())) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+    file_response(path, headers, &etag, response_headers).await
+}
+
+/// Stream a file on disk, honoring `Range`.
+async fn file_response(
+    path: impl AsRef<Path>,
+    headers: &HeaderMap,
+    etag: &str,
+    mut response_headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let mut file = tokio::fs::File::open(path.as_ref())
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Open file error: {}", e)))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Read file metadata error: {}", e)))?
+        .len();
+
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    let (status, range) = apply_range(
+        parse_range(headers, etag, size),
+        size,
+        &mut response_headers,
+    );
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok((status, response_headers).into_response());
+    }
+
+    let range = range.unwrap_or(0..size);
+    file.seek(std::io::SeekFrom::Start(range.start))
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Seek file error: {}", e)))?;
+    let body = Body::from_stream(ReaderStream::new(file.take(range.end - range.start)));
+
+    Ok((status, response_headers, body).into_response())
+}
+
+#[cfg(test)]
+mod tests_range {
+    use super::*;
+
+    fn range_headers(range: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_str(range).unwrap());
+        headers
+    }
+
+    #[test]
+    fn no_range() {
+        assert_eq!(
+            parse_range(&HeaderMap::new(), "\"a\"", 100),
+            RangeRequest::Full
+        );
+    }
+
+    #[test]
+    fn bounded_and_open() {
+        let etag = "\"a\"";
+        assert_eq!(
+            parse_range(&range_headers("bytes=0-9"), etag, 100),
+            RangeRequest::Partial(0..10)
+        );
+        assert_eq!(
+            parse_range(&range_headers("bytes=90-"), etag, 100),
+            RangeRequest::Partial(90..100)
+        );
+        assert_eq!(
+            parse_range(&range_headers("bytes=-10"), etag, 100),
+            RangeRequest::Partial(90..100)
+        );
+        assert_eq!(
+            parse_range(&range_headers("bytes=50-1000"), etag, 100),
+            RangeRequest::Partial(50..100)
+        );
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        let etag = "\"a\"";
+        assert_eq!(
+            parse_range(&range_headers("bytes=100-"), etag, 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(&range_headers("bytes=-0"), etag, 100),
+            RangeRequest::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn ignored() {
+        let etag = "\"a\"";
+        assert_eq!(
+            parse_range(&range_headers("bytes=0-1,5-6"), etag, 100),
+            RangeRequest::Full
+        );
+        assert_eq!(
+            parse_range(&range_headers("items=0-1"), etag, 100),
+            RangeRequest::Full
+        );
+
+        let mut headers = range_headers("bytes=0-9");
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"b\""));
+        assert_eq!(parse_range(&headers, etag, 100), RangeRequest::Full);
+    }
+}
+
+#[cfg(test)]
+mod tests_next_pages {
+    use super::*;
+    use crate::{
+        models::fixtures::{insert_category, memory_db},
+        routes::find_ordered_pages,
+    };
+    use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+
+    #[tokio::test]
+    async fn reading_order() {
+        let db = &memory_db().await;
+        let category_id = insert_category(db, "Manga").await;
+        let title_id = CustomID::new();
+        titles::Model {
+            id: title_id.clone(),
+            title: "Title".to_string(),
+            category_id,
+            ..Default::default()
+        }
+        .into_active_model()
+        .insert(db)
+        .await
+        .unwrap();
+        for path in ["1.jpg", "2.jpg", "3.jpg", "4.jpg"] {
+            pages::ActiveModel {
+                id: Set(PageID::new()),
+                title_id: Set(title_id.clone()),
+                path: Set(path.to_string()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        covers::ActiveModel {
+            id: Set(title_id.clone()),
+            path: Set("3.jpg".to_string()),
+            blurhash: Set(String::new()),
+            ratio: Set(0),
+            is_custom: Set(false),
+            rule: Set(None),
+            rules_hash: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        // the same pages as going through all of them in order
+        let ordered = find_ordered_pages(db, &title_id).await.unwrap();
+        for (i, page) in ordered.iter().enumerate() {
+            let expected = ordered
+                .iter()
+                .skip(i + 1)
+                .take(2)
+                .map(|page| page.id.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(find_next_page_ids(db, page, 2).await.unwrap(), expected);
+        }
+    }
+}
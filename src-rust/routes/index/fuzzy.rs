@@ -0,0 +1,317 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::SuggestCorpus;
+
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, DbErr, Statement};
+use tokio::sync::Mutex;
+
+/// Words of the search index that typos are looked for in, descriptions are left out.
+const VOCAB_SQL: &str = "SELECT DISTINCT term FROM titles_search_vocab
+    WHERE col IN ('title', 'author', 'tags')";
+
+/// At most this many close words are searched for each word typed.
+const MAX_ALTERNATIVES: usize = 16;
+
+/// Words shorter than this once folded only match as they are.
+const MIN_FUZZY_LENGTH: usize = 3;
+
+/// Lowercase `text` and drop everything but letters and digits, with the ways romanizations
+/// differ folded away: "Shōnen", "Shounen" and "Shonen" are all "shonen", "Tsuki ga" is
+/// "tsukiga".
+pub fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'ā' => 'a',
+            'è' | 'é' | 'ê' | 'ë' | 'ē' => 'e',
+            'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ō' => 'o',
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => continue,
+        };
+        // long vowels are doubled, written with a macron or as "ou"
+        let long_vowel = matches!(
+            (folded.chars().last(), c),
+            (Some('a'), 'a')
+                | (Some('e'), 'e')
+                | (Some('i'), 'i')
+                | (Some('o'), 'o' | 'u')
+                | (Some('u'), 'u')
+        );
+        if !long_vowel {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+/// Number of typos allowed in a folded word.
+pub fn allowed_typos(folded: &str) -> usize {
+    (folded.chars().count() / 4).min(2)
+}
+
+/// Edit distance from `pattern` to the closest prefix of `text`, or to its closest part
+/// anywhere when `anchored` is false. Swapping two letters counts as one typo.
+pub fn distance(pattern: &str, text: &str, anchored: bool) -> usize {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let mut before = vec![0; text.len() + 1];
+    let mut previous = (0..=text.len())
+        .map(|j| if anchored { j } else { 0 })
+        .collect::<Vec<_>>();
+    for (i, p) in pattern.iter().enumerate() {
+        let mut row = vec![i + 1; text.len() + 1];
+        for (j, t) in text.iter().enumerate() {
+            let mut cost = (previous[j] + usize::from(p != t))
+                .min(previous[j + 1] + 1)
+                .min(row[j] + 1);
+            if i > 0 && j > 0 && *p == text[j - 1] && pattern[i - 1] == *t {
+                cost = cost.min(before[j - 1] + 1);
+            }
+            row[j + 1] = cost;
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous.into_iter().min().unwrap_or_default()
+}
+
+/// How well a folded query matches `text`, lower is better: the typos, then whether the match
+/// isn't at the start, then the length of `text`. `None` with too many typos.
+pub fn match_rank(folded: &str, text: &str) -> Option<(usize, bool, usize)> {
+    let text = fold(text);
+    let inside = distance(folded, &text, false);
+    if inside > allowed_typos(folded) {
+        return None;
+    }
+    let at_start = distance(folded, &text, true);
+    Some((inside, at_start > inside, text.chars().count()))
+}
+
+/// Quote a word for FTS5, so operators and column filters are searched as text.
+pub fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('"', "\"\""))
+}
+
+/// What searches and suggestions are matched against, loaded from the library on first use
+/// and kept until the library changes.
+#[derive(Debug, Default)]
+pub struct SearchCache {
+    vocab: Mutex<Option<Arc<Vocab>>>,
+    suggestions: Mutex<Option<Arc<SuggestCorpus>>>,
+}
+
+impl SearchCache {
+    pub async fn vocab(&self, db: &DatabaseConnection) -> Result<Arc<Vocab>, DbErr> {
+        // held while loading, so requests coming in meanwhile wait for it rather than load it too
+        let mut vocab = self.vocab.lock().await;
+        if let Some(vocab) = vocab.as_ref() {
+            return Ok(vocab.clone());
+        }
+        let loaded = Arc::new(Vocab::find(db).await?);
+        *vocab = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    pub async fn suggestions(&self, db: &DatabaseConnection) -> Result<Arc<SuggestCorpus>, DbErr> {
+        let mut suggestions = self.suggestions.lock().await;
+        if let Some(suggestions) = suggestions.as_ref() {
+            return Ok(suggestions.clone());
+        }
+        let loaded = Arc::new(SuggestCorpus::find(db).await?);
+        *suggestions = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Load everything again on next use, after titles, authors or tags changed.
+    pub async fn clear(&self) {
+        *self.vocab.lock().await = None;
+        *self.suggestions.lock().await = None;
+    }
+}
+
+/// The words of titles, authors and tags in the search index, by their folded form.
+#[derive(Debug)]
+pub struct Vocab {
+    terms: Vec<(String, String)>,
+    folded: HashMap<String, String>,
+}
+
+impl Vocab {
+    pub async fn find(db: &DatabaseConnection) -> Result<Self, DbErr> {
+        let terms = db
+            .query_all(Statement::from_string(DbBackend::Sqlite, VOCAB_SQL))
+            .await?
+            .iter()
+            .map(|row| row.try_get::<String>("", "term"))
+            .collect::<Result<Vec<_>, DbErr>>()?;
+        Ok(Self::new(terms))
+    }
+
+    fn new(terms: Vec<String>) -> Self {
+        let terms = terms
+            .into_iter()
+            .map(|term| {
+                let folded = fold(&term);
+                (term, folded)
+            })
+            .collect::<Vec<_>>();
+        let folded = terms
+            .iter()
+            .map(|(term, folded)| (folded.clone(), term.clone()))
+            .collect();
+        Self { terms, folded }
+    }
+
+    /// FTS5 query matching `word` as a prefix, or the indexed words it's a few typos away from,
+    /// or two indexed words it's written as together.
+    fn word_query(&self, word: &str) -> String {
+        let mut alternatives = vec![format!("{}*", quote(word))];
+        let folded = fold(word);
+        if folded.chars().count() >= MIN_FUZZY_LENGTH {
+            let lowercase = word.to_lowercase();
+            let typos = allowed_typos(&folded);
+            let mut close = self
+                .terms
+                .iter()
+                .filter(|(term, _)| !term.starts_with(&lowercase))
+                .filter_map(|(term, folded_term)| {
+                    let distance = distance(&folded, folded_term, true);
+                    (distance <= typos).then_some((distance, term.len(), term))
+                })
+                .collect::<Vec<_>>();
+            close.sort();
+            alternatives.extend(
+                close
+                    .into_iter()
+                    .take(MAX_ALTERNATIVES)
+                    .map(|(_, _, term)| quote(term)),
+            );
+
+            // "tsukiga" for "tsuki ga"
+            let chars = folded.chars().collect::<Vec<_>>();
+            for split in 2..chars.len().saturating_sub(1) {
+                let first = chars[..split].iter().collect::<String>();
+                let second = chars[split..].iter().collect::<String>();
+                if let (Some(first), Some(second)) =
+                    (self.folded.get(&first), self.folded.get(&second))
+                {
+                    alternatives.push(quote(&format!("{} {}", first, second)));
+                }
+            }
+        }
+
+        match alternatives.len() {
+            1 => alternatives.remove(0),
+            _ => format!("({})", alternatives.join(" OR ")),
+        }
+    }
+
+    /// Turn what a user typed into an FTS5 query like `search_query`, where words also match
+    /// with a few typos, spelled another way, or written apart or together.
+    pub fn search_query(&self, input: &str) -> Option<String> {
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        let mut queries = vec![];
+        let mut i = 0;
+        while i < words.len() {
+            let query = self.word_query(words[i]);
+            // "tsuki ga" for "tsukiga"
+            let joined = words
+                .get(i + 1)
+                .and_then(|next| self.folded.get(&(fold(words[i]) + &fold(next))));
+            match joined {
+                Some(term) => {
+                    queries.push(format!(
+                        "(({} AND {}) OR {})",
+                        query,
+                        self.word_query(words[i + 1]),
+                        quote(term)
+                    ));
+                    i += 2;
+                }
+                None => {
+                    queries.push(query);
+                    i += 1;
+                }
+            }
+        }
+
+        match queries.is_empty() {
+            true => None,
+            // FTS5 only leaves out AND between phrases, not groups in parentheses
+            false => Some(queries.join(" AND ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_fuzzy {
+    use super::*;
+
+    fn vocab(terms: &[&str]) -> Vocab {
+        Vocab::new(terms.iter().map(|term| term.to_string()).collect())
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(fold("Shōnen"), "shonen");
+        assert_eq!(fold("Shounen"), "shonen");
+        assert_eq!(fold("SHONEN"), "shonen");
+        assert_eq!(fold("Tsuki ga"), "tsukiga");
+        assert_eq!(fold("Yuuki-san!"), "yukisan");
+        assert_eq!(fold("Pokémon"), "pokemon");
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("naruto", "naruto", true), 0);
+        assert_eq!(distance("nartuo", "naruto", true), 1);
+        assert_eq!(distance("nrauto", "naruto", true), 1);
+        assert_eq!(distance("nauto", "naruto", true), 1);
+        assert_eq!(distance("narut", "naruto shippuden", true), 0);
+        assert_eq!(distance("shipuden", "naruto shippuden", true), 7);
+        assert_eq!(distance("shipuden", "naruto shippuden", false), 1);
+        assert_eq!(distance("", "naruto", true), 0);
+    }
+
+    #[test]
+    fn ranks() {
+        let folded = fold("shounen");
+        assert_eq!(match_rank(&folded, "Shōnen Jump"), Some((0, false, 10)));
+        assert_eq!(match_rank(&folded, "Weekly Shonen"), Some((0, true, 11)));
+        assert_eq!(match_rank(&folded, "Shojo"), None);
+        assert!(match_rank(&fold("sinen"), "seinen").is_some());
+    }
+
+    #[test]
+    fn queries() {
+        let vocab = vocab(&["shonen", "shounen", "tsukiga", "tsuki", "ga", "jump"]);
+        assert_eq!(vocab.search_query("  "), None);
+        // already matched as a prefix
+        assert_eq!(vocab.search_query("jum"), Some("\"jum\"*".to_string()));
+        assert_eq!(
+            vocab.search_query("shonen"),
+            Some("(\"shonen\"* OR \"shounen\")".to_string())
+        );
+        assert_eq!(
+            vocab.search_query("jupm"),
+            Some("(\"jupm\"* OR \"jump\")".to_string())
+        );
+        assert_eq!(
+            vocab.search_query("tsuki ga"),
+            Some("((\"tsuki\"* AND \"ga\"*) OR \"tsukiga\")".to_string())
+        );
+        assert_eq!(
+            vocab.search_query("Tsukiga"),
+            Some("(\"Tsukiga\"* OR \"tsuki ga\")".to_string())
+        );
+        // short words aren't guessed
+        assert_eq!(vocab.search_query("sh"), Some("\"sh\"*".to_string()));
+        assert_eq!(
+            vocab.search_query("x\"y OR"),
+            Some("\"x\"\"y\"* AND \"OR\"*".to_string())
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use crate::{models::prelude::*, AppError, AppState};
+use super::ResponseCover;
+use crate::{models::prelude::*, routes::calculate_dimension, AppError, AppState};
 
 use axum::{
     extract::State,
@@ -19,6 +20,8 @@ pub struct CategoryResponse {
     pub id: String,
     pub name: String,
     pub description: Option<String>,
+    /// Fetched from `/api/file/category-cover/{id}`, once the analyzer has picked it.
+    pub cover: Option<ResponseCover>,
 }
 
 #[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
@@ -33,15 +36,59 @@ pub struct CategoriesResponseBody {
     (status = 500, description = "Internal server error", body = String)
 ))]
 pub async fn get_categories(State(data): State<Arc<AppState>>) -> Result<Response, AppError> {
-    let data = Categories::find()
+    let categories = Categories::find()
+        .find_also_related(CategoryCovers)
         .all(&data.db)
         .await
-        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find categories: {}", e)))?
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find categories: {}", e)))?;
+
+    // a category using the cover of a title has the same format
+    let title_ids = categories
+        .iter()
+        .filter_map(|(_, cover)| cover.as_ref())
+        .filter(|cover| cover.source == CategoryCoverSource::Title)
+        .flat_map(|cover| cover.title_ids())
+        .collect::<Vec<_>>();
+    let title_cover_paths = Covers::find()
+        .filter(covers::Column::Id.is_in(title_ids))
+        .all(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find covers: {}", e)))?
+        .into_iter()
+        .map(|cover| (cover.id, cover.path))
+        .collect::<HashMap<_, _>>();
+
+    let data = categories
         .into_iter()
-        .map(|category| CategoryResponse {
+        .map(|(category, cover)| CategoryResponse {
             id: category.id.to_string(),
             name: category.name,
             description: category.description,
+            cover: cover.map(|cover| {
+                let (width, height) = calculate_dimension(&data.config, cover.ratio);
+                let path = match cover.source {
+                    CategoryCoverSource::File => Some(&cover.path),
+                    CategoryCoverSource::Title => cover
+                        .title_ids()
+                        .first()
+                        .and_then(|id| title_cover_paths.get(id)),
+                    CategoryCoverSource::Mosaic => None,
+                };
+                ResponseCover {
+                    blurhash: cover.blurhash,
+                    width,
+                    height,
+                    format: match path {
+                        Some(path) => PathBuf::from(path)
+                            .extension()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or("")
+                            .to_ascii_lowercase(),
+                        None => "jpg".to_string(),
+                    },
+                    rule: None,
+                }
+            }),
         })
         .collect();
 
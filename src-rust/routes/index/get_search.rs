@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use super::{decode_cursor, encode_cursor, find_title_responses, quote, FilterTitleResponseBody};
+use crate::{models::prelude::*, AppError, AppState};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{
+    sea_query::{Expr, SimpleExpr},
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait,
+    QueryFilter, Statement,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+/// Used when `limit` isn't given.
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+/// `limit` can't be more than this.
+const MAX_SEARCH_LIMIT: u32 = 200;
+
+/// Matches are wrapped in these by SQLite, they're replaced by `<mark>` once the rest of the
+/// text is escaped.
+const MARK_START: &str = "\u{E000}";
+const MARK_END: &str = "\u{E001}";
+
+/// Titles are ranked with these weights for the title, author, description, tags and page
+/// descriptions columns, then by their search ID so pages never skip or repeat titles.
+const SEARCH_SQL: &str = "SELECT * FROM (
+        SELECT ids.title_id AS title_id,
+            highlight(titles_search, 0, ?1, ?2) AS highlight,
+            snippet(titles_search, -1, ?1, ?2, '…', 16) AS snippet,
+            bm25(titles_search, 10.0, 5.0, 1.0, 3.0, 1.0) AS rank,
+            ids.id AS search_id
+        FROM titles_search
+        JOIN titles_search_ids ids ON ids.id = titles_search.rowid
+        JOIN titles ON titles.id = ids.title_id
+        WHERE titles_search MATCH ?3 AND titles.duplicate_of IS NULL
+    )
+    WHERE ?5 IS NULL OR rank > ?5 OR (rank = ?5 AND search_id > ?6)
+    ORDER BY rank, search_id
+    LIMIT ?4";
+
+const SEARCH_COUNT_SQL: &str = "SELECT count(*) AS total
+    FROM titles_search
+    JOIN titles_search_ids ids ON ids.id = titles_search.rowid
+    JOIN titles ON titles.id = ids.title_id
+    WHERE titles_search MATCH ?1 AND titles.duplicate_of IS NULL";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct SearchQuery {
+    /// Words to search for in titles, authors, descriptions, tags and page descriptions.
+    /// Every word has to match, as a prefix, or with a few typos or spelled another way in
+    /// titles, authors and tags.
+    pub q: String,
+    /// Maximum number of results to return, 50 by default and 200 at most
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchTitleResponseBody {
+    #[serde(flatten)]
+    pub title: FilterTitleResponseBody,
+    /// The title, HTML escaped, with matches in `<mark>`.
+    pub highlight: String,
+    /// The best matching part of any field, HTML escaped, with matches in `<mark>`.
+    pub snippet: String,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchResponseBody {
+    pub data: Vec<SearchTitleResponseBody>,
+    /// Number of titles matching the search, on all pages.
+    pub total: u64,
+    /// Pass it as `cursor` with the same search to get the next page, `None` on the last one.
+    pub next_cursor: Option<String>,
+}
+
+/// Where a page of search results stopped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SearchCursor {
+    search: String,
+    rank: f64,
+    search_id: i64,
+}
+
+/// Search titles, best matches first.
+#[utoipa::path(get, path = "/api/index/search", params(SearchQuery), responses(
+    (status = 200, description = "Search successful", body = SearchResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_search(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Response, AppError> {
+    let search = match search_query(&query.q) {
+        Some(search) => search,
+        None => return Ok((StatusCode::BAD_REQUEST, "Search is empty.").into_response()),
+    };
+
+    let cursor = match &query.cursor {
+        Some(cursor) => match decode_cursor::<SearchCursor>(cursor) {
+            Some(cursor) if cursor.search == search => Some(cursor),
+            _ => return Ok((StatusCode::BAD_REQUEST, "Invalid cursor.").into_response()),
+        },
+        None => None,
+    };
+
+    // the cursor keeps what was typed, the close words can change between pages
+    let vocab = app_state
+        .search_cache
+        .vocab(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find search words: {}", e)))?;
+    // close words are looked for among every word of the index
+    let typed = query.q.clone();
+    let fuzzy_search = tokio::task::spawn_blocking(move || vocab.search_query(&typed))
+        .await?
+        .unwrap_or_else(|| search.clone());
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(MAX_SEARCH_LIMIT);
+    let (mut hits, total) = search_titles(&app_state.db, &fuzzy_search, limit + 1, cursor.as_ref())
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't search titles: {}", e)))?;
+
+    let next_cursor = match hits.len() > limit as usize {
+        true => {
+            hits.truncate(limit as usize);
+            hits.last().map(|hit| {
+                encode_cursor(&SearchCursor {
+                    search: search.clone(),
+                    rank: hit.rank,
+                    search_id: hit.search_id,
+                })
+            })
+        }
+        false => None,
+    };
+
+    let mut title_models = Titles::find()
+        .filter(titles::Column::Id.is_in(hits.iter().map(|hit| hit.title_id.clone())))
+        .all(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?;
+
+    // titles in the order of their hits
+    let (hits, title_models): (Vec<_>, Vec<_>) = hits
+        .into_iter()
+        .filter_map(|hit| {
+            let index = title_models
+                .iter()
+                .position(|title| title.id.to_string() == hit.title_id)?;
+            Some((hit, title_models.swap_remove(index)))
+        })
+        .unzip();
+
+    let data = find_title_responses(&app_state, title_models, &user.id)
+        .await?
+        .into_iter()
+        .zip(hits)
+        .filter_map(|(title, hit)| {
+            Some(SearchTitleResponseBody {
+                title: title?,
+                highlight: mark(&hit.highlight),
+                snippet: mark(&hit.snippet),
+            })
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(SearchResponseBody {
+            data,
+            total,
+            next_cursor,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SearchHit {
+    title_id: String,
+    highlight: String,
+    snippet: String,
+    rank: f64,
+    search_id: i64,
+}
+
+/// Find the titles matching an FTS5 query after `cursor`, best matches first, along with the
+/// total number of matches.
+async fn search_titles(
+    db: &DatabaseConnection,
+    search: &str,
+    limit: u32,
+    cursor: Option<&SearchCursor>,
+) -> Result<(Vec<SearchHit>, u64), DbErr> {
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            SEARCH_SQL,
+            [
+                MARK_START.into(),
+                MARK_END.into(),
+                search.into(),
+                limit.into(),
+                cursor.map(|cursor| cursor.rank).into(),
+                cursor.map(|cursor| cursor.search_id).into(),
+            ],
+        ))
+        .await?;
+    let hits = rows
+        .iter()
+        .map(|row| {
+            Ok(SearchHit {
+                title_id: row.try_get("", "title_id")?,
+                highlight: row.try_get("", "highlight")?,
+                snippet: row.try_get("", "snippet")?,
+                rank: row.try_get("", "rank")?,
+                search_id: row.try_get("", "search_id")?,
+            })
+        })
+        .collect::<Result<Vec<_>, DbErr>>()?;
+
+    let total = db
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            SEARCH_COUNT_SQL,
+            [search.into()],
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "total"))
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok((hits, total as u64))
+}
+
+/// Turn what a user typed into an FTS5 query where every word has to match as a prefix.
+///
+/// Words are quoted, so FTS5 operators and column filters are searched as text.
+pub fn search_query(input: &str) -> Option<String> {
+    let words = input
+        .split_whitespace()
+        .map(|word| format!("{}*", quote(word)))
+        .collect::<Vec<_>>();
+
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    }
+}
+
+/// Condition on titles matching an FTS5 query from `search_query`.
+pub fn matches_search(search: String) -> SimpleExpr {
+    Expr::cust_with_values(
+        "titles.id IN (SELECT ids.title_id FROM titles_search
+            JOIN titles_search_ids ids ON ids.id = titles_search.rowid
+            WHERE titles_search MATCH ?)",
+        [search],
+    )
+}
+
+/// Condition on titles where every word of what a user typed starts a word, or is found
+/// anywhere inside the text, like "lore" in "Folklore" or part of a Japanese title written
+/// without spaces. `None` when nothing was typed.
+pub fn matches_keyword(input: &str) -> Option<Condition> {
+    let words = input.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() {
+        return None;
+    }
+
+    let condition = words.iter().fold(Condition::all(), |all, word| {
+        let prefix = matches_search(format!("{}*", quote(word)));
+        // trigrams only index substrings of 3 characters and more
+        let substring = match word.chars().count() >= 3 {
+            true => Expr::cust_with_values(
+                "titles.id IN (SELECT ids.title_id FROM titles_trigram
+                    JOIN titles_search_ids ids ON ids.id = titles_trigram.rowid
+                    WHERE titles_trigram MATCH ?)",
+                [quote(word)],
+            ),
+            false => {
+                // `\` would be read as escaping the quote around it when the values are bound
+                let pattern = format!(
+                    "%{}%",
+                    word.replace('!', "!!")
+                        .replace('%', "!%")
+                        .replace('_', "!_")
+                );
+                Expr::cust_with_values(
+                    "titles.id IN (SELECT ids.title_id FROM titles_trigram
+                        JOIN titles_search_ids ids ON ids.id = titles_trigram.rowid
+                        WHERE title LIKE ? ESCAPE '!' OR author LIKE ? ESCAPE '!'
+                            OR description LIKE ? ESCAPE '!' OR tags LIKE ? ESCAPE '!'
+                            OR pages LIKE ? ESCAPE '!')",
+                    vec![pattern; 5],
+                )
+            }
+        };
+        all.add(Condition::any().add(prefix).add(substring))
+    });
+    Some(condition)
+}
+
+/// Escape text from the database for HTML, then turn the match markers into `<mark>`.
+fn mark(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+#[cfg(test)]
+mod tests_search {
+    use super::*;
+    use crate::{
+        models::fixtures::{insert_category, memory_db},
+        routes::Vocab,
+    };
+    use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+
+    #[test]
+    fn query() {
+        assert_eq!(search_query("  "), None);
+        assert_eq!(
+            search_query("Nar  fol"),
+            Some("\"Nar\"* \"fol\"*".to_string())
+        );
+        // operators and quotes are only text
+        assert_eq!(
+            search_query("title:x OR \"y"),
+            Some("\"title:x\"* \"OR\"* \"\"\"y\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn marks() {
+        assert_eq!(
+            mark("a <b> & \u{E000}c\u{E001}"),
+            "a &lt;b&gt; &amp; <mark>c</mark>"
+        );
+    }
+
+    async fn title(
+        db: &DatabaseConnection,
+        category_id: &CategoryID,
+        name: &str,
+        description: &str,
+    ) -> TitleID {
+        let title = titles::Model {
+            id: CustomID::new(),
+            title: name.to_string(),
+            category_id: category_id.clone(),
+            description: Some(description.to_string()),
+            ..Default::default()
+        };
+        title.clone().into_active_model().insert(db).await.unwrap();
+        title.id
+    }
+
+    async fn search(db: &DatabaseConnection, input: &str) -> Vec<SearchHit> {
+        search_titles(db, &search_query(input).unwrap(), 10, None)
+            .await
+            .unwrap()
+            .0
+    }
+
+    #[tokio::test]
+    async fn ranked_and_kept_in_sync() {
+        let db = memory_db().await;
+        let category_id = insert_category(&db, "Manga").await;
+
+        let in_description = title(&db, &category_id, "Hello", "A <folklore> tale").await;
+        let in_title = title(&db, &category_id, "Folklore", "Stories").await;
+
+        let hits = search(&db, "FOLK").await;
+        assert_eq!(
+            hits.iter().map(|hit| &hit.title_id).collect::<Vec<_>>(),
+            vec![&in_title.to_string(), &in_description.to_string()]
+        );
+        assert_eq!(hits[0].highlight, "\u{E000}Folklore\u{E001}");
+        assert_eq!(hits[1].snippet, "A <\u{E000}folklore\u{E001}> tale");
+
+        // tags and page descriptions are searched too
+        tags::ActiveModel {
+            id: Set(1),
+            name: Set("Adventure".to_string()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        titles_tags::ActiveModel {
+            title_id: Set(in_description.to_string()),
+            tag_id: Set(1),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        pages::ActiveModel {
+            id: Set(CustomID::new()),
+            title_id: Set(in_title.clone()),
+            path: Set("001.jpg".to_string()),
+            description: Set(Some("The dragon".to_string())),
+            is_spread: Set(false),
+            is_animated: Set(false),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        assert_eq!(
+            search(&db, "adv").await[0].title_id,
+            in_description.to_string()
+        );
+        assert_eq!(
+            search(&db, "dragon").await[0].title_id,
+            in_title.to_string()
+        );
+
+        titles::ActiveModel {
+            id: Set(in_title.clone()),
+            title: Set("Myths".to_string()),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        assert_eq!(search(&db, "folk").await.len(), 1);
+
+        Titles::delete_by_id(in_description)
+            .exec(&db)
+            .await
+            .unwrap();
+        assert!(search(&db, "folk").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pages() {
+        let db = memory_db().await;
+        let category_id = insert_category(&db, "Manga").await;
+        // the same rank for all of them
+        for _ in 0..5 {
+            title(&db, &category_id, "Saga", "").await;
+        }
+        title(&db, &category_id, "Other", "").await;
+
+        let search = search_query("saga").unwrap();
+        let mut cursor = None;
+        let mut listed = vec![];
+        loop {
+            let (hits, total) = search_titles(&db, &search, 2, cursor.as_ref())
+                .await
+                .unwrap();
+            assert_eq!(total, 5);
+            let Some(last) = hits.last() else {
+                break;
+            };
+            cursor = Some(SearchCursor {
+                search: search.clone(),
+                rank: last.rank,
+                search_id: last.search_id,
+            });
+            listed.extend(hits.into_iter().map(|hit| hit.title_id));
+        }
+
+        assert_eq!(listed.len(), 5);
+        listed.sort();
+        listed.dedup();
+        assert_eq!(listed.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn fuzzy() {
+        let db = memory_db().await;
+        let category_id = insert_category(&db, "Manga").await;
+        let shounen = title(&db, &category_id, "Shounen Days", "").await;
+        let tsukiga = title(&db, &category_id, "Tsukiga Kirei", "").await;
+        title(&db, &category_id, "Other", "Shonen in the description").await;
+
+        let vocab = Vocab::find(&db).await.unwrap();
+        for (input, title_id) in [
+            ("shonen", &shounen),
+            ("shōnen dyas", &shounen),
+            ("tsuki ga", &tsukiga),
+            ("kirie", &tsukiga),
+        ] {
+            let search = vocab.search_query(input).unwrap();
+            let (hits, _) = search_titles(&db, &search, 10, None).await.unwrap();
+            assert_eq!(hits[0].title_id, title_id.to_string(), "{}", input);
+        }
+        assert_eq!(search(&db, "tsuki ga").await.len(), 0);
+    }
+}
@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use super::{fold, match_rank};
+use crate::{models::prelude::*, routes::TagResponseBody, AppError, AppState};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+/// Used when `limit` isn't given.
+const DEFAULT_SUGGEST_LIMIT: u32 = 5;
+
+/// `limit` can't be more than this.
+const MAX_SUGGEST_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct SuggestQuery {
+    /// What has been typed so far.
+    pub q: String,
+    /// Maximum number of suggestions of each kind.
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TitleSuggestion {
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SuggestResponseBody {
+    pub titles: Vec<TitleSuggestion>,
+    pub authors: Vec<String>,
+    pub tags: Vec<TagResponseBody>,
+}
+
+/// Suggest titles, authors and tags as a search is typed, best matches first.
+///
+/// They match with a few typos or spelled another way, e.g. "shonen" for "Shōnen" or "Shounen".
+#[utoipa::path(get, path = "/api/index/suggest", params(SuggestQuery), responses(
+    (status = 200, description = "Suggest successful", body = SuggestResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_suggest(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Response, AppError> {
+    let folded = fold(&query.q);
+    if folded.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, "Search is empty.").into_response());
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SUGGEST_LIMIT)
+        .min(MAX_SUGGEST_LIMIT) as usize;
+
+    let corpus = app_state
+        .search_cache
+        .suggestions(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find suggestions: {}", e)))?;
+    // every title, author and tag is ranked, which is too slow for the async runtime
+    let suggestions = tokio::task::spawn_blocking(move || corpus.suggest(&folded, limit)).await?;
+
+    Ok((StatusCode::OK, Json(suggestions)).into_response())
+}
+
+/// The `limit` best matches of a folded query in each of `items`.
+fn best<'a, T>(
+    folded: &str,
+    items: &'a [T],
+    text: impl Fn(&T) -> &str,
+    limit: usize,
+) -> Vec<&'a T> {
+    let mut matches = items
+        .iter()
+        .filter_map(|item| Some((match_rank(folded, text(item))?, item)))
+        .collect::<Vec<_>>();
+    matches.sort_by(|(a, a_item), (b, b_item)| a.cmp(b).then(text(a_item).cmp(text(b_item))));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// The titles, authors and tags suggestions are picked from, kept in [SearchCache] between
+/// requests.
+///
+/// [SearchCache]: super::SearchCache
+#[derive(Debug)]
+pub struct SuggestCorpus {
+    titles: Vec<(TitleID, String, Option<String>)>,
+    authors: Vec<String>,
+    tags: Vec<tags::Model>,
+}
+
+impl SuggestCorpus {
+    pub async fn find(db: &DatabaseConnection) -> Result<Self, DbErr> {
+        let titles = Titles::find()
+            .select_only()
+            .column(titles::Column::Id)
+            .column(titles::Column::Title)
+            .column(titles::Column::Author)
+            .filter(titles::Column::DuplicateOf.is_null())
+            .into_tuple::<(TitleID, String, Option<String>)>()
+            .all(db)
+            .await?;
+        let authors = Titles::find()
+            .select_only()
+            .column(titles::Column::Author)
+            .distinct()
+            .filter(titles::Column::Author.is_not_null())
+            .filter(titles::Column::DuplicateOf.is_null())
+            .into_tuple::<String>()
+            .all(db)
+            .await?;
+        let tags = Tags::find().all(db).await?;
+
+        Ok(Self {
+            titles,
+            authors,
+            tags,
+        })
+    }
+
+    fn suggest(&self, folded: &str, limit: usize) -> SuggestResponseBody {
+        let titles = best(folded, &self.titles, |(_, title, _)| title, limit)
+            .into_iter()
+            .map(|(id, title, author)| TitleSuggestion {
+                id: id.to_string(),
+                title: title.clone(),
+                author: author.clone(),
+            })
+            .collect();
+        let authors = best(folded, &self.authors, |author| author, limit)
+            .into_iter()
+            .cloned()
+            .collect();
+        let tags = best(folded, &self.tags, |tag| &tag.name, limit)
+            .into_iter()
+            .map(|tag| TagResponseBody {
+                id: tag.id,
+                name: tag.name.clone(),
+            })
+            .collect();
+
+        SuggestResponseBody {
+            titles,
+            authors,
+            tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_suggest {
+    use super::*;
+    use crate::{
+        models::fixtures::{insert_category, memory_db},
+        routes::SearchCache,
+    };
+    use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+
+    #[tokio::test]
+    async fn suggestions() {
+        let db = memory_db().await;
+        let category_id = insert_category(&db, "Manga").await;
+        let duplicate_of = CustomID::new();
+        for (id, name, author, duplicate_of) in [
+            (duplicate_of.clone(), "Tsuki ga Kirei", "Ōno Yūki", None),
+            (
+                CustomID::new(),
+                "Tsukiga Kirei",
+                "Ono Yuki",
+                Some(duplicate_of),
+            ),
+            (CustomID::new(), "Weekly Shōnen Tales", "Shounen Club", None),
+            (CustomID::new(), "Shonen", "Kaneshiro", None),
+            (CustomID::new(), "Seinen", "Kaneshiro", None),
+        ] {
+            titles::Model {
+                id,
+                title: name.to_string(),
+                author: Some(author.to_string()),
+                category_id: category_id.clone(),
+                duplicate_of: duplicate_of.map(|id| id.to_string()),
+                ..Default::default()
+            }
+            .into_active_model()
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        for (id, name) in [(1, "Shōnen-ai"), (2, "Romance")] {
+            tags::ActiveModel {
+                id: Set(id),
+                name: Set(name.to_string()),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let corpus = SuggestCorpus::find(&db).await.unwrap();
+        let suggestions = corpus.suggest(&fold("shonen"), 5);
+        assert_eq!(
+            suggestions
+                .titles
+                .iter()
+                .map(|title| title.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Shonen", "Weekly Shōnen Tales"]
+        );
+        assert_eq!(suggestions.authors, vec!["Shounen Club"]);
+        assert_eq!(suggestions.tags.len(), 1);
+        assert_eq!(suggestions.tags[0].name, "Shōnen-ai");
+
+        // written together, with a typo, and without the duplicate
+        let suggestions = corpus.suggest(&fold("tsukiga kriei"), 5);
+        assert_eq!(suggestions.titles.len(), 1);
+        assert_eq!(suggestions.titles[0].title, "Tsuki ga Kirei");
+
+        let suggestions = corpus.suggest(&fold("ono yuki"), 1);
+        assert_eq!(suggestions.authors, vec!["Ōno Yūki"]);
+        assert!(suggestions.titles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cached_until_cleared() {
+        let db = memory_db().await;
+        let cache = SearchCache::default();
+        let tag_count = |corpus: &SuggestCorpus| corpus.suggest(&fold("romance"), 5).tags.len();
+
+        let corpus = cache.suggestions(&db).await.unwrap();
+        assert_eq!(tag_count(&corpus), 0);
+
+        tags::ActiveModel {
+            id: Set(1),
+            name: Set("Romance".to_string()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let corpus = cache.suggestions(&db).await.unwrap();
+        assert_eq!(tag_count(&corpus), 0);
+
+        cache.clear().await;
+        let corpus = cache.suggestions(&db).await.unwrap();
+        assert_eq!(tag_count(&corpus), 1);
+    }
+}
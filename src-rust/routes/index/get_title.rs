@@ -1,5 +1,6 @@
 use std::{path::PathBuf, sync::Arc};
 
+use super::{find_title_people, order_pages, TitlePersonResponse};
 use crate::{models::prelude::*, routes::calculate_dimension, AppError, AppState};
 
 use axum::{
@@ -20,6 +21,23 @@ pub struct ResponsePage {
     pub format: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Size in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    /// Fetch it with `?half=first` and `?half=second` to show it as two pages.
+    pub is_spread: bool,
+    /// Animated image or video, fetch it with `?transcode=true` to get an mp4.
+    pub is_animated: bool,
+    /// Fetch it with `?slice=`. Each slice is listed as a page of its own, and counts
+    /// as one for the progress.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slice: Option<usize>,
 }
 
 #[derive(Debug, Clone, ToSchema, Serialize, Deserialize, TS)]
@@ -29,6 +47,10 @@ pub struct ResponseCover {
     pub width: u32,
     pub height: u32,
     pub format: String,
+    /// What picked the cover of a title: `toml`, `custom`, a cover rule such as `stems`, or
+    /// `none`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
 }
 
 #[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
@@ -38,8 +60,16 @@ pub struct TitleResponseBody {
     pub category_id: String,
     pub title: String,
     pub author: Option<String>,
+    /// Credited from the TOML, the ComicInfo.xml or `author` once the analyzer has run.
+    pub people: Vec<TitlePersonResponse>,
     pub description: Option<String>,
     pub release_date: Option<String>,
+    /// Archive hash, pass it as `?v=` to the file routes to get immutable responses.
+    pub hash: String,
+    pub reading_direction: ReadingDirection,
+    pub long_strip: bool,
+    /// Set when the title is hidden as a copy of this one.
+    pub duplicate_of: Option<String>,
     pub cover: ResponseCover,
     pub tag_ids: Vec<u32>,
     pub pages: Vec<ResponsePage>,
@@ -95,26 +125,43 @@ pub async fn get_title(
         .map_err(|e| AppError::from(anyhow::anyhow!("Can't find pages: {}", e)))?;
 
     // place the cover.path at the front of the Vec<pages::Model>
-    // and convert it to Vec<ResponsePage>
-    let pages = pages
+    // and convert it to Vec<ResponsePage>, one per slice for long strips
+    let pages = order_pages(pages, Some(&cover.path))
         .into_iter()
-        .fold(Vec::new(), |mut list, page_model| {
-            if page_model.path == cover.path {
-                list.insert(0, page_model);
-            } else {
-                list.push(page_model);
+        .flat_map(|page| {
+            let response_page = ResponsePage {
+                id: page.id.to_string(),
+                format: PathBuf::from(&page.path)
+                    .extension()
+                    .map(|s| s.to_str().unwrap_or(""))
+                    .unwrap_or("")
+                    .to_ascii_lowercase(),
+                description: page.description.clone(),
+                width: page.width,
+                height: page.height,
+                size: page.size,
+                blurhash: page
+                    .blurhash
+                    .clone()
+                    .filter(|blurhash| !blurhash.is_empty()),
+                is_spread: page.is_spread,
+                is_animated: page.is_animated,
+                slice: None,
+            };
+
+            match title.long_strip {
+                true if !page.slices().is_empty() => page
+                    .slices()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, rows)| ResponsePage {
+                        height: Some(rows.end - rows.start),
+                        slice: Some(i),
+                        ..response_page.clone()
+                    })
+                    .collect::<Vec<_>>(),
+                _ => vec![response_page],
             }
-            list
-        })
-        .into_iter()
-        .map(|page| ResponsePage {
-            id: page.id.to_string(),
-            format: PathBuf::from(page.path)
-                .extension()
-                .map(|s| s.to_str().unwrap_or(""))
-                .unwrap_or("")
-                .to_ascii_lowercase(),
-            description: page.description,
         })
         .collect::<Vec<_>>();
 
@@ -180,6 +227,10 @@ pub async fn get_title(
         .map(|tag| tag.tag_id)
         .collect::<Vec<_>>();
 
+    let people = find_title_people(&app_state.db, &title.id)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find people: {}", e)))?;
+
     let (width, height) = calculate_dimension(&app_state.config, cover.ratio);
 
     Ok((
@@ -188,8 +239,13 @@ pub async fn get_title(
             category_id: title.category_id.to_string(),
             title: title.title,
             author: title.author,
+            people,
             description: title.description,
             release_date: title.release,
+            hash: title.hash,
+            reading_direction: title.reading_direction,
+            long_strip: title.long_strip,
+            duplicate_of: title.duplicate_of,
             cover: ResponseCover {
                 blurhash: cover.blurhash,
                 width,
@@ -199,6 +255,7 @@ pub async fn get_title(
                     .and_then(|s| s.to_str())
                     .unwrap_or("")
                     .to_ascii_lowercase(),
+                rule: cover.rule,
             },
             tag_ids,
             pages,
@@ -1,58 +1,160 @@
+mod fuzzy;
 mod get_categories;
+mod get_search;
+mod get_suggest;
 mod get_title;
+mod people;
 mod post_filter;
+mod query_syntax;
+mod saved_filters;
+
+use std::collections::HashMap;
 
 use crate::models::prelude::*;
 
-use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use serde::{de::DeserializeOwned, Serialize};
 
+pub use fuzzy::*;
 pub use get_categories::*;
+pub use get_search::*;
+pub use get_suggest::*;
 pub use get_title::*;
+pub use people::*;
 pub use post_filter::*;
+pub use query_syntax::*;
+pub use saved_filters::*;
 
-pub async fn find_page_count(db: &DatabaseConnection, title_id: &TitleID) -> i64 {
-    let pages = Pages::find()
-        .filter(pages::Column::TitleId.contains(title_id))
+/// Count the pages of titles as they are listed, with slices of long strips counted apart.
+///
+/// Titles without pages are left out.
+pub async fn find_page_counts(
+    db: &DatabaseConnection,
+    titles: &[titles::Model],
+) -> Result<HashMap<TitleID, i64>, DbErr> {
+    let mut counts = Pages::find()
+        .select_only()
+        .column(pages::Column::TitleId)
+        .column_as(pages::Column::Id.count(), "count")
+        .filter(pages::Column::TitleId.is_in(titles.iter().map(|title| &title.id)))
+        .group_by(pages::Column::TitleId)
+        .into_tuple::<(TitleID, i64)>()
         .all(db)
-        .await
-        .unwrap_or(vec![]);
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
 
-    match pages.is_empty() {
-        true => 0,
-        false => pages.len() as i64,
+    // only the few sliced pages of long strips count more than once
+    let sliced = Pages::find()
+        .filter(
+            pages::Column::TitleId.is_in(
+                titles
+                    .iter()
+                    .filter(|title| title.long_strip)
+                    .map(|title| &title.id),
+            ),
+        )
+        .filter(pages::Column::Slices.is_not_null())
+        .all(db)
+        .await?;
+    for page in sliced {
+        if let Some(count) = counts.get_mut(&page.title_id) {
+            *count += page.virtual_count(true) as i64 - 1;
+        }
     }
+
+    Ok(counts)
 }
 
-pub async fn find_page_read(
+/// The page each title was last read at by a user, titles never opened are left out.
+pub async fn find_pages_read(
     db: &DatabaseConnection,
-    title_id: &TitleID,
+    title_ids: &[&TitleID],
     user_id: &UserID,
-) -> Option<i64> {
-    let progresses = Progresses::find()
+) -> Result<HashMap<TitleID, i64>, DbErr> {
+    let pages_read = Progresses::find()
         .filter(
             Condition::all()
-                .add(progresses::Column::TitleId.eq(title_id))
+                .add(progresses::Column::TitleId.is_in(title_ids.iter().copied()))
                 .add(progresses::Column::UserId.eq(user_id)),
         )
-        .one(db)
-        .await
-        .unwrap_or_default();
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|progress| (progress.title_id, progress.page))
+        .collect();
 
-    match progresses {
-        Some(progress) => Some(progress.page),
-        None => None,
-    }
+    Ok(pages_read)
 }
 
-pub async fn find_favorite_count(db: &DatabaseConnection, title_id: &TitleID) -> Option<i64> {
-    let favorites = Favorites::find()
-        .filter(favorites::Column::TitleId.contains(title_id))
+/// How many users favorited each title, titles nobody favorited are left out.
+pub async fn find_favorite_counts(
+    db: &DatabaseConnection,
+    title_ids: &[&TitleID],
+) -> Result<HashMap<TitleID, i64>, DbErr> {
+    let counts = Favorites::find()
+        .select_only()
+        .column(favorites::Column::TitleId)
+        .column_as(favorites::Column::Id.count(), "count")
+        .filter(favorites::Column::TitleId.is_in(title_ids.iter().copied()))
+        .group_by(favorites::Column::TitleId)
+        .into_tuple::<(TitleID, i64)>()
         .all(db)
-        .await
-        .unwrap_or(vec![]);
+        .await?
+        .into_iter()
+        .collect();
 
-    match favorites.is_empty() {
-        true => None,
-        false => Some(favorites.len() as i64),
-    }
+    Ok(counts)
+}
+
+/// Find the pages of a title in reading order, with the cover page at the front.
+pub async fn find_ordered_pages(
+    db: &DatabaseConnection,
+    title_id: &TitleID,
+) -> Result<Vec<pages::Model>, DbErr> {
+    let cover_path = Covers::find_by_id(title_id)
+        .one(db)
+        .await?
+        .map(|cover| cover.path);
+
+    let pages = Pages::find()
+        .filter(pages::Column::TitleId.eq(title_id))
+        .order_by_asc(pages::Column::Path)
+        .all(db)
+        .await?;
+
+    Ok(order_pages(pages, cover_path.as_deref()))
+}
+
+/// Place the cover page at the front, keeping the order of the others.
+pub fn order_pages(pages: Vec<pages::Model>, cover_path: Option<&str>) -> Vec<pages::Model> {
+    pages.into_iter().fold(Vec::new(), |mut list, page_model| {
+        if Some(page_model.path.as_str()) == cover_path {
+            list.insert(0, page_model);
+        } else {
+            list.push(page_model);
+        }
+        list
+    })
+}
+
+/// Encode where a listing stopped as an opaque `next_cursor` for clients.
+pub fn encode_cursor(cursor: &impl Serialize) -> String {
+    serde_json::to_vec(cursor)
+        .unwrap_or_default()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Decode a `cursor` from `encode_cursor`, `None` when it was tampered with.
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Option<T> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    serde_json::from_slice(&bytes).ok()
 }
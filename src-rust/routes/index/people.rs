@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use super::{filter_response, Filter, FilterRequestBody, PersonFilter};
+use crate::{models::prelude::*, AppError, AppState};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TitlePersonResponse {
+    /// Fetch their details and other works from `/api/index/person/{id}`.
+    pub id: String,
+    pub name: String,
+    pub role: PersonRole,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PersonResponseBody {
+    pub id: String,
+    pub name: String,
+    /// Other spellings of the name.
+    pub aliases: Vec<String>,
+    /// Every role they're credited in, on any title.
+    pub roles: Vec<PersonRole>,
+    /// Number of titles crediting them, copies hidden as duplicates left out.
+    pub title_count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct PersonTitlesQuery {
+    /// Only the titles they're credited on in this role.
+    pub role: Option<PersonRole>,
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub facets: Option<bool>,
+}
+
+/// Get a person credited on titles, with their aliases and roles.
+#[utoipa::path(get, path = "/api/index/person/{person_id}", responses(
+    (status = 200, description = "Fetch person successful", body = PersonResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Person not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_person(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Path(person_id): Path<String>,
+) -> Result<Response, AppError> {
+    let person = match find_person(&app_state.db, person_id).await? {
+        Some(person) => person,
+        None => return Ok((StatusCode::NOT_FOUND, "Person not found.").into_response()),
+    };
+
+    let aliases = PersonAliases::find()
+        .filter(person_aliases::Column::PersonId.eq(&person.id))
+        .order_by_asc(person_aliases::Column::Name)
+        .all(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find person aliases: {}", e)))?
+        .into_iter()
+        .map(|alias| alias.name)
+        .collect();
+
+    let mut roles = TitlePeople::find()
+        .select_only()
+        .column(title_people::Column::Role)
+        .distinct()
+        .filter(title_people::Column::PersonId.eq(&person.id))
+        .into_tuple::<PersonRole>()
+        .all(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find roles: {}", e)))?;
+    roles.sort();
+
+    let works = Filter::Person(PersonFilter {
+        id: person.id.to_string(),
+        role: None,
+    });
+    let title_count = Titles::find()
+        .filter(works.condition(&user.id))
+        .filter(titles::Column::DuplicateOf.is_null())
+        .count(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't count titles: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PersonResponseBody {
+            id: person.id.to_string(),
+            name: person.name,
+            aliases,
+            roles,
+            title_count,
+        }),
+    )
+        .into_response())
+}
+
+/// List the titles a person is credited on, as `/api/index/filter` does with a `person`
+/// filter, e.g. for their other works.
+#[utoipa::path(get, path = "/api/index/person/{person_id}/titles", params(PersonTitlesQuery), responses(
+    (status = 200, description = "Fetch all items successful", body = FilterResponseBody),
+    (status = 204, description = "Fetch all items successful, but none were found", body = FilterResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Person not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_person_titles(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Path(person_id): Path<String>,
+    Query(query): Query<PersonTitlesQuery>,
+) -> Result<Response, AppError> {
+    let person = match find_person(&app_state.db, person_id).await? {
+        Some(person) => person,
+        None => return Ok((StatusCode::NOT_FOUND, "Person not found.").into_response()),
+    };
+
+    let filter = FilterRequestBody {
+        filter: Some(Filter::Person(PersonFilter {
+            id: person.id.to_string(),
+            role: query.role,
+        })),
+        limit: query.limit,
+        cursor: query.cursor,
+        facets: query.facets,
+        ..Default::default()
+    };
+
+    filter_response(&app_state, &filter, &user.id).await
+}
+
+async fn find_person(
+    db: &DatabaseConnection,
+    person_id: String,
+) -> Result<Option<people::Model>, AppError> {
+    let Ok(person_id) = PersonID::from(person_id) else {
+        return Ok(None);
+    };
+
+    People::find_by_id(person_id)
+        .one(db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find person: {}", e)))
+}
+
+/// The people credited on a title, in the order they were credited.
+pub async fn find_title_people(
+    db: &DatabaseConnection,
+    title_id: &TitleID,
+) -> Result<Vec<TitlePersonResponse>, DbErr> {
+    Ok(TitlePeople::find()
+        .find_also_related(People)
+        .filter(title_people::Column::TitleId.eq(title_id))
+        .order_by_asc(title_people::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(credit, person)| {
+            let person = person?;
+            Some(TitlePersonResponse {
+                id: person.id.to_string(),
+                name: person.name,
+                role: credit.role,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests_people {
+    use super::*;
+    use crate::models::fixtures::{insert_category, memory_db};
+    use sea_orm::{ActiveModelTrait, Condition, IntoActiveModel, Set};
+
+    async fn credit(
+        db: &DatabaseConnection,
+        title_id: &TitleID,
+        person_id: &PersonID,
+        role: PersonRole,
+    ) {
+        title_people::ActiveModel {
+            title_id: Set(title_id.clone()),
+            person_id: Set(person_id.clone()),
+            role: Set(role),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn matching(db: &DatabaseConnection, filter: Filter) -> Vec<String> {
+        let mut titles = Titles::find()
+            .filter(Condition::all().add(filter.condition(&CustomID::new())))
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|title| title.title)
+            .collect::<Vec<_>>();
+        titles.sort();
+        titles
+    }
+
+    #[tokio::test]
+    async fn credited() {
+        let db = &memory_db().await;
+        let category_id = insert_category(db, "Manga").await;
+        let mut title_ids = vec![];
+        for (name, author) in [
+            ("Alpha", Some("Oda, Someone")),
+            ("Beta", None),
+            ("Gamma", None),
+        ] {
+            let title = titles::Model {
+                id: CustomID::new(),
+                title: name.to_string(),
+                author: author.map(str::to_string),
+                category_id: category_id.clone(),
+                ..Default::default()
+            };
+            title.clone().into_active_model().insert(db).await.unwrap();
+            title_ids.push(title.id);
+        }
+
+        let oda = PersonID::new();
+        let translator = PersonID::new();
+        for (id, name) in [(&oda, "Oda Eiichirō"), (&translator, "Tom")] {
+            people::ActiveModel {
+                id: Set(id.clone()),
+                name: Set(name.to_string()),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        person_aliases::ActiveModel {
+            person_id: Set(oda.clone()),
+            name: Set("Eiichiro Oda".to_string()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        credit(db, &title_ids[1], &oda, PersonRole::Writer).await;
+        credit(db, &title_ids[1], &oda, PersonRole::Artist).await;
+        credit(db, &title_ids[1], &translator, PersonRole::Translator).await;
+        credit(db, &title_ids[2], &oda, PersonRole::Artist).await;
+
+        let person = |role| {
+            Filter::Person(PersonFilter {
+                id: oda.to_string(),
+                role,
+            })
+        };
+        assert_eq!(matching(db, person(None)).await, vec!["Beta", "Gamma"]);
+        assert_eq!(
+            matching(db, person(Some(PersonRole::Writer))).await,
+            vec!["Beta"]
+        );
+        // the free text author, the names of people, and their aliases
+        assert_eq!(
+            matching(db, Filter::Author("oda".to_string())).await,
+            vec!["Alpha", "Beta", "Gamma"]
+        );
+        assert_eq!(
+            matching(db, Filter::Author("Eiichiro".to_string())).await,
+            vec!["Beta", "Gamma"]
+        );
+        assert_eq!(
+            matching(db, Filter::Author("tom".to_string())).await,
+            vec!["Beta"]
+        );
+
+        assert_eq!(
+            find_title_people(db, &title_ids[1]).await.unwrap(),
+            vec![
+                TitlePersonResponse {
+                    id: oda.to_string(),
+                    name: "Oda Eiichirō".to_string(),
+                    role: PersonRole::Writer,
+                },
+                TitlePersonResponse {
+                    id: oda.to_string(),
+                    name: "Oda Eiichirō".to_string(),
+                    role: PersonRole::Artist,
+                },
+                TitlePersonResponse {
+                    id: translator.to_string(),
+                    name: "Tom".to_string(),
+                    role: PersonRole::Translator,
+                },
+            ]
+        );
+        assert!(find_title_people(db, &title_ids[0])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,40 +1,377 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use super::{find_favorite_count, find_page_count, find_page_read};
+use super::{
+    decode_cursor, encode_cursor, find_favorite_counts, find_page_counts, find_pages_read,
+    find_query_names, matches_keyword, parse_query,
+};
 use crate::{models::prelude::*, routes::calculate_dimension, AppError, AppState};
 
 use axum::{
-    extract::State,
+    extract::{rejection::JsonRejection, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use sea_orm::{
-    ColumnTrait, Condition, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+    sea_query::{
+        Alias, Expr, Func, LikeExpr, Query, SelectStatement, SimpleExpr, SubQueryStatement,
+        TableRef,
+    },
+    ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, JoinType, Order,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait, Select, Value,
 };
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use utoipa::ToSchema;
 
-#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+/// Used when `limit` isn't given.
+const DEFAULT_FILTER_LIMIT: u32 = 100;
+
+/// `limit` can't be more than this.
+const MAX_FILTER_LIMIT: u32 = 500;
+
+/// How many title IDs are bound in one query when listing titles.
+const TITLE_IDS_CHUNK: usize = 500;
+
+/// Each field narrows the results down further, fields left out don't filter anything.
+#[derive(Debug, Clone, Default, ToSchema, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct FilterRequestBody {
-    /// Keywords to search for (search in title, description, author, tags)
+    /// Keywords to search for (search in title, description, author, tags, page descriptions),
+    /// all of them have to match
     pub keywords: Option<Vec<String>>,
-    /// Categories to filter by
+    /// Titles in any of these categories, no filter when empty
     pub category_ids: Option<Vec<String>>,
-    /// Tags to filter by
-    pub tag_ids: Option<Vec<i32>>,
-    /// Maximum number of results to return
+    /// Titles in none of these categories
+    pub exclude_category_ids: Option<Vec<String>>,
+    /// Titles with all of these tags
+    pub tag_ids: Option<Vec<u32>>,
+    /// Titles with none of these tags
+    pub exclude_tag_ids: Option<Vec<u32>>,
+    /// Anything the fields above can't express
+    pub filter: Option<Filter>,
+    /// The same as `filter`, typed in a search box, e.g.
+    /// `tag:romance -tag:horror author:"Oda" year:>2010 status:reading pages:<50`.
+    /// Check it with `/api/index/query/check` while it's typed.
+    pub query: Option<String>,
+    /// Maximum number of results to return, 100 by default and 500 at most
     pub limit: Option<u32>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// `true` to also count the results by tag, category, author and release year
+    pub facets: Option<bool>,
 
+    /// `true` for titles the user is reading, `false` is the same as leaving it out. Use
+    /// `not` in `filter` to leave titles out.
     pub is_reading: Option<bool>,
     pub is_finished: Option<bool>,
     pub is_bookmarked: Option<bool>,
     pub is_favorite: Option<bool>,
 
-    pub sort_by: Option<String>,
-    pub sort_order: Option<String>,
+    pub sort_by: Option<SortBy>,
+    pub sort_order: Option<SortOrder>,
+    /// Seed of the `random` sort, the same seed lists titles in the same order. A new one is
+    /// picked when left out, pages after the first keep the seed of their cursor.
+    pub seed: Option<u32>,
+}
+
+/// What titles are listed by, `alphabetical` when left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum SortBy {
+    #[default]
+    #[serde(rename = "alphabetical")]
+    Alphabetical,
+    #[serde(rename = "add date")]
+    AddDate,
+    /// Titles without a release date come first.
+    #[serde(rename = "release date")]
+    ReleaseDate,
+    #[serde(rename = "update date")]
+    UpdateDate,
+    /// When the current user last read the title, titles never opened come first.
+    #[serde(rename = "last read")]
+    LastRead,
+    #[serde(rename = "favorite count")]
+    FavoriteCount,
+    #[serde(rename = "bookmark count")]
+    BookmarkCount,
+    /// Slices of long strips are counted apart, like in `page_count`.
+    #[serde(rename = "page count")]
+    PageCount,
+    /// Shuffled with `seed`.
+    #[serde(rename = "random")]
+    Random,
+}
+
+impl SortBy {
+    /// Name of the sort in cursors.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Alphabetical => "title",
+            Self::AddDate => "date_added",
+            Self::ReleaseDate => "release",
+            Self::UpdateDate => "date_updated",
+            Self::LastRead => "last_read_at",
+            Self::FavoriteCount => "favorite_count",
+            Self::BookmarkCount => "bookmark_count",
+            Self::PageCount => "page_count",
+            Self::Random => "random",
+        }
+    }
+
+    /// Whether titles are sorted by a number rather than by text.
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::FavoriteCount | Self::BookmarkCount | Self::PageCount | Self::Random
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl FilterRequestBody {
+    /// All the filters of the request, as one.
+    pub fn to_filter(&self) -> Filter {
+        let mut filters = vec![];
+
+        for keyword in self.keywords.iter().flatten() {
+            filters.push(Filter::Keyword(keyword.clone()));
+        }
+        if let Some(category_ids) = self.category_ids.as_ref().filter(|ids| !ids.is_empty()) {
+            filters.push(Filter::Any(
+                category_ids.iter().cloned().map(Filter::Category).collect(),
+            ));
+        }
+        if let Some(category_ids) = self
+            .exclude_category_ids
+            .as_ref()
+            .filter(|ids| !ids.is_empty())
+        {
+            filters.push(Filter::Not(Box::new(Filter::Any(
+                category_ids.iter().cloned().map(Filter::Category).collect(),
+            ))));
+        }
+        for tag_id in self.tag_ids.iter().flatten() {
+            filters.push(Filter::Tag(*tag_id));
+        }
+        if let Some(tag_ids) = self.exclude_tag_ids.as_ref().filter(|ids| !ids.is_empty()) {
+            filters.push(Filter::Not(Box::new(Filter::Any(
+                tag_ids.iter().copied().map(Filter::Tag).collect(),
+            ))));
+        }
+
+        for (flag, filter) in [
+            (self.is_reading, Filter::Reading),
+            (self.is_finished, Filter::Finished),
+            (self.is_bookmarked, Filter::Bookmarked),
+            (self.is_favorite, Filter::Favorite),
+        ] {
+            if flag == Some(true) {
+                filters.push(filter);
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            filters.push(filter.clone());
+        }
+        Filter::All(filters)
+    }
+}
+
+/// A condition on titles, nested with `all`, `any` and `not`, e.g.
+/// `{ "all": [{ "tag": 1 }, { "not": { "any": [{ "category": "<id>" }, "finished"] } }] }`.
+///
+/// `reading`, `finished`, `bookmarked` and `favorite` are about the current user.
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Filter {
+    /// Every filter matches, always true when empty.
+    All(Vec<Filter>),
+    /// At least one filter matches, always false when empty.
+    Any(Vec<Filter>),
+    Not(Box<Filter>),
+    /// Full-text search, every word has to match the start of a word or be found anywhere
+    /// inside the text.
+    Keyword(String),
+    Category(String),
+    Tag(u32),
+    Reading,
+    Finished,
+    Bookmarked,
+    Favorite,
+    /// Author containing the text, ignoring case, or a credited person with a name or alias
+    /// containing it.
+    Author(String),
+    Person(PersonFilter),
+    /// Year at the start of the release date, titles without one never match.
+    ReleaseYear(NumberRange),
+    /// Number of pages, with slices of long strips counted apart.
+    PageCount(NumberRange),
+}
+
+/// Titles crediting the person, in `role` when given.
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PersonFilter {
+    pub id: String,
+    pub role: Option<PersonRole>,
+}
+
+/// Numbers from `min` to `max`, both included, either left out for no bound.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct NumberRange {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+impl NumberRange {
+    /// The condition that `expr` is in the range.
+    fn condition(&self, expr: SimpleExpr) -> Condition {
+        Condition::all()
+            .add_option(self.min.map(|min| Expr::expr(expr.clone()).gte(min)))
+            .add_option(self.max.map(|max| Expr::expr(expr.clone()).lte(max)))
+    }
+}
+
+impl Filter {
+    /// The condition on `titles` for `user_id`.
+    pub fn condition(&self, user_id: &UserID) -> Condition {
+        match self {
+            Self::All(filters) => filters.iter().fold(Condition::all(), |all, filter| {
+                all.add(filter.condition(user_id))
+            }),
+            Self::Any(filters) => filters.iter().fold(Condition::any(), |any, filter| {
+                any.add(filter.condition(user_id))
+            }),
+            Self::Not(filter) => filter.condition(user_id).not(),
+            // nothing to search for, like an empty search box
+            Self::Keyword(keyword) => matches_keyword(keyword).unwrap_or_else(Condition::all),
+            Self::Category(category_id) => {
+                Condition::all().add(titles::Column::CategoryId.eq(category_id))
+            }
+            Self::Tag(tag_id) => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(titles_tags::Column::TitleId)
+                        .from(TitlesTags)
+                        .and_where(titles_tags::Column::TagId.eq(*tag_id))
+                        .to_owned(),
+                ),
+            ),
+            Self::Reading => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(progresses::Column::TitleId)
+                        .from(Progresses)
+                        .and_where(progresses::Column::UserId.eq(user_id))
+                        .and_where(progresses::Column::Page.gt(0))
+                        .to_owned(),
+                ),
+            ),
+            Self::Finished => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(progresses::Column::TitleId)
+                        .from(Progresses)
+                        .and_where(progresses::Column::UserId.eq(user_id))
+                        .and_where(progresses::Column::Page.eq(0))
+                        .to_owned(),
+                ),
+            ),
+            Self::Bookmarked => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(bookmarks::Column::TitleId)
+                        .from(Bookmarks)
+                        .and_where(bookmarks::Column::UserId.eq(user_id))
+                        .to_owned(),
+                ),
+            ),
+            Self::Favorite => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(favorites::Column::TitleId)
+                        .from(Favorites)
+                        .and_where(favorites::Column::UserId.eq(user_id))
+                        .to_owned(),
+                ),
+            ),
+            Self::Author(author) => {
+                let like = || LikeExpr::new(format!("%{}%", escape_like(author))).escape('\\');
+                let people = Condition::any()
+                    .add(
+                        title_people::Column::PersonId.in_subquery(
+                            Query::select()
+                                .column(people::Column::Id)
+                                .from(People)
+                                .and_where(Expr::col((People, people::Column::Name)).like(like()))
+                                .to_owned(),
+                        ),
+                    )
+                    .add(
+                        title_people::Column::PersonId.in_subquery(
+                            Query::select()
+                                .column(person_aliases::Column::PersonId)
+                                .from(PersonAliases)
+                                .and_where(
+                                    Expr::col((PersonAliases, person_aliases::Column::Name))
+                                        .like(like()),
+                                )
+                                .to_owned(),
+                        ),
+                    );
+                Condition::any()
+                    .add(Expr::col((Titles, titles::Column::Author)).like(like()))
+                    .add(
+                        titles::Column::Id.in_subquery(
+                            Query::select()
+                                .column(title_people::Column::TitleId)
+                                .from(TitlePeople)
+                                .cond_where(people)
+                                .to_owned(),
+                        ),
+                    )
+            }
+            Self::Person(person) => Condition::all().add(
+                titles::Column::Id.in_subquery(
+                    Query::select()
+                        .column(title_people::Column::TitleId)
+                        .from(TitlePeople)
+                        .and_where(title_people::Column::PersonId.eq(&person.id))
+                        .and_where_option(
+                            person.role.map(|role| title_people::Column::Role.eq(role)),
+                        )
+                        .to_owned(),
+                ),
+            ),
+            Self::ReleaseYear(range) => {
+                let year = Expr::cust(r#"CAST(SUBSTR("titles"."release", 1, 4) AS INTEGER)"#);
+                range
+                    .condition(year)
+                    .add(titles::Column::Release.is_not_null())
+            }
+            Self::PageCount(range) => range.condition(page_count_expr()),
+        }
+    }
+}
+
+/// `text` matched as is in a `LIKE` pattern escaped with `\`.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 #[derive(Debug, Clone, ToSchema, Serialize, Deserialize, TS)]
@@ -46,6 +383,8 @@ pub struct FilterTitleResponseBody {
     pub author: Option<String>,
     pub category_id: String,
     pub release: Option<String>,
+    /// Archive hash, pass it as `?v=` to the file routes to get immutable responses.
+    pub hash: String,
     pub favorite_count: Option<i64>,
     pub page_count: i64,
     pub page_read: Option<i64>,
@@ -61,173 +400,1579 @@ pub struct FilterTitleResponseBody {
 #[ts(export)]
 pub struct FilterResponseBody {
     pub data: Vec<FilterTitleResponseBody>,
+    /// Number of titles matching the filters, on all pages.
+    pub total: u64,
+    /// Pass it as `cursor` with the same filters and sort to get the next page, `None` on the
+    /// last one.
+    pub next_cursor: Option<String>,
+    /// Counts of the titles matching the filters, on all pages, when asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<FilterFacets>,
+}
+
+/// How many of the matching titles have each value, the most common first. Values no title
+/// has are left out.
+#[derive(Debug, Clone, Default, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FilterFacets {
+    pub tags: Vec<TagFacet>,
+    pub categories: Vec<CategoryFacet>,
+    pub authors: Vec<ValueFacet>,
+    /// Newest first rather than the most common.
+    pub release_years: Vec<ValueFacet>,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TagFacet {
+    pub id: u32,
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CategoryFacet {
+    pub id: String,
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ValueFacet {
+    pub value: String,
+    pub count: i64,
 }
 
 /// Filtering titles by various parameters.
 ///
-/// And also sorting them by various options.
-#[utoipa::path(post, path = "api/index/filter", responses(
+/// And also sorting them by various options. Without any filter, every title is listed.
+#[utoipa::path(post, path = "api/index/filter", request_body = FilterRequestBody, responses(
     (status = 200, description = "Fetch all items successful", body = FilterResponseBody),
     (status = 204, description = "Fetch all items successful, but none were found", body = FilterResponseBody),
+    (status = 400, description = "Bad request", body = String),
     (status = 401, description = "Unauthorized", body = String),
     (status = 500, description = "Internal server error", body = String)
 ))]
 pub async fn post_filter(
     State(app_state): State<Arc<AppState>>,
     Extension(user): Extension<users::Model>,
-    Json(query): Json<FilterRequestBody>,
+    query: Result<Json<FilterRequestBody>, JsonRejection>,
 ) -> Result<Response, AppError> {
-    let keywords = query.keywords;
-    let category_ids = query.category_ids;
-    let tag_ids = query.tag_ids;
-    let limit = query.limit;
+    // unknown sorts are rejected here
+    let query = match query {
+        Ok(Json(query)) => query,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.body_text()).into_response()),
+    };
+
+    filter_response(&app_state, &query, &user.id).await
+}
+
+/// List the titles asked for by `query`, as `post_filter` does.
+pub async fn filter_response(
+    app_state: &AppState,
+    query: &FilterRequestBody,
+    user_id: &UserID,
+) -> Result<Response, AppError> {
+    // the typed query is resolved once, then filters like the other fields
+    let query = match &query.query {
+        Some(text) => {
+            let names = find_query_names(&app_state.db).await.map_err(|e| {
+                AppError::from(anyhow::anyhow!("Can't find tags and categories: {}", e))
+            })?;
+            let parsed = match parse_query(text, &names) {
+                Ok(filter) => filter,
+                Err(errors) => {
+                    let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    let message = format!("Invalid query: {}.", errors.join(", "));
+                    return Ok((StatusCode::BAD_REQUEST, message).into_response());
+                }
+            };
+            FilterRequestBody {
+                query: None,
+                filter: Some(match &query.filter {
+                    Some(filter) => Filter::All(vec![filter.clone(), parsed]),
+                    None => parsed,
+                }),
+                ..query.clone()
+            }
+        }
+        None => query.clone(),
+    };
+    let query = &query;
+
+    let cursor = match parse_cursor(query) {
+        Ok(cursor) => cursor,
+        Err(_) => return Ok((StatusCode::BAD_REQUEST, "Invalid cursor.").into_response()),
+    };
+
+    let (title_models, total, next_cursor) =
+        find_titles_page(&app_state.db, query, user_id, cursor.as_ref())
+            .await
+            .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?;
+
+    let facets = match query.facets {
+        Some(true) => Some(
+            find_facets(&app_state.db, query, user_id)
+                .await
+                .map_err(|e| AppError::from(anyhow::anyhow!("Can't count facets: {}", e)))?,
+        ),
+        _ => None,
+    };
+
+    let resp_data = match find_title_responses(app_state, title_models, user_id)
+        .await?
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(resp_data) => resp_data,
+        None => return Ok((StatusCode::NO_CONTENT, "No cover found").into_response()),
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(FilterResponseBody {
+            data: resp_data,
+            total,
+            next_cursor,
+            facets,
+        }),
+    )
+        .into_response())
+}
 
-    if keywords.is_none() && category_ids.is_none() && tag_ids.is_none() {
-        return Ok((StatusCode::NO_CONTENT, "No title found").into_response());
+/// Where a page of titles stopped, in the sort it was listed with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FilterCursor {
+    sort_by: String,
+    descending: bool,
+    /// Seed of the `random` sort.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    /// Sort value of the last title of the page.
+    value: String,
+    id: String,
+}
+
+/// The sort of `query` and its order.
+fn sort_of(query: &FilterRequestBody) -> (SortBy, Order) {
+    let sort_order = match query.sort_order.unwrap_or_default() {
+        SortOrder::Ascending => Order::Asc,
+        SortOrder::Descending => Order::Desc,
+    };
+
+    (query.sort_by.unwrap_or_default(), sort_order)
+}
+
+/// The seed of the `random` sort, from `query`, then `cursor`, or a new one.
+fn seed_of(query: &FilterRequestBody, cursor: Option<&FilterCursor>) -> u32 {
+    query
+        .seed
+        .or(cursor.and_then(|cursor| cursor.seed))
+        .unwrap_or_else(rand::random)
+}
+
+/// What titles are sorted by, as a value of the `titles` row.
+fn sort_expr(sort_by: SortBy, user_id: &UserID, seed: u32) -> SimpleExpr {
+    match sort_by {
+        SortBy::Alphabetical => Expr::col((Titles, titles::Column::Title)).into(),
+        SortBy::AddDate => Expr::col((Titles, titles::Column::DateAdded)).into(),
+        SortBy::ReleaseDate => Func::coalesce([
+            Expr::col((Titles, titles::Column::Release)).into(),
+            Expr::val("").into(),
+        ])
+        .into(),
+        SortBy::UpdateDate => Expr::col((Titles, titles::Column::DateUpdated)).into(),
+        SortBy::LastRead => Func::coalesce([
+            of_title(
+                Query::select()
+                    .column(progresses::Column::LastReadAt)
+                    .and_where(progresses::Column::UserId.eq(user_id)),
+                progresses::Column::TitleId,
+            ),
+            Expr::val("").into(),
+        ])
+        .into(),
+        SortBy::FavoriteCount => of_title(
+            Query::select().expr(Expr::cust("COUNT(*)")),
+            favorites::Column::TitleId,
+        ),
+        SortBy::BookmarkCount => of_title(
+            Query::select().expr(Expr::cust("COUNT(*)")),
+            bookmarks::Column::TitleId,
+        ),
+        SortBy::PageCount => page_count_expr(),
+        SortBy::Random => {
+            // a permutation of the rows, picked by the seed
+            let mut rng = StdRng::seed_from_u64(seed as u64);
+            Expr::cust(format!(
+                r#"("titles".rowid * {} + {}) % {}"#,
+                rng.gen_range(1..RANDOM_MODULUS),
+                rng.gen_range(0..RANDOM_MODULUS),
+                RANDOM_MODULUS
+            ))
+        }
     }
+}
+
+/// The number of pages of the title, a sliced page counts as its slices in long strips like
+/// `pages::Model::virtual_count`.
+fn page_count_expr() -> SimpleExpr {
+    of_title(
+        Query::select().expr(Func::coalesce([
+            Expr::cust(
+                r#"SUM(CASE WHEN "titles"."long_strip" AND "pages"."slices" IS NOT NULL
+                THEN MAX(1, LENGTH("pages"."slices") - LENGTH(REPLACE("pages"."slices", ',', '')))
+                ELSE 1 END)"#,
+            ),
+            Expr::val(0).into(),
+        ])),
+        pages::Column::TitleId,
+    )
+}
+
+/// `query` on the rows of the table of `title_id` that belong to the title being sorted.
+fn of_title(query: &mut SelectStatement, title_id: impl ColumnTrait) -> SimpleExpr {
+    let query = query
+        .from(TableRef::Table(title_id.entity_name()))
+        .and_where(Expr::col(title_id.as_column_ref()).equals((Titles, titles::Column::Id)))
+        .to_owned();
+    SimpleExpr::SubQuery(None, Box::new(SubQueryStatement::SelectStatement(query)))
+}
 
-    let mut condition = Condition::any();
+/// Prime the keys of the `random` sort are taken modulo of.
+const RANDOM_MODULUS: i64 = 2147483647;
 
-    if let Some(category_ids) = category_ids {
-        for category_id in category_ids {
-            condition = condition.add(titles::Column::CategoryId.eq(category_id));
+/// The cursor of `query`, `Err` when it doesn't come from a listing in the same sort.
+fn parse_cursor(query: &FilterRequestBody) -> Result<Option<FilterCursor>, ()> {
+    let Some(cursor) = &query.cursor else {
+        return Ok(None);
+    };
+    let (sort_by, sort_order) = sort_of(query);
+    match decode_cursor::<FilterCursor>(cursor) {
+        Some(cursor)
+            if cursor.sort_by == sort_by.as_str()
+                && cursor.descending == matches!(sort_order, Order::Desc)
+                && (sort_by != SortBy::Random
+                    || query.seed.is_none()
+                    || cursor.seed == query.seed)
+                && (!sort_by.is_numeric() || cursor.value.parse::<i64>().is_ok()) =>
+        {
+            Ok(Some(cursor))
         }
+        _ => Err(()),
     }
+}
+
+/// The titles asked for by `query`, with copies of other titles left out.
+fn find_filtered(query: &FilterRequestBody, user_id: &UserID) -> Select<Titles> {
+    Titles::find()
+        .filter(query.to_filter().condition(user_id))
+        .filter(titles::Column::DuplicateOf.is_null())
+}
+
+/// A page of the titles asked for by `query` after `cursor`, along with the total number of
+/// titles and the cursor of the next page, if there is one.
+///
+/// Titles are sorted by their ID after the sort column, so pages never skip or repeat titles
+/// with the same value.
+async fn find_titles_page(
+    db: &DatabaseConnection,
+    query: &FilterRequestBody,
+    user_id: &UserID,
+    cursor: Option<&FilterCursor>,
+) -> Result<(Vec<titles::Model>, u64, Option<String>), DbErr> {
+    let filtered = find_filtered(query, user_id);
+    let total = filtered.clone().count(db).await?;
+
+    let (sort_by, sort_order) = sort_of(query);
+    let seed = seed_of(query, cursor);
+    let key = sort_expr(sort_by, user_id, seed);
+    let after_cursor = cursor.map(|cursor| {
+        let value = match sort_by.is_numeric() {
+            true => Value::from(cursor.value.parse::<i64>().unwrap_or_default()),
+            false => Value::from(cursor.value.as_str()),
+        };
+        let (after, same) = match sort_order {
+            Order::Desc => (
+                Expr::expr(key.clone()).lt(value.clone()),
+                titles::Column::Id.lt(cursor.id.as_str()),
+            ),
+            _ => (
+                Expr::expr(key.clone()).gt(value.clone()),
+                titles::Column::Id.gt(cursor.id.as_str()),
+            ),
+        };
+        Condition::any().add(after).add(
+            Condition::all()
+                .add(Expr::expr(key.clone()).eq(value))
+                .add(same),
+        )
+    });
+
+    // one more to know if there's a next page
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_FILTER_LIMIT)
+        .min(MAX_FILTER_LIMIT) as usize;
+    let mut title_models = filtered
+        .apply_if(after_cursor, QueryFilter::filter)
+        .order_by(key.clone(), sort_order.clone())
+        .order_by(titles::Column::Id, sort_order.clone())
+        .limit(limit as u64 + 1)
+        .all(db)
+        .await?;
 
-    if let Some(keywords) = keywords {
-        for keyword in keywords {
-            condition = condition
-                .add(titles::Column::Title.contains(keyword.to_lowercase()))
-                .add(titles::Column::Author.contains(keyword.to_lowercase()))
-                .add(titles::Column::Description.contains(keyword.to_lowercase()));
+    let last = if title_models.len() > limit {
+        title_models.truncate(limit);
+        title_models.last()
+    } else {
+        None
+    };
+    let next_cursor = match last {
+        Some(title) => {
+            // the sort value can be about other tables, it's read back as it was sorted with
+            let value = Titles::find_by_id(title.id.clone())
+                .select_only()
+                .column_as(key.cast_as(Alias::new("TEXT")), "value")
+                .into_tuple::<String>()
+                .one(db)
+                .await?
+                .unwrap_or_default();
+            Some(encode_cursor(&FilterCursor {
+                sort_by: sort_by.as_str().to_string(),
+                descending: matches!(sort_order, Order::Desc),
+                seed: (sort_by == SortBy::Random).then_some(seed),
+                value,
+                id: title.id.to_string(),
+            }))
         }
+        None => None,
+    };
+
+    Ok((title_models, total, next_cursor))
+}
+
+/// Count the titles asked for by `query` by tag, category, author and release year.
+///
+/// Each count is a single grouped query over the IDs of the matching titles.
+async fn find_facets(
+    db: &DatabaseConnection,
+    query: &FilterRequestBody,
+    user_id: &UserID,
+) -> Result<FilterFacets, DbErr> {
+    let title_ids = find_filtered(query, user_id)
+        .select_only()
+        .column(titles::Column::Id)
+        .into_query();
+    let count = Expr::cust("COUNT(*)");
+
+    let tags = TitlesTags::find()
+        .select_only()
+        .column(tags::Column::Id)
+        .column(tags::Column::Name)
+        .column_as(count.clone(), "count")
+        .join(JoinType::InnerJoin, titles_tags::Relation::Tags.def())
+        .filter(titles_tags::Column::TitleId.in_subquery(title_ids.clone()))
+        .group_by(tags::Column::Id)
+        .order_by_desc(count.clone())
+        .order_by_asc(tags::Column::Name)
+        .into_tuple::<(u32, String, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(id, name, count)| TagFacet { id, name, count })
+        .collect();
+
+    let categories = Titles::find()
+        .select_only()
+        .column(categories::Column::Id)
+        .column(categories::Column::Name)
+        .column_as(count.clone(), "count")
+        .join(JoinType::InnerJoin, titles::Relation::Categories.def())
+        .filter(titles::Column::Id.in_subquery(title_ids.clone()))
+        .group_by(categories::Column::Id)
+        .order_by_desc(count.clone())
+        .order_by_asc(categories::Column::Name)
+        .into_tuple::<(String, String, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(id, name, count)| CategoryFacet { id, name, count })
+        .collect();
+
+    let authors = Titles::find()
+        .select_only()
+        .column(titles::Column::Author)
+        .column_as(count.clone(), "count")
+        .filter(titles::Column::Id.in_subquery(title_ids.clone()))
+        .filter(titles::Column::Author.is_not_null())
+        .group_by(titles::Column::Author)
+        .order_by_desc(count.clone())
+        .order_by_asc(titles::Column::Author)
+        .into_tuple::<(String, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(value, count)| ValueFacet { value, count })
+        .collect();
+
+    // releases start with the year, e.g. `2024` or `2024-07-01`
+    let year = SimpleExpr::from(
+        Func::cust(Alias::new("SUBSTR"))
+            .arg(Expr::col(titles::Column::Release))
+            .arg(1)
+            .arg(4),
+    );
+    let release_years = Titles::find()
+        .select_only()
+        .column_as(year.clone(), "year")
+        .column_as(count, "count")
+        .filter(titles::Column::Id.in_subquery(title_ids))
+        .filter(titles::Column::Release.is_not_null())
+        .group_by(year.clone())
+        .order_by_desc(year)
+        .into_tuple::<(String, i64)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(value, count)| ValueFacet { value, count })
+        .collect();
+
+    Ok(FilterFacets {
+        tags,
+        categories,
+        authors,
+        release_years,
+    })
+}
+
+/// Build the listings of titles in the same order, `None` for titles without a cover yet.
+///
+/// Counts, progresses and covers are fetched for many titles at once, so listing more titles
+/// doesn't take more queries. The IDs are bound in chunks to stay under SQLite's limit of
+/// variables in a query.
+pub async fn find_title_responses(
+    app_state: &AppState,
+    titles: Vec<titles::Model>,
+    user_id: &UserID,
+) -> Result<Vec<Option<FilterTitleResponseBody>>, AppError> {
+    let mut page_counts = HashMap::new();
+    let mut favorite_counts = HashMap::new();
+    let mut pages_read = HashMap::new();
+    let mut cover_models = HashMap::new();
+    for chunk in titles.chunks(TITLE_IDS_CHUNK) {
+        let title_ids = chunk.iter().map(|title| &title.id).collect::<Vec<_>>();
+        page_counts.extend(
+            find_page_counts(&app_state.db, chunk)
+                .await
+                .map_err(|e| AppError::from(anyhow::anyhow!("Can't count pages: {}", e)))?,
+        );
+        favorite_counts.extend(
+            find_favorite_counts(&app_state.db, &title_ids)
+                .await
+                .map_err(|e| AppError::from(anyhow::anyhow!("Can't count favorites: {}", e)))?,
+        );
+        pages_read.extend(
+            find_pages_read(&app_state.db, &title_ids, user_id)
+                .await
+                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find progresses: {}", e)))?,
+        );
+        cover_models.extend(
+            Covers::find()
+                .filter(covers::Column::Id.is_in(title_ids))
+                .all(&app_state.db)
+                .await
+                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find covers: {}", e)))?
+                .into_iter()
+                .map(|cover| (cover.id.clone(), cover)),
+        );
     }
 
-    if let Some(tag_ids) = tag_ids {
-        let mut internal_cond = Condition::any();
+    let responses = titles
+        .into_iter()
+        .map(|title| {
+            let cover_model = cover_models.remove(&title.id)?;
+            let (width, height) = calculate_dimension(&app_state.config, cover_model.ratio);
+
+            Some(FilterTitleResponseBody {
+                id: title.id.to_string(),
+                favorite_count: favorite_counts.get(&title.id).copied(),
+                page_count: page_counts.get(&title.id).copied().unwrap_or_default(),
+                page_read: pages_read.get(&title.id).copied(),
+                title: title.title,
+                author: title.author,
+                category_id: title.category_id.to_string(),
+                release: title.release,
+                hash: title.hash,
+
+                blurhash: cover_model.blurhash,
+                width,
+                height,
+                format: PathBuf::from(cover_model.path)
+                    .extension()
+                    .map(|s| s.to_str().unwrap_or(""))
+                    .unwrap_or("")
+                    .to_ascii_lowercase(),
+            })
+        })
+        .collect();
+
+    Ok(responses)
+}
+
+#[cfg(test)]
+pub(crate) mod tests_filter {
+    use super::*;
+    use crate::{
+        archive::ArchiveCache,
+        config::Config,
+        models::fixtures::{insert_category, insert_user, memory_db},
+    };
+    use sea_orm::{ActiveModelTrait, DatabaseConnection, IntoActiveModel, Set};
+    use std::time::Instant;
+    use tokio::sync::Mutex;
+
+    pub(crate) struct Library {
+        pub db: DatabaseConnection,
+        pub user_id: UserID,
+        pub manga: CategoryID,
+        pub webtoons: CategoryID,
+    }
+
+    async fn title(
+        db: &DatabaseConnection,
+        category_id: &CategoryID,
+        name: &str,
+        tag_ids: &[u32],
+    ) -> TitleID {
+        let title = titles::Model {
+            id: CustomID::new(),
+            title: name.to_string(),
+            category_id: category_id.clone(),
+            description: Some(format!("The {} story", name.to_lowercase())),
+            ..Default::default()
+        };
+        title.clone().into_active_model().insert(db).await.unwrap();
         for tag_id in tag_ids {
-            internal_cond = internal_cond.add(titles_tags::Column::TagId.eq(tag_id));
+            titles_tags::ActiveModel {
+                title_id: Set(title.id.to_string()),
+                tag_id: Set(*tag_id),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        title.id
+    }
+
+    async fn progress(db: &DatabaseConnection, user_id: &UserID, title_id: &TitleID, page: i64) {
+        progresses::ActiveModel {
+            user_id: Set(user_id.clone()),
+            title_id: Set(title_id.clone()),
+            last_read_at: Set(String::new()),
+            page: Set(page),
+            ..Default::default()
         }
-        let title_tag_has_tag_id = TitlesTags::find()
-            .filter(internal_cond)
-            .all(&app_state.db)
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    /// Alpha (manga, tag 1) is being read and a favorite, Beta (manga, tags 1 and 2) is
+    /// finished and bookmarked, Gamma (webtoons, tag 2) is only read by someone else, and
+    /// Delta is a hidden copy of Gamma.
+    pub(crate) async fn library() -> Library {
+        let db = memory_db().await;
+
+        for (id, name) in [(1, "Action"), (2, "Comedy")] {
+            tags::ActiveModel {
+                id: Set(id),
+                name: Set(name.to_string()),
+            }
+            .insert(&db)
             .await
-            // .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags: {}", e)))?;
-            .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags: {}", e)))?;
-        for entity in title_tag_has_tag_id {
-            condition = condition.add(titles::Column::Id.eq(entity.title_id));
+            .unwrap();
+        }
+        let user_id = insert_user(&db, "reader").await;
+        let other_user_id = insert_user(&db, "other").await;
+        let manga = insert_category(&db, "Manga").await;
+        let webtoons = insert_category(&db, "Webtoons").await;
+
+        let alpha = title(&db, &manga, "Alpha", &[1]).await;
+        let beta = title(&db, &manga, "Beta", &[1, 2]).await;
+        let gamma = title(&db, &webtoons, "Gamma", &[2]).await;
+        let delta = title(&db, &webtoons, "Delta", &[1, 2]).await;
+        titles::ActiveModel {
+            id: Set(delta),
+            duplicate_of: Set(Some(gamma.to_string())),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+
+        progress(&db, &user_id, &alpha, 3).await;
+        progress(&db, &user_id, &beta, 0).await;
+        progress(&db, &other_user_id, &gamma, 5).await;
+        favorites::ActiveModel {
+            user_id: Set(user_id.clone()),
+            title_id: Set(alpha),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        bookmarks::ActiveModel {
+            user_id: Set(user_id.clone()),
+            title_id: Set(beta),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        Library {
+            db,
+            user_id,
+            manga,
+            webtoons,
         }
     }
 
-    if let Some(is_reading) = query.is_reading {
-        if is_reading {
-            let progress_models = Progresses::find()
-                .filter(progresses::Column::UserId.eq(&user.id))
-                .filter(progresses::Column::Page.gt(0))
-                .all(&app_state.db)
+    async fn page(
+        library: &Library,
+        query: &FilterRequestBody,
+    ) -> (Vec<String>, u64, Option<String>) {
+        let cursor = parse_cursor(query).unwrap();
+        let (titles, total, next_cursor) =
+            find_titles_page(&library.db, query, &library.user_id, cursor.as_ref())
+                .await
+                .unwrap();
+        (
+            titles.into_iter().map(|title| title.title).collect(),
+            total,
+            next_cursor,
+        )
+    }
+
+    async fn filtered(library: &Library, query: FilterRequestBody) -> Vec<String> {
+        page(library, &query).await.0
+    }
+
+    async fn filtered_by(library: &Library, filter: Filter) -> Vec<String> {
+        let query = FilterRequestBody {
+            filter: Some(filter),
+            ..Default::default()
+        };
+        filtered(library, query).await
+    }
+
+    #[tokio::test]
+    async fn everything_without_filters() {
+        let library = library().await;
+        assert_eq!(
+            filtered(&library, FilterRequestBody::default()).await,
+            ["Alpha", "Beta", "Gamma"]
+        );
+    }
+
+    #[tokio::test]
+    async fn fields_intersect() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            category_ids: Some(vec![library.manga.to_string()]),
+            tag_ids: Some(vec![2]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta"]);
+
+        let query = FilterRequestBody {
+            keywords: Some(vec!["STOR".to_string()]),
+            category_ids: Some(vec![library.webtoons.to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Gamma"]);
+
+        let query = FilterRequestBody {
+            category_ids: Some(vec![library.webtoons.to_string()]),
+            is_finished: Some(true),
+            ..Default::default()
+        };
+        assert!(filtered(&library, query).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn categories_any_tags_all() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            category_ids: Some(vec![
+                library.manga.to_string(),
+                library.webtoons.to_string(),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Alpha", "Beta", "Gamma"]);
+
+        let query = FilterRequestBody {
+            tag_ids: Some(vec![1, 2]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta"]);
+    }
+
+    #[tokio::test]
+    async fn keywords_all_match() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            keywords: Some(vec!["gam".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Gamma"]);
+
+        let query = FilterRequestBody {
+            keywords: Some(vec!["alpha".to_string(), "gamma".to_string()]),
+            ..Default::default()
+        };
+        assert!(filtered(&library, query).await.is_empty());
+
+        // tags are searched too
+        let query = FilterRequestBody {
+            keywords: Some(vec!["comedy".to_string(), "  ".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta", "Gamma"]);
+    }
+
+    #[tokio::test]
+    async fn keywords_inside_words() {
+        let library = library().await;
+        title(&library.db, &library.manga, "Folklore", &[]).await;
+        title(&library.db, &library.manga, "進撃の巨人", &[]).await;
+        let keyword = |keyword: &str| Filter::Keyword(keyword.to_string());
+
+        assert_eq!(filtered_by(&library, keyword("lore")).await, ["Folklore"]);
+        assert_eq!(filtered_by(&library, keyword("LKL")).await, ["Folklore"]);
+        // Japanese is written without spaces, so it's a single word
+        assert_eq!(
+            filtered_by(&library, keyword("撃の巨")).await,
+            ["進撃の巨人"]
+        );
+        assert_eq!(filtered_by(&library, keyword("巨人")).await, ["進撃の巨人"]);
+        assert_eq!(filtered_by(&library, keyword("ph")).await, ["Alpha"]);
+        assert_eq!(
+            filtered_by(&library, keyword("lore ph")).await,
+            Vec::<String>::new()
+        );
+        // LIKE wildcards are searched as text
+        assert!(filtered_by(&library, keyword("%")).await.is_empty());
+        assert!(filtered_by(&library, keyword("_")).await.is_empty());
+        assert!(filtered_by(&library, keyword("!")).await.is_empty());
+        // the patterns are bound in order, before the limit
+        let query = FilterRequestBody {
+            filter: Some(keyword("ph")),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Alpha"]);
+    }
+
+    #[tokio::test]
+    async fn exclusions() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            exclude_tag_ids: Some(vec![2]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Alpha"]);
+
+        let query = FilterRequestBody {
+            exclude_category_ids: Some(vec![library.manga.to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Gamma"]);
+
+        let query = FilterRequestBody {
+            tag_ids: Some(vec![1]),
+            exclude_tag_ids: Some(vec![1]),
+            ..Default::default()
+        };
+        assert!(filtered(&library, query).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn user_state() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            is_reading: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Alpha"]);
+
+        // the progress of someone else doesn't count
+        let filter = Filter::Not(Box::new(Filter::Reading));
+        assert_eq!(filtered_by(&library, filter).await, ["Beta", "Gamma"]);
+
+        let query = FilterRequestBody {
+            is_finished: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta"]);
+
+        let query = FilterRequestBody {
+            is_bookmarked: Some(true),
+            is_favorite: Some(true),
+            ..Default::default()
+        };
+        assert!(filtered(&library, query).await.is_empty());
+
+        let query = FilterRequestBody {
+            tag_ids: Some(vec![1]),
+            filter: Some(Filter::Not(Box::new(Filter::Favorite))),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta"]);
+    }
+
+    #[tokio::test]
+    async fn groups() {
+        let library = library().await;
+
+        let filter = Filter::Any(vec![Filter::Bookmarked, Filter::Favorite]);
+        assert_eq!(filtered_by(&library, filter).await, ["Alpha", "Beta"]);
+
+        let filter = Filter::All(vec![
+            Filter::Any(vec![Filter::Reading, Filter::Tag(2)]),
+            Filter::Not(Box::new(Filter::Category(library.webtoons.to_string()))),
+        ]);
+        assert_eq!(filtered_by(&library, filter).await, ["Alpha", "Beta"]);
+
+        let filter = Filter::Not(Box::new(Filter::All(vec![
+            Filter::Tag(1),
+            Filter::Not(Box::new(Filter::Finished)),
+        ])));
+        assert_eq!(filtered_by(&library, filter).await, ["Beta", "Gamma"]);
+
+        // combined with the other fields
+        let query = FilterRequestBody {
+            category_ids: Some(vec![library.manga.to_string()]),
+            filter: Some(Filter::Any(vec![Filter::Finished, Filter::Tag(2)])),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&library, query).await, ["Beta"]);
+    }
+
+    #[tokio::test]
+    async fn empty_groups() {
+        let library = library().await;
+
+        assert_eq!(
+            filtered_by(&library, Filter::All(vec![])).await,
+            ["Alpha", "Beta", "Gamma"]
+        );
+        assert!(filtered_by(&library, Filter::Any(vec![])).await.is_empty());
+        assert!(
+            filtered_by(&library, Filter::Not(Box::new(Filter::All(vec![]))))
                 .await
-                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find progress: {}", e)))?;
-            for entity in progress_models {
-                condition = condition.add(titles::Column::Id.eq(entity.title_id));
+                .is_empty()
+        );
+        assert_eq!(
+            filtered_by(&library, Filter::Not(Box::new(Filter::Any(vec![])))).await,
+            ["Alpha", "Beta", "Gamma"]
+        );
+    }
+
+    /// Bodies of clients written before `filter`, empty lists and `false` flags don't filter.
+    #[tokio::test]
+    async fn older_bodies() {
+        let library = library().await;
+        let sent = |body: &str| serde_json::from_str::<FilterRequestBody>(body).unwrap();
+
+        for body in [
+            r#"{"category_ids":[]}"#,
+            r#"{"exclude_category_ids":[],"exclude_tag_ids":[]}"#,
+            r#"{"is_reading":false}"#,
+            r#"{"is_reading":false,"is_finished":false,"is_bookmarked":false,"is_favorite":false}"#,
+        ] {
+            assert_eq!(
+                filtered(&library, sent(body)).await,
+                ["Alpha", "Beta", "Gamma"],
+                "{}",
+                body
+            );
+        }
+        assert_eq!(
+            filtered(&library, sent(r#"{"category_ids":[],"is_finished":true}"#)).await,
+            ["Beta"]
+        );
+    }
+
+    /// Bodies as src/pages/filter.vue sends them, with what isn't selected left out.
+    #[tokio::test]
+    async fn filter_page() {
+        let library = library().await;
+        let sent = |body: String| serde_json::from_str::<FilterRequestBody>(&body).unwrap();
+
+        assert_eq!(
+            filtered(&library, sent("{}".to_string())).await,
+            ["Alpha", "Beta", "Gamma"]
+        );
+        assert_eq!(
+            filtered(
+                &library,
+                sent(r#"{"filter":{"any":["reading"]}}"#.to_string())
+            )
+            .await,
+            ["Alpha"]
+        );
+        // reading and finished checked, no title is both
+        assert_eq!(
+            filtered(
+                &library,
+                sent(r#"{"filter":{"any":["reading","finished"]}}"#.to_string())
+            )
+            .await,
+            ["Alpha", "Beta"]
+        );
+        assert_eq!(
+            filtered(
+                &library,
+                sent(format!(
+                    r#"{{"keywords":["story"],"category_ids":["{}"],"filter":{{"any":["finished"]}},"sort_by":"alphabetical","sort_order":"descending"}}"#,
+                    library.manga
+                ))
+            )
+            .await,
+            ["Beta"]
+        );
+    }
+
+    #[tokio::test]
+    async fn pages() {
+        let library = library().await;
+
+        for (sort_by, sort_order, expected) in [
+            (
+                SortBy::Alphabetical,
+                SortOrder::Ascending,
+                ["Alpha", "Beta", "Gamma"],
+            ),
+            (
+                SortBy::Alphabetical,
+                SortOrder::Descending,
+                ["Gamma", "Beta", "Alpha"],
+            ),
+        ] {
+            let mut query = FilterRequestBody {
+                limit: Some(2),
+                sort_by: Some(sort_by),
+                sort_order: Some(sort_order),
+                ..Default::default()
+            };
+            let (first, total, next_cursor) = page(&library, &query).await;
+            assert_eq!(first, expected[..2]);
+            assert_eq!(total, 3);
+
+            query.cursor = next_cursor;
+            let (second, total, next_cursor) = page(&library, &query).await;
+            assert_eq!(second, expected[2..]);
+            assert_eq!(total, 3);
+            assert_eq!(next_cursor, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_with_same_sort_value() {
+        let library = library().await;
+
+        // no title has a release date, they're listed by ID
+        let mut query = FilterRequestBody {
+            limit: Some(1),
+            sort_by: Some(SortBy::ReleaseDate),
+            ..Default::default()
+        };
+        let mut listed = vec![];
+        loop {
+            let (titles, total, next_cursor) = page(&library, &query).await;
+            assert_eq!(total, 3);
+            listed.extend(titles);
+            match next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
             }
         }
+        listed.sort();
+        assert_eq!(listed, ["Alpha", "Beta", "Gamma"]);
     }
 
-    if let Some(is_finished) = query.is_finished {
-        if is_finished {
-            let progress_models = Progresses::find()
-                .filter(progresses::Column::UserId.eq(&user.id))
-                .filter(progresses::Column::Page.eq(0))
-                .all(&app_state.db)
-                .await
-                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find progress: {}", e)))?;
-            for entity in progress_models {
-                condition = condition.add(titles::Column::Id.eq(entity.title_id));
+    #[tokio::test]
+    async fn pages_of_filtered() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            tag_ids: Some(vec![2]),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let (titles, total, next_cursor) = page(&library, &query).await;
+        assert_eq!((titles, total), (vec!["Beta".to_string()], 2));
+        assert!(next_cursor.is_some());
+
+        let query = FilterRequestBody {
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(page(&library, &query).await.2, None);
+    }
+
+    #[test]
+    fn cursors() {
+        let query = FilterRequestBody {
+            sort_by: Some(SortBy::AddDate),
+            ..Default::default()
+        };
+        let cursor = FilterCursor {
+            sort_by: "date_added".to_string(),
+            descending: false,
+            seed: None,
+            value: "2024-07-01".to_string(),
+            id: "abc".to_string(),
+        };
+        let with_cursor = |cursor: &str| FilterRequestBody {
+            cursor: Some(cursor.to_string()),
+            ..query.clone()
+        };
+
+        assert_eq!(
+            parse_cursor(&with_cursor(&encode_cursor(&cursor))),
+            Ok(Some(cursor.clone()))
+        );
+        assert_eq!(parse_cursor(&query), Ok(None));
+        assert_eq!(parse_cursor(&with_cursor("zz")), Err(()));
+
+        // listed in another sort
+        let descending = FilterCursor {
+            descending: true,
+            ..cursor
+        };
+        assert_eq!(
+            parse_cursor(&with_cursor(&encode_cursor(&descending))),
+            Err(())
+        );
+    }
+
+    async fn sorted(library: &Library, sort_by: SortBy, sort_order: SortOrder) -> Vec<String> {
+        let query = FilterRequestBody {
+            sort_by: Some(sort_by),
+            sort_order: Some(sort_order),
+            ..Default::default()
+        };
+        filtered(library, query).await
+    }
+
+    #[tokio::test]
+    async fn title_fields() {
+        let library = library().await;
+        let db = &library.db;
+        for (name, author, release) in [
+            ("Alpha", "Eiichiro Oda", Some("1997-07-22")),
+            ("Beta", "100%_Ana", Some("2012")),
+            ("Gamma", "Bo", None),
+        ] {
+            titles::ActiveModel {
+                id: Set(title_named(db, name).await.id),
+                author: Set(Some(author.to_string())),
+                release: Set(release.map(|release| release.to_string())),
+                ..Default::default()
             }
+            .update(db)
+            .await
+            .unwrap();
         }
+        pages_of(db, &title_named(db, "Alpha").await.id, &[None; 3]).await;
+        pages_of(db, &title_named(db, "Gamma").await.id, &[None; 60]).await;
+
+        let filter = Filter::Author("oda".to_string());
+        assert_eq!(filtered_by(&library, filter).await, ["Alpha"]);
+        // wildcards are matched as is
+        let filter = Filter::Author("%_".to_string());
+        assert_eq!(filtered_by(&library, filter).await, ["Beta"]);
+
+        let filter = Filter::ReleaseYear(NumberRange {
+            min: Some(2011),
+            max: None,
+        });
+        assert_eq!(filtered_by(&library, filter).await, ["Beta"]);
+        // titles without a release date never match
+        let filter = Filter::Not(Box::new(Filter::ReleaseYear(NumberRange::default())));
+        assert_eq!(filtered_by(&library, filter).await, ["Gamma"]);
+
+        let filter = Filter::PageCount(NumberRange {
+            min: None,
+            max: Some(49),
+        });
+        assert_eq!(filtered_by(&library, filter).await, ["Alpha", "Beta"]);
     }
 
-    if let Some(is_bookmarked) = query.is_bookmarked {
-        if is_bookmarked {
-            let bookmark_models = Bookmarks::find()
-                .filter(bookmarks::Column::UserId.eq(&user.id))
-                .all(&app_state.db)
+    #[tokio::test]
+    async fn sorts() {
+        let library = library().await;
+        let db = &library.db;
+        let alpha = title_named(db, "Alpha").await;
+        let beta = title_named(db, "Beta").await;
+        let gamma = title_named(db, "Gamma").await;
+
+        for (title, last_read_at) in [(&alpha, "2024-07-01"), (&beta, "2024-07-02")] {
+            let progress = Progresses::find()
+                .filter(progresses::Column::TitleId.eq(&title.id))
+                .one(db)
                 .await
-                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find bookmark: {}", e)))?;
-            for entity in bookmark_models {
-                condition = condition.add(titles::Column::Id.eq(entity.title_id));
+                .unwrap()
+                .unwrap();
+            progresses::ActiveModel {
+                id: Set(progress.id),
+                last_read_at: Set(last_read_at.to_string()),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .unwrap();
+        }
+        for username in ["fan", "other fan"] {
+            favorites::ActiveModel {
+                user_id: Set(insert_user(db, username).await),
+                title_id: Set(gamma.id.clone()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        titles::ActiveModel {
+            id: Set(gamma.id.clone()),
+            long_strip: Set(true),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        pages_of(db, &alpha.id, &[None, None, Some("0,100,200")]).await;
+        pages_of(db, &gamma.id, &[None, Some("0,100,200,300")]).await;
+
+        // Gamma was only read by someone else
+        assert_eq!(
+            sorted(&library, SortBy::LastRead, SortOrder::Descending).await,
+            ["Beta", "Alpha", "Gamma"]
+        );
+        assert_eq!(
+            sorted(&library, SortBy::FavoriteCount, SortOrder::Descending).await,
+            ["Gamma", "Alpha", "Beta"]
+        );
+        assert_eq!(
+            sorted(&library, SortBy::BookmarkCount, SortOrder::Descending).await[0],
+            "Beta"
+        );
+        // slices only count apart in long strips
+        assert_eq!(
+            sorted(&library, SortBy::PageCount, SortOrder::Ascending).await,
+            ["Beta", "Alpha", "Gamma"]
+        );
+
+        // numbers are compared as numbers across pages
+        let mut query = FilterRequestBody {
+            limit: Some(1),
+            sort_by: Some(SortBy::FavoriteCount),
+            sort_order: Some(SortOrder::Descending),
+            ..Default::default()
+        };
+        let mut listed = vec![];
+        loop {
+            let (titles, _, next_cursor) = page(&library, &query).await;
+            listed.extend(titles);
+            match next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
             }
         }
+        assert_eq!(listed, ["Gamma", "Alpha", "Beta"]);
     }
 
-    if let Some(is_favorite) = query.is_favorite {
-        if is_favorite {
-            let favorite_models = Favorites::find()
-                .filter(favorites::Column::UserId.eq(&user.id))
-                .all(&app_state.db)
-                .await
-                .map_err(|e| AppError::from(anyhow::anyhow!("Can't find favorite: {}", e)))?;
-            for entity in favorite_models {
-                condition = condition.add(titles::Column::Id.eq(entity.title_id));
+    #[tokio::test]
+    async fn random_sort() {
+        let library = library().await;
+
+        let query = FilterRequestBody {
+            sort_by: Some(SortBy::Random),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let shuffled = filtered(&library, query.clone()).await;
+        assert_eq!(filtered(&library, query).await, shuffled);
+
+        // pages keep the seed picked for the first one
+        let mut query = FilterRequestBody {
+            limit: Some(1),
+            sort_by: Some(SortBy::Random),
+            ..Default::default()
+        };
+        let mut listed = vec![];
+        loop {
+            let (titles, total, next_cursor) = page(&library, &query).await;
+            assert_eq!(total, 3);
+            listed.extend(titles);
+            match next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
             }
         }
+        listed.sort();
+        assert_eq!(listed, ["Alpha", "Beta", "Gamma"]);
+
+        // a cursor from another seed
+        let query = FilterRequestBody {
+            seed: Some(7),
+            ..query
+        };
+        assert_eq!(parse_cursor(&query), Err(()));
     }
 
-    let sort_by = match &query.sort_by {
-        Some(sort_by) => match sort_by.as_str() {
-            "alphabetical" => titles::Column::Title,
-            "add date" => titles::Column::DateAdded,
-            "release date" => titles::Column::Release,
-            "update date" => titles::Column::DateUpdated,
-            // "last read" => {},
-            _ => titles::Column::Title,
-        },
-        None => titles::Column::Title,
-    };
+    #[tokio::test]
+    async fn facets() {
+        let library = library().await;
+        let db = &library.db;
+        for (name, author, release) in [
+            ("Alpha", "Ana", Some("2023-05-01")),
+            ("Beta", "Ana", Some("2024")),
+            ("Gamma", "Bo", None),
+        ] {
+            titles::ActiveModel {
+                id: Set(title_named(db, name).await.id),
+                author: Set(Some(author.to_string())),
+                release: Set(release.map(|release| release.to_string())),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+            .unwrap();
+        }
 
-    let sort_order = match &query.sort_order {
-        Some(sort_order) => match sort_order.as_str() {
-            "ascending" => Order::Asc,
-            "descending" => Order::Desc,
-            _ => Order::Asc,
-        },
-        None => Order::Asc,
-    };
+        // the hidden copy isn't counted
+        let facets = find_facets(db, &FilterRequestBody::default(), &library.user_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            facets.tags,
+            [
+                TagFacet {
+                    id: 1,
+                    name: "Action".to_string(),
+                    count: 2,
+                },
+                TagFacet {
+                    id: 2,
+                    name: "Comedy".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            facets.categories,
+            [
+                CategoryFacet {
+                    id: library.manga.to_string(),
+                    name: "Manga".to_string(),
+                    count: 2,
+                },
+                CategoryFacet {
+                    id: library.webtoons.to_string(),
+                    name: "Webtoons".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+        let values = |facets: &[ValueFacet]| {
+            facets
+                .iter()
+                .map(|facet| (facet.value.clone(), facet.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            values(&facets.authors),
+            [("Ana".to_string(), 2), ("Bo".to_string(), 1)]
+        );
+        assert_eq!(
+            values(&facets.release_years),
+            [("2024".to_string(), 1), ("2023".to_string(), 1)]
+        );
 
-    let title_models = Titles::find()
-        .apply_if(limit.map(|limit| limit as u64), QuerySelect::limit)
-        .filter(condition)
-        .order_by(sort_by, sort_order)
-        .all(&app_state.db)
+        // only the results are counted
+        let query = FilterRequestBody {
+            tag_ids: Some(vec![2]),
+            ..Default::default()
+        };
+        let facets = find_facets(db, &query, &library.user_id).await.unwrap();
+        assert_eq!(
+            facets
+                .tags
+                .iter()
+                .map(|tag| (tag.id, tag.count))
+                .collect::<Vec<_>>(),
+            [(2, 2), (1, 1)]
+        );
+        assert_eq!(
+            values(&facets.authors),
+            [("Ana".to_string(), 1), ("Bo".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn sort_json() {
+        let query = serde_json::from_str::<FilterRequestBody>(
+            r#"{ "sort_by": "last read", "sort_order": "descending" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            (query.sort_by, query.sort_order),
+            (Some(SortBy::LastRead), Some(SortOrder::Descending))
+        );
+
+        for body in [
+            r#"{ "sort_by": "last opened" }"#,
+            r#"{ "sort_by": "" }"#,
+            r#"{ "sort_order": "newest" }"#,
+        ] {
+            assert!(serde_json::from_str::<FilterRequestBody>(body).is_err());
+        }
+    }
+
+    #[test]
+    fn json() {
+        let filter = serde_json::from_str::<Filter>(
+            r#"{ "all": [{ "tag": 1 }, { "not": { "any": [{ "category": "x" }, "finished"] } }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            filter,
+            Filter::All(vec![
+                Filter::Tag(1),
+                Filter::Not(Box::new(Filter::Any(vec![
+                    Filter::Category("x".to_string()),
+                    Filter::Finished,
+                ]))),
+            ])
+        );
+    }
+
+    fn app_state(db: DatabaseConnection) -> AppState {
+        std::env::set_var("JWT_SECRET", "secret");
+        AppState {
+            db,
+            config: Config::init(),
+            scanning_complete: Mutex::new(true),
+            scanning_progress: Mutex::new(1.0),
+            archive_cache: ArchiveCache::new(1, None),
+            search_cache: Default::default(),
+        }
+    }
+
+    async fn cover(db: &DatabaseConnection, title_id: &TitleID) {
+        covers::ActiveModel {
+            id: Set(title_id.clone()),
+            path: Set("000.JPG".to_string()),
+            blurhash: Set("LEHV6nWB2yk8pyo0adR*.7kCMdnj".to_string()),
+            ratio: Set(700),
+            is_custom: Set(false),
+            rule: Set(None),
+            rules_hash: Set(None),
+        }
+        .insert(db)
         .await
-        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find titles: {}", e)))?;
+        .unwrap();
+    }
 
-    let mut resp_data: Vec<FilterTitleResponseBody> = vec![];
+    async fn pages_of(db: &DatabaseConnection, title_id: &TitleID, slices: &[Option<&str>]) {
+        Pages::insert_many(
+            slices
+                .iter()
+                .enumerate()
+                .map(|(i, slices)| pages::ActiveModel {
+                    id: Set(CustomID::new()),
+                    title_id: Set(title_id.clone()),
+                    path: Set(format!("{:03}.jpg", i)),
+                    slices: Set(slices.map(|slices| slices.to_string())),
+                    ..Default::default()
+                }),
+        )
+        .exec(db)
+        .await
+        .unwrap();
+    }
 
-    for title in title_models {
-        let page_count = find_page_count(&app_state.db, &title.id).await;
-        let favorite_count = find_favorite_count(&app_state.db, &title.id).await;
-        let page_read = find_page_read(&app_state.db, &title.id, &user.id).await;
-        let cover_model = match Covers::find_by_id(&title.id)
-            .one(&app_state.db)
+    async fn title_named(db: &DatabaseConnection, name: &str) -> titles::Model {
+        Titles::find()
+            .filter(titles::Column::Title.eq(name))
+            .one(db)
             .await
-            .map_err(|e| AppError::from(anyhow::anyhow!("Can't find cover: {}", e)))?
-        {
-            Some(cover) => cover,
-            None => return Ok((StatusCode::NO_CONTENT, "No cover found").into_response()),
-        };
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn listings() {
+        let Library { db, user_id, .. } = library().await;
+        let other_user_id = insert_user(&db, "fan").await;
+        let alpha = title_named(&db, "Alpha").await;
+        let mut gamma = title_named(&db, "Gamma").await;
+        titles::ActiveModel {
+            id: Set(gamma.id.clone()),
+            long_strip: Set(true),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        gamma.long_strip = true;
+        let beta = title_named(&db, "Beta").await;
+
+        pages_of(&db, &alpha.id, &[None, None, Some("0,100,200")]).await;
+        pages_of(&db, &gamma.id, &[None, Some("0,100,200,300")]).await;
+        favorites::ActiveModel {
+            user_id: Set(other_user_id),
+            title_id: Set(alpha.id.clone()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        cover(&db, &alpha.id).await;
+        cover(&db, &gamma.id).await;
+
+        let app_state = app_state(db);
+        let listings = find_title_responses(&app_state, vec![gamma, beta, alpha], &user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|listing| {
+                listing.map(|listing| {
+                    (
+                        listing.title,
+                        listing.page_count,
+                        listing.favorite_count,
+                        listing.page_read,
+                        listing.format,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            listings,
+            vec![
+                // slices only count apart in long strips
+                Some(("Gamma".to_string(), 4, None, None, "jpg".to_string())),
+                None,
+                Some(("Alpha".to_string(), 3, Some(2), Some(3), "jpg".to_string())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn many_titles() {
+        let db = memory_db().await;
+        let user_id = insert_user(&db, "reader").await;
+        let category_id = insert_category(&db, "Manga").await;
+        let mut title_models = vec![];
+        for i in 0..MAX_FILTER_LIMIT + 1 {
+            let title = titles::Model {
+                id: CustomID::new(),
+                title: format!("Title {:04}", i),
+                category_id: category_id.clone(),
+                ..Default::default()
+            }
+            .into_active_model()
+            .insert(&db)
+            .await
+            .unwrap();
+            cover(&db, &title.id).await;
+            title_models.push(title);
+        }
 
+        // a page is never the whole library
+        for (limit, expected) in [
+            (None, DEFAULT_FILTER_LIMIT),
+            (Some(u32::MAX), MAX_FILTER_LIMIT),
+        ] {
+            let query = FilterRequestBody {
+                limit,
+                ..Default::default()
+            };
+            let (titles, total, next_cursor) =
+                find_titles_page(&db, &query, &user_id, None).await.unwrap();
+            assert_eq!(titles.len(), expected as usize);
+            assert_eq!(total, MAX_FILTER_LIMIT as u64 + 1);
+            assert!(next_cursor.is_some());
+        }
+
+        // the IDs are bound in more than one chunk
+        let listings = find_title_responses(&app_state(db), title_models, &user_id)
+            .await
+            .unwrap();
+        assert_eq!(listings.len(), MAX_FILTER_LIMIT as usize + 1);
+        assert!(listings.iter().all(|listing| listing.is_some()));
+    }
+
+    /// How a title was listed before the batched queries, four queries per title, kept to
+    /// compare with.
+    async fn find_title_response_per_title(
+        app_state: &AppState,
+        title: titles::Model,
+        user_id: &UserID,
+    ) -> Option<FilterTitleResponseBody> {
+        let db = &app_state.db;
+        let pages = Pages::find()
+            .filter(pages::Column::TitleId.contains(&title.id))
+            .all(db)
+            .await
+            .unwrap_or(vec![]);
+        let page_count = pages
+            .iter()
+            .map(|page| page.virtual_count(title.long_strip) as i64)
+            .sum();
+        let favorites = Favorites::find()
+            .filter(favorites::Column::TitleId.contains(&title.id))
+            .all(db)
+            .await
+            .unwrap_or(vec![]);
+        let page_read = Progresses::find()
+            .filter(
+                Condition::all()
+                    .add(progresses::Column::TitleId.eq(&title.id))
+                    .add(progresses::Column::UserId.eq(user_id)),
+            )
+            .one(db)
+            .await
+            .unwrap_or_default()
+            .map(|progress| progress.page);
+        let cover_model = Covers::find_by_id(&title.id).one(db).await.ok()??;
         let (width, height) = calculate_dimension(&app_state.config, cover_model.ratio);
 
-        resp_data.push(FilterTitleResponseBody {
+        Some(FilterTitleResponseBody {
             id: title.id.to_string(),
+            favorite_count: (!favorites.is_empty()).then_some(favorites.len() as i64),
+            page_count,
+            page_read,
             title: title.title,
             author: title.author,
             category_id: title.category_id.to_string(),
             release: title.release,
-            favorite_count,
-            page_count,
-            page_read,
+            hash: title.hash,
 
             blurhash: cover_model.blurhash,
             width,
@@ -237,8 +1982,51 @@ pub async fn post_filter(
                 .map(|s| s.to_str().unwrap_or(""))
                 .unwrap_or("")
                 .to_ascii_lowercase(),
-        });
+        })
     }
 
-    Ok((StatusCode::OK, Json(FilterResponseBody { data: resp_data })).into_response())
+    /// Compare listing titles one at a time, as it used to be, with listing them at once.
+    ///
+    /// Run it with `cargo test --release bench_listings -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore]
+    async fn bench_listings() {
+        let db = memory_db().await;
+        let user_id = insert_user(&db, "reader").await;
+        let category_id = insert_category(&db, "Manga").await;
+
+        let mut title_models = vec![];
+        for i in 0..5000 {
+            let title = titles::Model {
+                id: CustomID::new(),
+                title: format!("Title {:04}", i),
+                category_id: category_id.clone(),
+                ..Default::default()
+            };
+            title_models.push(title.clone());
+            title.into_active_model().insert(&db).await.unwrap();
+            cover(&db, &title_models[i].id).await;
+            pages_of(&db, &title_models[i].id, &[None; 20]).await;
+            progress(&db, &user_id, &title_models[i].id, 1).await;
+        }
+        let app_state = app_state(db);
+
+        let start = Instant::now();
+        for title in title_models.clone() {
+            find_title_response_per_title(&app_state, title, &user_id).await;
+        }
+        let one_at_a_time = start.elapsed();
+
+        let start = Instant::now();
+        let listings = find_title_responses(&app_state, title_models, &user_id)
+            .await
+            .unwrap();
+        let at_once = start.elapsed();
+
+        assert!(listings.iter().all(|listing| listing.is_some()));
+        println!(
+            "5000 titles: {:?} one at a time, {:?} at once",
+            one_at_a_time, at_once
+        );
+    }
 }
@@ -0,0 +1,698 @@
+use std::{collections::HashMap, fmt, ops::Range, sync::Arc};
+
+use super::{Filter, NumberRange};
+use crate::{models::prelude::*, AppError, AppState};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+/// Fields of `field:value` terms, as listed in errors.
+const FIELDS: &str = "tag, category, author, year, status or pages";
+
+/// How deep `(` and `-` can nest, deeper queries are refused rather than recursed into.
+const MAX_QUERY_DEPTH: usize = 32;
+
+/// A problem in a query, from the character `start` up to `end`.
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueryError {
+    /// Counted in characters, not bytes.
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            start: span.start,
+            end: span.end,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.start, self.end)
+    }
+}
+
+/// Tags and categories by their lowercase name, for `tag:` and `category:` terms.
+#[derive(Debug, Clone, Default)]
+pub struct QueryNames {
+    pub tags: HashMap<String, u32>,
+    pub categories: HashMap<String, Vec<String>>,
+}
+
+/// Load the names `tag:` and `category:` terms can refer to.
+pub async fn find_query_names(db: &DatabaseConnection) -> Result<QueryNames, DbErr> {
+    let mut names = QueryNames::default();
+    for tag in Tags::find().all(db).await? {
+        names.tags.insert(tag.name.to_lowercase(), tag.id);
+    }
+    for category in Categories::find().all(db).await? {
+        names
+            .categories
+            .entry(category.name.to_lowercase())
+            .or_default()
+            .push(category.id.to_string());
+    }
+    Ok(names)
+}
+
+/// Parse a query typed in a search box into a filter.
+///
+/// Terms are separated by spaces and all have to match. `OR` between terms matches either,
+/// `-` before a term excludes it and parentheses group terms. A term is a word or a
+/// `"quoted text"` to search for, or `field:value` with one of the fields in [FIELDS]. Values
+/// of `year` and `pages` are a number, a comparison like `>2010` or a range like `10..50`.
+///
+/// Syntax errors stop the parsing, unknown names and values are all reported. Parentheses and
+/// `-` nest up to [MAX_QUERY_DEPTH] deep.
+pub fn parse_query(query: &str, names: &QueryNames) -> Result<Filter, Vec<QueryError>> {
+    let tokens = tokenize(query).map_err(|e| vec![e])?;
+    let node = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    }
+    .parse()
+    .map_err(|e| vec![e])?;
+
+    let mut errors = vec![];
+    let filter = node.to_filter(names, &mut errors);
+    match errors.is_empty() {
+        true => Ok(filter),
+        false => Err(errors),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    Or,
+    Term {
+        field: Option<(String, Range<usize>)>,
+        value: String,
+        value_span: Range<usize>,
+    },
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, Range<usize>)>, QueryError> {
+    let chars = query.chars().collect::<Vec<_>>();
+    let ends_word = |c: char| c.is_whitespace() || c == '(' || c == ')';
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    // a quoted text from `start`, returning it and the index after its closing quote
+    let quoted = |start: usize| -> Result<(String, usize), QueryError> {
+        match chars[start + 1..].iter().position(|&c| c == '"') {
+            Some(len) => Ok((
+                chars[start + 1..start + 1 + len].iter().collect(),
+                start + len + 2,
+            )),
+            None => Err(QueryError::new(start..chars.len(), "Unclosed quote")),
+        }
+    };
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                tokens.push((Token::Open, start..i));
+            }
+            ')' => {
+                i += 1;
+                tokens.push((Token::Close, start..i));
+            }
+            '-' => {
+                i += 1;
+                let negated = chars.get(i).filter(|&&c| !c.is_whitespace() && c != ')');
+                if negated.is_none() {
+                    return Err(QueryError::new(start..i, "Expected a term after `-`"));
+                }
+                tokens.push((Token::Not, start..i));
+            }
+            '"' => {
+                let (value, end) = quoted(start)?;
+                i = end;
+                tokens.push((
+                    Token::Term {
+                        field: None,
+                        value,
+                        value_span: start..i,
+                    },
+                    start..i,
+                ));
+            }
+            _ => {
+                while i < chars.len() && !ends_word(chars[i]) && chars[i] != ':' && chars[i] != '"'
+                {
+                    i += 1;
+                }
+                let word = chars[start..i].iter().collect::<String>();
+
+                let token = match chars.get(i) {
+                    Some(':') if !word.is_empty() => {
+                        let field = (word, start..i);
+                        i += 1;
+                        let value_start = i;
+                        let value = match chars.get(i) {
+                            Some('"') => {
+                                let (value, end) = quoted(i)?;
+                                i = end;
+                                value
+                            }
+                            _ => {
+                                while i < chars.len() && !ends_word(chars[i]) {
+                                    i += 1;
+                                }
+                                chars[value_start..i].iter().collect()
+                            }
+                        };
+                        if i == value_start {
+                            return Err(QueryError::new(
+                                start..i,
+                                format!("Missing value after `{}:`", field.0),
+                            ));
+                        }
+                        Token::Term {
+                            field: Some(field),
+                            value,
+                            value_span: value_start..i,
+                        }
+                    }
+                    _ if word == "OR" => Token::Or,
+                    _ => {
+                        // a stray `:` or `"` inside a word is searched for as is
+                        while i < chars.len() && !ends_word(chars[i]) {
+                            i += 1;
+                        }
+                        Token::Term {
+                            field: None,
+                            value: chars[start..i].iter().collect(),
+                            value_span: start..i,
+                        }
+                    }
+                };
+                tokens.push((token, start..i));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    All(Vec<Node>),
+    Any(Vec<Node>),
+    Not(Box<Node>),
+    Term {
+        field: Option<(String, Range<usize>)>,
+        value: String,
+        value_span: Range<usize>,
+    },
+}
+
+struct Parser {
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+    /// `(` and `-` the current term is inside of.
+    depth: usize,
+}
+
+impl Parser {
+    fn parse(mut self) -> Result<Node, QueryError> {
+        let node = self.parse_any()?;
+        match self.tokens.get(self.pos) {
+            Some((_, span)) => Err(QueryError::new(span.clone(), "Unmatched `)`")),
+            None => Ok(node),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Terms separated by `OR`.
+    fn parse_any(&mut self) -> Result<Node, QueryError> {
+        let mut nodes = vec![self.parse_all()?];
+        while let Some((Token::Or, span)) = self.tokens.get(self.pos).cloned() {
+            if nodes.last() == Some(&Node::All(vec![])) {
+                return Err(QueryError::new(span, "Expected a term before `OR`"));
+            }
+            self.pos += 1;
+            let node = self.parse_all()?;
+            if node == Node::All(vec![]) {
+                return Err(QueryError::new(span, "Expected a term after `OR`"));
+            }
+            nodes.push(node);
+        }
+
+        match nodes.len() {
+            1 => Ok(nodes.remove(0)),
+            _ => Ok(Node::Any(nodes)),
+        }
+    }
+
+    /// Terms next to each other, until `OR`, `)` or the end.
+    fn parse_all(&mut self) -> Result<Node, QueryError> {
+        let mut nodes = vec![];
+        while !matches!(self.peek(), None | Some(Token::Or) | Some(Token::Close)) {
+            nodes.push(self.parse_term()?);
+        }
+
+        match nodes.len() {
+            1 => Ok(nodes.remove(0)),
+            _ => Ok(Node::All(nodes)),
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Node, QueryError> {
+        let Some((token, span)) = self.tokens.get(self.pos).cloned() else {
+            return Err(QueryError::new(0..0, "Expected a term"));
+        };
+        self.pos += 1;
+
+        match token {
+            Token::Not => Ok(Node::Not(Box::new(
+                self.parse_nested(span, Self::parse_term)?,
+            ))),
+            Token::Open => {
+                let node = self.parse_nested(span.clone(), Self::parse_any)?;
+                if node == Node::All(vec![]) {
+                    return Err(QueryError::new(span, "Expected a term after `(`"));
+                }
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(node)
+                    }
+                    _ => Err(QueryError::new(span, "Missing `)` for this `(`")),
+                }
+            }
+            Token::Term {
+                field,
+                value,
+                value_span,
+            } => Ok(Node::Term {
+                field,
+                value,
+                value_span,
+            }),
+            Token::Close | Token::Or => Err(QueryError::new(span, "Expected a term")),
+        }
+    }
+
+    /// Parse what's inside the `(` or `-` at `span`, as long as it isn't nested too deep.
+    fn parse_nested(
+        &mut self,
+        span: Range<usize>,
+        parse: fn(&mut Self) -> Result<Node, QueryError>,
+    ) -> Result<Node, QueryError> {
+        if self.depth == MAX_QUERY_DEPTH {
+            return Err(QueryError::new(
+                span,
+                format!("More than {} nested `(` and `-`", MAX_QUERY_DEPTH),
+            ));
+        }
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+        node
+    }
+}
+
+impl Node {
+    fn to_filter(&self, names: &QueryNames, errors: &mut Vec<QueryError>) -> Filter {
+        match self {
+            Self::All(nodes) => Filter::All(
+                nodes
+                    .iter()
+                    .map(|node| node.to_filter(names, errors))
+                    .collect(),
+            ),
+            Self::Any(nodes) => Filter::Any(
+                nodes
+                    .iter()
+                    .map(|node| node.to_filter(names, errors))
+                    .collect(),
+            ),
+            Self::Not(node) => Filter::Not(Box::new(node.to_filter(names, errors))),
+            Self::Term {
+                field: None, value, ..
+            } => Filter::Keyword(value.clone()),
+            Self::Term {
+                field: Some((field, field_span)),
+                value,
+                value_span,
+            } => {
+                let filter = match field.to_lowercase().as_str() {
+                    "tag" => match names.tags.get(&value.to_lowercase()) {
+                        Some(tag_id) => Ok(Filter::Tag(*tag_id)),
+                        None => Err(format!("No tag named `{}`", value)),
+                    },
+                    "category" => match names.categories.get(&value.to_lowercase()) {
+                        Some(category_ids) => Ok(Filter::Any(
+                            category_ids.iter().cloned().map(Filter::Category).collect(),
+                        )),
+                        None => Err(format!("No category named `{}`", value)),
+                    },
+                    "author" => Ok(Filter::Author(value.clone())),
+                    "year" => parse_range(value).map(Filter::ReleaseYear),
+                    "pages" => parse_range(value).map(Filter::PageCount),
+                    "status" => match value.to_lowercase().as_str() {
+                        "reading" => Ok(Filter::Reading),
+                        "finished" => Ok(Filter::Finished),
+                        "bookmarked" => Ok(Filter::Bookmarked),
+                        "favorite" => Ok(Filter::Favorite),
+                        _ => Err(format!(
+                            "Unknown status `{}`, expected reading, finished, bookmarked or favorite",
+                            value
+                        )),
+                    },
+                    _ => {
+                        errors.push(QueryError::new(
+                            field_span.clone(),
+                            format!("Unknown field `{}`, expected {}", field, FIELDS),
+                        ));
+                        return Filter::All(vec![]);
+                    }
+                };
+
+                filter.unwrap_or_else(|message| {
+                    errors.push(QueryError::new(value_span.clone(), message));
+                    Filter::All(vec![])
+                })
+            }
+        }
+    }
+}
+
+/// The numbers of a `year` or `pages` value: `2010`, `>2010`, `<=50` or `2000..2010`.
+fn parse_range(value: &str) -> Result<NumberRange, String> {
+    let number = |text: &str| text.trim().parse::<i64>().ok();
+    let range = if let Some((min, max)) = value.split_once("..") {
+        // a bound left out is `Some(None)`, one that isn't a number `None`
+        let bound = |text: &str| match text.is_empty() {
+            true => Some(None),
+            false => number(text).map(Some),
+        };
+        match (bound(min), bound(max)) {
+            (Some(None), Some(None)) => None,
+            (Some(min), Some(max)) => Some(NumberRange { min, max }),
+            _ => None,
+        }
+    } else if let Some(min) = value.strip_prefix(">=") {
+        number(min).map(|min| NumberRange {
+            min: Some(min),
+            max: None,
+        })
+    } else if let Some(max) = value.strip_prefix("<=") {
+        number(max).map(|max| NumberRange {
+            min: None,
+            max: Some(max),
+        })
+    } else if let Some(min) = value.strip_prefix('>') {
+        number(min)
+            .and_then(|min| min.checked_add(1))
+            .map(|min| NumberRange {
+                min: Some(min),
+                max: None,
+            })
+    } else if let Some(max) = value.strip_prefix('<') {
+        number(max)
+            .and_then(|max| max.checked_sub(1))
+            .map(|max| NumberRange {
+                min: None,
+                max: Some(max),
+            })
+    } else {
+        number(value.strip_prefix('=').unwrap_or(value)).map(|number| NumberRange {
+            min: Some(number),
+            max: Some(number),
+        })
+    };
+
+    range.ok_or_else(|| {
+        format!(
+            "Expected a number like `2010`, a comparison like `>2010` or a range like `2000..2010`, not `{}`",
+            value
+        )
+    })
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct QueryCheckQuery {
+    /// The query as typed so far.
+    pub q: String,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueryCheckResponseBody {
+    /// What the query filters, `None` when it has errors.
+    pub filter: Option<Filter>,
+    pub errors: Vec<QueryError>,
+}
+
+/// Check a query for the `query` field of filters, to point out its errors while it's typed.
+#[utoipa::path(get, path = "/api/index/query/check", params(QueryCheckQuery), responses(
+    (status = 200, description = "Check query successful", body = QueryCheckResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_query_check(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<QueryCheckQuery>,
+) -> Result<Response, AppError> {
+    let names = find_query_names(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags and categories: {}", e)))?;
+
+    let body = match parse_query(&query.q, &names) {
+        Ok(filter) => QueryCheckResponseBody {
+            filter: Some(filter),
+            errors: vec![],
+        },
+        Err(errors) => QueryCheckResponseBody {
+            filter: None,
+            errors,
+        },
+    };
+
+    Ok((StatusCode::OK, Json(body)).into_response())
+}
+
+#[cfg(test)]
+mod tests_query_syntax {
+    use super::*;
+
+    fn names() -> QueryNames {
+        QueryNames {
+            tags: HashMap::from([("romance".to_string(), 1), ("horror".to_string(), 2)]),
+            categories: HashMap::from([("manga".to_string(), vec!["m".to_string()])]),
+        }
+    }
+
+    fn parse(query: &str) -> Result<Filter, Vec<QueryError>> {
+        parse_query(query, &names())
+    }
+
+    fn error(query: &str) -> (Range<usize>, String) {
+        let errors = parse(query).unwrap_err();
+        (errors[0].start..errors[0].end, errors[0].message.clone())
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            parse(
+                r#"tag:romance -tag:Horror author:"Eiichiro Oda" year:>2010 status:reading pages:<50"#
+            ),
+            Ok(Filter::All(vec![
+                Filter::Tag(1),
+                Filter::Not(Box::new(Filter::Tag(2))),
+                Filter::Author("Eiichiro Oda".to_string()),
+                Filter::ReleaseYear(NumberRange {
+                    min: Some(2011),
+                    max: None,
+                }),
+                Filter::Reading,
+                Filter::PageCount(NumberRange {
+                    min: None,
+                    max: Some(49),
+                }),
+            ]))
+        );
+        assert_eq!(
+            parse("category:MANGA"),
+            Ok(Filter::Any(vec![Filter::Category("m".to_string())]))
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(parse("  "), Ok(Filter::All(vec![])));
+        assert_eq!(
+            parse(r#"one "two words" "re:zero""#),
+            Ok(Filter::All(vec![
+                Filter::Keyword("one".to_string()),
+                Filter::Keyword("two words".to_string()),
+                Filter::Keyword("re:zero".to_string()),
+            ]))
+        );
+        // a word with a colon at its start is searched for as is
+        assert_eq!(
+            parse(":)"),
+            Err(vec![QueryError::new(1..2, "Unmatched `)`")])
+        );
+        assert_eq!(parse(":3"), Ok(Filter::Keyword(":3".to_string())));
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            parse("tag:romance OR tag:horror status:finished"),
+            Ok(Filter::Any(vec![
+                Filter::Tag(1),
+                Filter::All(vec![Filter::Tag(2), Filter::Finished]),
+            ]))
+        );
+        assert_eq!(
+            parse("-(tag:romance OR status:favorite) pages:10..20"),
+            Ok(Filter::All(vec![
+                Filter::Not(Box::new(Filter::Any(vec![
+                    Filter::Tag(1),
+                    Filter::Favorite,
+                ]))),
+                Filter::PageCount(NumberRange {
+                    min: Some(10),
+                    max: Some(20),
+                }),
+            ]))
+        );
+        // `or` in lowercase is a keyword
+        assert_eq!(
+            parse("a or b"),
+            Ok(Filter::All(vec![
+                Filter::Keyword("a".to_string()),
+                Filter::Keyword("or".to_string()),
+                Filter::Keyword("b".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        for (value, min, max) in [
+            ("2010", Some(2010), Some(2010)),
+            ("=2010", Some(2010), Some(2010)),
+            (">=2010", Some(2010), None),
+            ("<=2010", None, Some(2010)),
+            ("<2010", None, Some(2009)),
+            ("2000..2010", Some(2000), Some(2010)),
+            ("..2010", None, Some(2010)),
+            ("2000..", Some(2000), None),
+        ] {
+            assert_eq!(
+                parse_range(value),
+                Ok(NumberRange { min, max }),
+                "{}",
+                value
+            );
+        }
+        for value in ["", "..", "2k", ">", "1..x", "x..1"] {
+            assert!(parse_range(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            error(r#"tag:romance "oda"#),
+            (12..16, "Unclosed quote".to_string())
+        );
+        assert_eq!(
+            error("author: oda"),
+            (0..7, "Missing value after `author:`".to_string())
+        );
+        assert_eq!(
+            error("(tag:romance"),
+            (0..1, "Missing `)` for this `(`".to_string())
+        );
+        assert_eq!(error("tag:romance)"), (11..12, "Unmatched `)`".to_string()));
+        assert_eq!(
+            error("OR oda"),
+            (0..2, "Expected a term before `OR`".to_string())
+        );
+        assert_eq!(
+            error("oda OR"),
+            (4..6, "Expected a term after `OR`".to_string())
+        );
+        assert_eq!(
+            error("oda - one"),
+            (4..5, "Expected a term after `-`".to_string())
+        );
+        assert_eq!(error("()"), (0..1, "Expected a term after `(`".to_string()));
+    }
+
+    #[test]
+    fn all_value_errors() {
+        // positions are in characters
+        let errors = parse("「漫画」 tags:romance tag:comedy year:soon status:new").unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.start, error.end))
+                .collect::<Vec<_>>(),
+            [(5, 9), (22, 28), (34, 38), (46, 49)]
+        );
+        assert_eq!(
+            errors[0].message,
+            format!("Unknown field `tags`, expected {}", FIELDS)
+        );
+        assert_eq!(errors[1].message, "No tag named `comedy`");
+        assert_eq!(errors[1].to_string(), "No tag named `comedy` at 22..28");
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let nested = |open: &str, depth: usize| {
+            let close = if open == "(" { ")" } else { "" };
+            format!("{}oda{}", open.repeat(depth), close.repeat(depth))
+        };
+        assert!(parse(&nested("(", MAX_QUERY_DEPTH)).is_ok());
+        assert!(parse(&nested("-", MAX_QUERY_DEPTH)).is_ok());
+
+        // the offending `(` or `-` is the first one past the limit
+        let message = format!("More than {} nested `(` and `-`", MAX_QUERY_DEPTH);
+        assert_eq!(
+            error(&nested("(", MAX_QUERY_DEPTH + 1)),
+            (32..33, message.clone())
+        );
+        assert_eq!(error(&"(".repeat(300_000)), (32..33, message.clone()));
+        assert_eq!(error(&"-(".repeat(150_000)), (32..33, message.clone()));
+        assert_eq!(
+            error(&format!("{}oda", "-".repeat(300_000))),
+            (32..33, message)
+        );
+    }
+}
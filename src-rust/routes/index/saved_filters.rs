@@ -0,0 +1,459 @@
+use std::sync::Arc;
+
+use super::{filter_response, find_query_names, parse_query, FilterRequestBody};
+use crate::{models::prelude::*, AppError, AppState, GenericResponseBody};
+
+use axum::{
+    extract::{rejection::JsonRejection, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QueryTrait, Set,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use ts_rs::TS;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SavedFilterRequestBody {
+    pub name: String,
+    /// Saved without its cursor.
+    pub filter: FilterRequestBody,
+    /// Show it on the home screen as a smart collection, `false` when left out.
+    pub pinned: Option<bool>,
+}
+
+#[derive(Debug, Clone, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SavedFilterResponseBody {
+    pub id: String,
+    pub name: String,
+    pub filter: FilterRequestBody,
+    pub pinned: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl TryFrom<saved_filters::Model> for SavedFilterResponseBody {
+    type Error = serde_json::Error;
+
+    fn try_from(saved: saved_filters::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            filter: serde_json::from_str(&saved.filter)?,
+            id: saved.id.to_string(),
+            name: saved.name,
+            pinned: saved.pinned,
+            created_at: saved.created_at,
+            updated_at: saved.updated_at,
+        })
+    }
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SavedFiltersResponseBody {
+    pub data: Vec<SavedFilterResponseBody>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct SavedFiltersQuery {
+    /// `true` for the smart collections of the home screen only.
+    pub pinned: Option<bool>,
+}
+
+/// Overrides of the saved filter when listing its titles.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct SavedFilterTitlesQuery {
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub facets: Option<bool>,
+}
+
+/// List the saved filters of the user, pinned ones first, then by name.
+///
+/// Filters saved in a form that can't be read anymore are left out.
+#[utoipa::path(get, path = "/api/index/saved", params(SavedFiltersQuery), responses(
+    (status = 200, description = "Fetch saved filters successful", body = SavedFiltersResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_saved_filters(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Query(query): Query<SavedFiltersQuery>,
+) -> Result<Response, AppError> {
+    let saved_models = SavedFilters::find()
+        .filter(saved_filters::Column::UserId.eq(&user.id))
+        .apply_if(query.pinned, |select, pinned| {
+            select.filter(saved_filters::Column::Pinned.eq(pinned))
+        })
+        .order_by_desc(saved_filters::Column::Pinned)
+        .order_by_asc(saved_filters::Column::Name)
+        .all(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find saved filters: {}", e)))?;
+
+    let data = readable_responses(saved_models);
+
+    Ok((StatusCode::OK, Json(SavedFiltersResponseBody { data })).into_response())
+}
+
+/// Save a filter under a name.
+#[utoipa::path(post, path = "/api/index/saved", request_body = SavedFilterRequestBody, responses(
+    (status = 200, description = "Save filter successful", body = SavedFilterResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn post_saved_filter(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    body: Result<Json<SavedFilterRequestBody>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let body = match body {
+        Ok(Json(body)) => body,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.body_text()).into_response()),
+    };
+    if let Err(e) = check_name(&app_state.db, &body.name, &user.id, None).await? {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    if let Err(e) = check_query(&app_state.db, &body.filter).await? {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let saved = saved_filters::ActiveModel {
+        id: Set(CustomID::new()),
+        user_id: Set(user.id),
+        name: Set(body.name.trim().to_string()),
+        filter: Set(saved_json(body.filter)?),
+        pinned: Set(body.pinned.unwrap_or_default()),
+        created_at: Set(now.clone()),
+        updated_at: Set(now),
+    }
+    .insert(&app_state.db)
+    .await
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't insert saved filter: {}", e)))?;
+
+    saved_response(saved)
+}
+
+/// Replace the name, filter and pin of a saved filter.
+#[utoipa::path(put, path = "/api/index/saved/{id}", request_body = SavedFilterRequestBody, responses(
+    (status = 200, description = "Update saved filter successful", body = SavedFilterResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Saved filter not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn put_saved_filter(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Path(id): Path<String>,
+    body: Result<Json<SavedFilterRequestBody>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let body = match body {
+        Ok(Json(body)) => body,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.body_text()).into_response()),
+    };
+    let saved = match find_saved_filter(&app_state.db, id, &user.id).await? {
+        Some(saved) => saved,
+        None => return Ok((StatusCode::NOT_FOUND, "Saved filter not found.").into_response()),
+    };
+    if let Err(e) = check_name(&app_state.db, &body.name, &user.id, Some(&saved.id)).await? {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    if let Err(e) = check_query(&app_state.db, &body.filter).await? {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+
+    let mut active_saved: saved_filters::ActiveModel = saved.into();
+    active_saved.name = Set(body.name.trim().to_string());
+    active_saved.filter = Set(saved_json(body.filter)?);
+    active_saved.pinned = Set(body.pinned.unwrap_or_default());
+    active_saved.updated_at = Set(chrono::Utc::now().to_rfc3339());
+    let saved = active_saved
+        .update(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't update saved filter: {}", e)))?;
+
+    saved_response(saved)
+}
+
+#[utoipa::path(delete, path = "/api/index/saved/{id}", responses(
+    (status = 200, description = "Delete saved filter successful", body = GenericResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Saved filter not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn delete_saved_filter(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let saved = match find_saved_filter(&app_state.db, id, &user.id).await? {
+        Some(saved) => saved,
+        None => return Ok((StatusCode::NOT_FOUND, "Saved filter not found.").into_response()),
+    };
+
+    SavedFilters::delete_by_id(saved.id)
+        .exec(&app_state.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't delete saved filter: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("Delete saved filter successful.")),
+    )
+        .into_response())
+}
+
+/// List the titles of a saved filter, as `/api/index/filter` does with it.
+///
+/// The filter runs against the library as it is now, so a pinned smart collection picks up
+/// new titles and progress as they come.
+#[utoipa::path(get, path = "/api/index/saved/{id}/titles", params(SavedFilterTitlesQuery), responses(
+    (status = 200, description = "Fetch all items successful", body = FilterResponseBody),
+    (status = 204, description = "Fetch all items successful, but none were found", body = FilterResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 404, description = "Saved filter not found", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn get_saved_filter_titles(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Path(id): Path<String>,
+    Query(query): Query<SavedFilterTitlesQuery>,
+) -> Result<Response, AppError> {
+    let saved = match find_saved_filter(&app_state.db, id, &user.id).await? {
+        Some(saved) => saved,
+        None => return Ok((StatusCode::NOT_FOUND, "Saved filter not found.").into_response()),
+    };
+
+    let filter = saved_query(&saved, query)
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't read saved filter: {}", e)))?;
+
+    filter_response(&app_state, &filter, &user.id).await
+}
+
+/// The saved filter `id` of the user, `None` when it's someone else's.
+async fn find_saved_filter(
+    db: &DatabaseConnection,
+    id: String,
+    user_id: &UserID,
+) -> Result<Option<saved_filters::Model>, AppError> {
+    let Ok(id) = SavedFilterID::from(id) else {
+        return Ok(None);
+    };
+
+    SavedFilters::find_by_id(id)
+        .filter(saved_filters::Column::UserId.eq(user_id))
+        .one(db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find saved filter: {}", e)))
+}
+
+/// `Err` with the reason when `name` can't be used for a new filter, or to rename `renamed`.
+async fn check_name(
+    db: &DatabaseConnection,
+    name: &str,
+    user_id: &UserID,
+    renamed: Option<&SavedFilterID>,
+) -> Result<Result<(), &'static str>, AppError> {
+    if name.trim().is_empty() {
+        return Ok(Err("Name can't be empty."));
+    }
+
+    let taken = SavedFilters::find()
+        .filter(
+            Condition::all()
+                .add(saved_filters::Column::UserId.eq(user_id))
+                .add(saved_filters::Column::Name.eq(name.trim()))
+                .add_option(renamed.map(|id| saved_filters::Column::Id.ne(id))),
+        )
+        .one(db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find saved filter: {}", e)))?;
+
+    match taken {
+        Some(_) => Ok(Err("A saved filter with this name already exists.")),
+        None => Ok(Ok(())),
+    }
+}
+
+/// `Err` with the reason when the typed `query` of `filter` doesn't parse, it would fail every
+/// time the saved filter runs.
+async fn check_query(
+    db: &DatabaseConnection,
+    filter: &FilterRequestBody,
+) -> Result<Result<(), String>, AppError> {
+    let Some(text) = &filter.query else {
+        return Ok(Ok(()));
+    };
+
+    let names = find_query_names(db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't find tags and categories: {}", e)))?;
+    match parse_query(text, &names) {
+        Ok(_) => Ok(Ok(())),
+        Err(errors) => {
+            let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            Ok(Err(format!("Invalid query: {}.", errors.join(", "))))
+        }
+    }
+}
+
+/// The responses of the saved filters that can still be read, e.g. not the ones saved before
+/// a sort was renamed.
+fn readable_responses(saved_models: Vec<saved_filters::Model>) -> Vec<SavedFilterResponseBody> {
+    saved_models
+        .into_iter()
+        .filter_map(|saved| {
+            let id = saved.id.clone();
+            SavedFilterResponseBody::try_from(saved)
+                .map_err(|e| warn!("can't read saved filter {}: {}", id, e))
+                .ok()
+        })
+        .collect()
+}
+
+/// The JSON a filter is saved as, cursors only make sense for the listing they come from.
+fn saved_json(filter: FilterRequestBody) -> Result<String, AppError> {
+    serde_json::to_string(&FilterRequestBody {
+        cursor: None,
+        ..filter
+    })
+    .map_err(|e| AppError::from(anyhow::anyhow!("Can't serialize filter: {}", e)))
+}
+
+fn saved_response(saved: saved_filters::Model) -> Result<Response, AppError> {
+    let saved = SavedFilterResponseBody::try_from(saved)
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't read saved filter: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(saved)).into_response())
+}
+
+/// The request a saved filter lists its titles with, `query` overriding what was saved.
+fn saved_query(
+    saved: &saved_filters::Model,
+    query: SavedFilterTitlesQuery,
+) -> Result<FilterRequestBody, serde_json::Error> {
+    let filter = serde_json::from_str::<FilterRequestBody>(&saved.filter)?;
+
+    Ok(FilterRequestBody {
+        limit: query.limit.or(filter.limit),
+        cursor: query.cursor,
+        facets: query.facets.or(filter.facets),
+        ..filter
+    })
+}
+
+#[cfg(test)]
+mod tests_saved_filters {
+    use super::*;
+    use crate::routes::{tests_filter::library, Filter, SortBy};
+
+    fn saved(filter: FilterRequestBody) -> saved_filters::Model {
+        saved_filters::Model {
+            id: CustomID::new(),
+            user_id: CustomID::new(),
+            name: "Unread comedies".to_string(),
+            filter: saved_json(filter).unwrap(),
+            pinned: true,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn saved_without_cursor() {
+        let filter = FilterRequestBody {
+            tag_ids: Some(vec![2]),
+            filter: Some(Filter::Not(Box::new(Filter::Finished))),
+            sort_by: Some(SortBy::LastRead),
+            cursor: Some("7b7d".to_string()),
+            ..Default::default()
+        };
+
+        let response = SavedFilterResponseBody::try_from(saved(filter.clone())).unwrap();
+        assert_eq!(response.filter.cursor, None);
+        assert_eq!(response.filter.tag_ids, filter.tag_ids);
+        assert_eq!(response.filter.filter, filter.filter);
+        assert_eq!(response.filter.sort_by, filter.sort_by);
+    }
+
+    #[test]
+    fn listed_with_overrides() {
+        let saved = saved(FilterRequestBody {
+            limit: Some(20),
+            sort_by: Some(SortBy::AddDate),
+            ..Default::default()
+        });
+
+        let query = saved_query(&saved, SavedFilterTitlesQuery::default()).unwrap();
+        assert_eq!(
+            (query.limit, query.cursor, query.facets),
+            (Some(20), None, None)
+        );
+
+        let overrides = SavedFilterTitlesQuery {
+            limit: Some(5),
+            cursor: Some("7b7d".to_string()),
+            facets: Some(true),
+        };
+        let query = saved_query(&saved, overrides).unwrap();
+        assert_eq!(
+            (query.limit, query.cursor, query.facets),
+            (Some(5), Some("7b7d".to_string()), Some(true))
+        );
+        assert_eq!(query.sort_by, Some(SortBy::AddDate));
+    }
+
+    #[test]
+    fn unreadable_left_out() {
+        let mut renamed = saved(FilterRequestBody::default());
+        renamed.filter = r#"{"sort_by":"popularity"}"#.to_string();
+        let readable = saved(FilterRequestBody {
+            sort_by: Some(SortBy::AddDate),
+            ..Default::default()
+        });
+
+        let responses = readable_responses(vec![renamed, readable.clone()]);
+        assert_eq!(
+            responses.iter().map(|saved| &saved.id).collect::<Vec<_>>(),
+            [&readable.id.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_query() {
+        let db = &library().await.db;
+        let check = |query: &str| FilterRequestBody {
+            query: Some(query.to_string()),
+            ..Default::default()
+        };
+
+        let checked = check_query(db, &check("tag:action -status:finished")).await;
+        assert_eq!(checked.unwrap(), Ok(()));
+        let checked = check_query(db, &check("tag:romance")).await;
+        assert_eq!(
+            checked.unwrap(),
+            Err("Invalid query: No tag named `romance` at 4..11.".to_string())
+        );
+        let checked = check_query(db, &check("(tag:action")).await;
+        assert!(checked.unwrap().is_err());
+        assert_eq!(
+            check_query(db, &FilterRequestBody::default())
+                .await
+                .unwrap(),
+            Ok(())
+        );
+    }
+}
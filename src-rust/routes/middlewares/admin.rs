@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use crate::{models::prelude::*, AppState};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+/// Only let the users listed in `ADMIN_USERNAMES` through. Must run after `auth`.
+pub async fn admin(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    match data.config.admin_usernames.contains(&user.username) {
+        true => next.run(req).await,
+        false => (StatusCode::FORBIDDEN, "Admin only.").into_response(),
+    }
+}
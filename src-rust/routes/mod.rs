@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod auth;
 pub mod file;
 pub mod index;
@@ -5,10 +6,17 @@ pub mod middlewares;
 pub mod user;
 pub mod utils;
 
-pub use self::{auth::*, file::*, index::*, user::*, utils::*};
-pub use middlewares::auth::auth;
-
-use crate::{config::Config, models::categories::Model as Categories};
+pub use self::{admin::*, auth::*, file::*, index::*, user::*, utils::*};
+pub use middlewares::{admin::admin, auth::auth};
+
+use crate::{
+    config::Config,
+    imaging::EinkProfile,
+    models::{
+        categories::Model as Categories,
+        prelude::{PersonRole, ReadingDirection},
+    },
+};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use serde::{Deserialize, Serialize};
 use utoipa::{OpenApi, ToSchema};
@@ -39,6 +47,10 @@ use utoipa::{OpenApi, ToSchema};
         (
             name = "file",
             description = "all the routes related to file fetching."
+        ),
+        (
+            name = "admin",
+            description = "library maintenance, for the users in ADMIN_USERNAMES."
         )
     ),
     paths(
@@ -59,18 +71,41 @@ use utoipa::{OpenApi, ToSchema};
         user::put_bookmark,
         user::put_favorite,
         user::put_progress,
+        user::get_eink,
+        user::put_eink,
+        user::delete_eink,
 
         index::get_categories,
         index::post_filter,
+        index::get_search,
+        index::get_suggest,
         index::get_title,
+        index::get_saved_filters,
+        index::post_saved_filter,
+        index::put_saved_filter,
+        index::delete_saved_filter,
+        index::get_saved_filter_titles,
+        index::get_query_check,
+        index::get_person,
+        index::get_person_titles,
 
         utils::get_status,
         utils::post_status,
         utils::get_tags,
         utils::get_scanning_progress,
+        utils::get_archive_cache,
 
         file::get_page,
         file::get_cover,
+        file::get_category_cover,
+        file::get_batch,
+        file::get_download,
+
+        admin::get_duplicates,
+        admin::post_resolve_duplicate,
+        admin::delete_duplicate,
+        admin::put_cover,
+        admin::post_cover,
     ),
     components(schemas(
         // Auth
@@ -82,14 +117,37 @@ use utoipa::{OpenApi, ToSchema};
         DeleteRequestBody,
         ModifyRequestBody,
         ResetRequestBody,
+        EinkProfile,
 
         // Index
         Categories,
         CategoriesResponseBody,
         TitleResponseBody,
         FilterRequestBody,
+        SortBy,
+        SortOrder,
+        Filter,
+        PersonFilter,
+        PersonRole,
+        NumberRange,
+        QueryCheckResponseBody,
+        QueryError,
         FilterResponseBody,
+        FilterFacets,
+        TagFacet,
+        CategoryFacet,
+        ValueFacet,
         FilterTitleResponseBody,
+        SearchResponseBody,
+        SearchTitleResponseBody,
+        SuggestResponseBody,
+        TitleSuggestion,
+        ReadingDirection,
+        SavedFilterRequestBody,
+        SavedFilterResponseBody,
+        SavedFiltersResponseBody,
+        PersonResponseBody,
+        TitlePersonResponse,
 
         // Utils
         StatusRequestBody,
@@ -98,7 +156,19 @@ use utoipa::{OpenApi, ToSchema};
         TagsMapResponseBody,
         TitleResponseBody,
         ScanningProgressResponseBody,
-
+        ArchiveCacheResponseBody,
+
+        // File
+        BatchFormat,
+        DownloadFormat,
+        SpreadHalf,
+
+        // Admin
+        DuplicatesResponseBody,
+        DuplicateResponseBody,
+        DuplicateTitleResponseBody,
+        ResolveDuplicateRequestBody,
+        CoverRequestBody,
 
         // Other
         GenericResponseBody,
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::{imaging::EinkProfile, models::prelude::*, AppError, AppState, GenericResponseBody};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{ActiveModelTrait, Set};
+
+/// Get the saved e-ink profile, or the default one if none is saved.
+#[utoipa::path(get, path = "/api/user/eink", responses(
+    (status = 200, description = "Fetch e-ink profile successful", body = EinkProfile),
+    (status = 401, description = "Unauthorized", body = String),
+))]
+pub async fn get_eink(Extension(user): Extension<users::Model>) -> Result<Response, AppError> {
+    let profile = user
+        .eink_profile
+        .as_deref()
+        .and_then(|profile| serde_json::from_str::<EinkProfile>(profile).ok())
+        .unwrap_or_default();
+
+    Ok((StatusCode::OK, Json(profile)).into_response())
+}
+
+/// Save an e-ink profile, pages fetched with `?eink=true` are then rendered with it.
+#[utoipa::path(put, path = "/api/user/eink", request_body = EinkProfile, responses(
+    (status = 200, description = "Save e-ink profile successful", body = GenericResponseBody),
+    (status = 400, description = "Bad request", body = String),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn put_eink(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+    Json(body): Json<EinkProfile>,
+) -> Result<Response, AppError> {
+    if let Err(e) = body.validate() {
+        return Ok((StatusCode::BAD_REQUEST, e).into_response());
+    }
+
+    let profile = serde_json::to_string(&body)
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't serialize profile: {}", e)))?;
+
+    let mut active_user: users::ActiveModel = user.into();
+    active_user.eink_profile = Set(Some(profile));
+    active_user
+        .update(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't update user: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("E-ink profile saved.")),
+    )
+        .into_response())
+}
+
+/// Remove the saved e-ink profile, pages fetched with `?eink=true` then use the default one.
+#[utoipa::path(delete, path = "/api/user/eink", responses(
+    (status = 200, description = "Remove e-ink profile successful", body = GenericResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+    (status = 500, description = "Internal server error", body = String)
+))]
+pub async fn delete_eink(
+    State(data): State<Arc<AppState>>,
+    Extension(user): Extension<users::Model>,
+) -> Result<Response, AppError> {
+    let mut active_user: users::ActiveModel = user.into();
+    active_user.eink_profile = Set(None);
+    active_user
+        .update(&data.db)
+        .await
+        .map_err(|e| AppError::from(anyhow::anyhow!("Can't update user: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GenericResponseBody::new("E-ink profile removed.")),
+    )
+        .into_response())
+}
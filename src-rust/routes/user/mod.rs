@@ -1,4 +1,5 @@
 mod delete;
+mod eink;
 mod favorite_bookmark;
 mod get_check;
 mod modify;
@@ -17,6 +18,7 @@ use lettre::{
 use tracing::warn;
 
 pub use delete::*;
+pub use eink::*;
 pub use favorite_bookmark::*;
 pub use get_check::*;
 pub use modify::*;
@@ -10,6 +10,10 @@ use axum::{
 };
 use sea_orm::{ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, QueryFilter, Set};
 
+/// Set the reading progress of a title.
+///
+/// `page` is an index in the pages of `/api/index/title/{title_id}`, where each slice of a
+/// long strip counts as a page.
 #[utoipa::path(put, path = "/api/user/progress/{title_id}/{page}", responses(
     (status = 200, description = "Set progress successfully", body = GenericResponseBody),
     (status = 400, description = "Bad request", body = String),
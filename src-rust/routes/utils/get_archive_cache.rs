@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::{AppError, AppState};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utoipa::ToSchema;
+
+#[derive(Debug, ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ArchiveCacheResponseBody {
+    /// Maximum number of archive indexes kept in memory, 0 when the cache is disabled.
+    pub capacity: usize,
+    /// Number of archive indexes currently in memory.
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Get the hit/miss statistics of the archive index cache.
+#[utoipa::path(get, path = "/api/utils/archive_cache", responses(
+    (status = 200, description = "Archive cache statistics.", body = ArchiveCacheResponseBody),
+    (status = 401, description = "Unauthorized", body = String),
+))]
+pub async fn get_archive_cache(State(data): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let stats = data.archive_cache.stats().await;
+
+    Ok((
+        StatusCode::OK,
+        Json(ArchiveCacheResponseBody {
+            capacity: stats.capacity,
+            size: stats.size,
+            hits: stats.hits,
+            misses: stats.misses,
+            evictions: stats.evictions,
+        }),
+    )
+        .into_response())
+}
@@ -1,7 +1,9 @@
+mod get_archive_cache;
 mod get_scanning_progress;
 mod get_tags;
 mod status;
 
+pub use get_archive_cache::*;
 pub use get_scanning_progress::*;
 pub use get_tags::*;
 pub use status::*;
@@ -12,13 +12,16 @@ const FilterSortBy: FilterTypePosibleVal = {
 	AddDate: { name: "add date", icon: "calendar-plus" },
 	ReleaseDate: { name: "release date", icon: "calendar" },
 	UpdateDate: { name: "update date", icon: "calendar-clock" },
-
-	// LastRead: { name: "last read", icon: "calendar-check" },
+	LastRead: { name: "last read", icon: "calendar-check" },
+	FavoriteCount: { name: "favorite count", icon: "heart" },
+	BookmarkCount: { name: "bookmark count", icon: "bookmark" },
+	PageCount: { name: "page count", icon: "book" },
+	Random: { name: "random", icon: "shuffle" },
 };
 
 const FilterSortOrder: FilterTypePosibleVal = {
-	Ascending: { name: "ascending | newest", icon: "arrow-down-a-z" },
-	Descending: { name: "descending | oldest", icon: "arrow-up-z-a" },
+	Ascending: { name: "ascending", icon: "arrow-down-a-z" },
+	Descending: { name: "descending", icon: "arrow-up-z-a" },
 };
 
 enum FilterType {
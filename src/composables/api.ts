@@ -9,6 +9,7 @@ import type {
 	CategoryItemServerResponse,
 	CategoryServerResponse,
 	CategoriesFnResponse,
+	StatusFilter,
 } from "./api/index";
 import type { SsimEvalServerResponse, SsimEvalTitleServerResponse } from "./api/utils";
 
@@ -27,6 +28,7 @@ export type {
 	CategoryItemServerResponse,
 	CategoryServerResponse,
 	CategoriesFnResponse,
+	StatusFilter,
 	SsimEvalServerResponse,
 	SsimEvalTitleServerResponse,
 };
@@ -34,11 +34,16 @@ async function categories(): Promise<{ data?: Array<CategoryResponse>; message?:
 	return { message: GenericResponseBody.from_bitcode(buffer).message };
 }
 
+/** Statuses of the current user, as `filter` takes them. */
+type StatusFilter = "reading" | "finished" | "bookmarked" | "favorite";
+
 async function filter(body: {
 	keywords?: Array<string>;
 	category_ids?: Array<string>;
 	tag_ids?: Array<number>;
+	filter?: { any: Array<StatusFilter> };
 	limit?: number;
+	cursor?: string;
 
 	is_reading?: boolean;
 	is_finished?: boolean;
@@ -69,6 +74,17 @@ async function filter(body: {
 	const data = FilterResponseBody.from_bitcode(buffer);
 
 	if (res.ok && data !== undefined) {
+		// Without a limit, every page is fetched
+		if (body.limit === undefined && data.next_cursor) {
+			const next = await filter({ ...body, cursor: data.next_cursor });
+
+			if (next.data === undefined) {
+				return next;
+			}
+
+			return { data: data.data.concat(next.data) };
+		}
+
 		return { data: data.data };
 	}
 
@@ -103,3 +119,4 @@ async function title(id: string): Promise<{ data?: TitleResponseBody; message?:
 }
 
 export default { categories, filter, title };
+export type { StatusFilter };